@@ -1,4 +1,4 @@
-use image::Rgba;
+use image::{ImageFormat, Rgba, RgbaImage};
 use slint_build::CompilerConfiguration;
 use std::{env, fs, path::Path};
 
@@ -7,15 +7,37 @@ const DARK_ACTIVE_PNG_ICON: &[u8] = include_bytes!("assets/icon-dark-active.png"
 const LIGHT_INACTIVE_PNG_ICON: &[u8] = include_bytes!("assets/icon-light-inactive.png");
 const LIGHT_ACTIVE_PNG_ICON: &[u8] = include_bytes!("assets/icon-light-active.png");
 
+const ERROR_BADGE_COLOR: Rgba<u8> = Rgba([229, 57, 53, 255]);
+
 fn convert(img: &[u8]) -> Result<Vec<u8>, image::ImageError> {
-    let img = image::load_from_memory(img)?;
-    let mut img = img.to_rgba8();
+    Ok(convert_image(image::load_from_memory(img)?.to_rgba8()))
+}
 
+fn convert_image(mut img: RgbaImage) -> Vec<u8> {
     for Rgba(pixel) in img.pixels_mut() {
         *pixel = u32::from_be_bytes(*pixel).rotate_right(8).to_be_bytes();
     }
 
-    Ok(img.into_raw())
+    img.into_raw()
+}
+
+/// Derives the error icon from the active one by drawing a badge into the bottom right corner.
+fn error_icon(img: &[u8]) -> Result<RgbaImage, image::ImageError> {
+    let mut img = image::load_from_memory(img)?.to_rgba8();
+    let radius = img.width() as f32 * 0.22;
+    let center_x = img.width() as f32 - radius - 1.0;
+    let center_y = img.height() as f32 - radius - 1.0;
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let distance_x = x as f32 + 0.5 - center_x;
+        let distance_y = y as f32 + 0.5 - center_y;
+
+        if distance_x * distance_x + distance_y * distance_y <= radius * radius {
+            *pixel = ERROR_BADGE_COLOR;
+        }
+    }
+
+    Ok(img)
 }
 
 fn main() {
@@ -49,12 +71,44 @@ fn main() {
             convert(LIGHT_ACTIVE_PNG_ICON).unwrap(),
         )
         .unwrap();
+        fs::write(
+            out_path.join("linux-dark-error-icon"),
+            convert_image(error_icon(DARK_ACTIVE_PNG_ICON).unwrap()),
+        )
+        .unwrap();
+        fs::write(
+            out_path.join("linux-light-error-icon"),
+            convert_image(error_icon(LIGHT_ACTIVE_PNG_ICON).unwrap()),
+        )
+        .unwrap();
     }
 
     if env::var_os("CARGO_CFG_TARGET_OS").unwrap() == "windows" {
+        let out_dir = &env::var_os("OUT_DIR").unwrap();
+        let out_path = Path::new(out_dir);
+
         embed_resource::compile("assets/icons.rc", embed_resource::NONE)
             .manifest_optional()
             .unwrap();
+
+        // The error icons are generated, so they live next to their own resource file in the output directory.
+        error_icon(DARK_ACTIVE_PNG_ICON)
+            .unwrap()
+            .save_with_format(out_path.join("icon-dark-error.ico"), ImageFormat::Ico)
+            .unwrap();
+        error_icon(LIGHT_ACTIVE_PNG_ICON)
+            .unwrap()
+            .save_with_format(out_path.join("icon-light-error.ico"), ImageFormat::Ico)
+            .unwrap();
+        fs::write(
+            out_path.join("error_icons.rc"),
+            "dark_error_icon ICON \"icon-dark-error.ico\"\n\
+             light_error_icon ICON \"icon-light-error.ico\"\n",
+        )
+        .unwrap();
+        embed_resource::compile(out_path.join("error_icons.rc"), embed_resource::NONE)
+            .manifest_optional()
+            .unwrap();
     }
 
     println!("cargo:rerun-if-changed=assets/icons.rc");
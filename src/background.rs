@@ -1,31 +1,54 @@
-use crate::config::RootConfig;
-use crate::osc_query::node::OscAccess;
-use crate::osc_query::service::{OscHostInfo, OscQueryServiceBuilder};
+use crate::config::{AvatarsConfig, NotificationCategory, RootConfig};
+use crate::osc_query::service::{OscHostInfo, OscQueryService};
 use crate::plugins::{ChannelManager, Plugin};
+use crate::tasks::avatars::AvatarsTask;
 use crate::tasks::broadcaster::BroadcasterTask;
+use crate::tasks::config_monitor::ConfigMonitorTask;
 use crate::tasks::config_writer::{ConfigWriterTask, WriteConfigRequest};
-use crate::tasks::orchestrate::{AppEvent, OrchestrateTask, UiEvent};
-use crate::tasks::osc_query::OscQueryTask;
+use crate::tasks::diagnostics::{DiagnosticsParams, DiagnosticsTask};
+use crate::tasks::inspector::InspectorTask;
+use crate::tasks::logs::LogsTask;
+use crate::tasks::metrics::MetricsTask;
+use crate::tasks::notifications::{Notification, NotificationAction, NotificationsTask};
+use crate::tasks::orchestrate::{AppEvent, OrchestrateParams, OrchestrateTask, UiEvent};
+use crate::tasks::osc_query::{osc_query_endpoints, OscQueryTask};
 use crate::tasks::osc_receiver::OscReceiverTask;
 use crate::tasks::osc_sender::OscSenderTask;
 use crate::tasks::plugin_manager::PluginManagerTask;
+use crate::tasks::profiles::{list_profiles, ProfilesTask};
+use crate::tasks::recorder::RecorderTask;
+use crate::tasks::restart::RestartingTask;
+use crate::tasks::setups::SetupsTask;
+use crate::tasks::simulator::SimulatorTask;
 use crate::tasks::tray::TrayTask;
 use crate::tasks::update_checker::UpdateCheckerTask;
 use crate::tasks::vrchat_monitor::VrchatMonitorTask;
-use crate::utils::config::ConfigHandle;
+use crate::utils::config::{ConfigHandle, ConfigRegistry, Secrets};
+use crate::utils::{crash, instance};
 use crate::AppWindow;
-use log::error;
+use flexi_logger::LoggerHandle;
+use interprocess::local_socket::Listener;
+use log::{error, warn};
 use slint::Weak;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::iter;
 use std::net::{TcpListener, UdpSocket};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::{broadcast, mpsc};
 use tokio::task::JoinHandle;
+use tokio::{join, select};
+use tokio_graceful_shutdown::errors::{GracefulShutdownError, SubsystemError};
 use tokio_graceful_shutdown::{IntoSubsystem, SubsystemBuilder, SubsystemHandle, Toplevel};
 
+/// More crashes than this within [`CRASH_WINDOW`] quit the application instead of restarting the background tasks.
+const MAX_RESTARTS: usize = 3;
+const CRASH_WINDOW: Duration = Duration::from_secs(10 * 60);
+
 fn get_available_tcp_port() -> anyhow::Result<u16> {
     let socket = TcpListener::bind("127.0.0.1:0")?;
     Ok(socket.local_addr()?.port())
@@ -36,142 +59,515 @@ fn get_available_udp_port() -> anyhow::Result<u16> {
     Ok(socket.local_addr()?.port())
 }
 
+#[derive(Clone)]
 pub struct RuntimeParams {
     osc_listener_port: u16,
     osc_query_port: u16,
     config: ConfigHandle<RootConfig>,
+    avatars_config: ConfigHandle<AvatarsConfig>,
     logs_dir: PathBuf,
+    data_dir: PathBuf,
     plugins: HashMap<&'static str, Arc<dyn Plugin>>,
-    config_writer_rx: mpsc::Receiver<WriteConfigRequest>,
+    config_registry: ConfigRegistry,
+    secrets: Secrets,
+    logger: LoggerHandle,
     app_window: Weak<AppWindow>,
-    ui_event_rx: mpsc::Receiver<UiEvent>,
     app_event_tx: mpsc::Sender<AppEvent>,
+}
+
+/// Receivers of events sent from outside the background tasks, which have to outlive a restart of them.
+pub struct EventReceivers {
+    config_writer_rx: mpsc::Receiver<WriteConfigRequest>,
+    ui_event_rx: mpsc::Receiver<UiEvent>,
     app_event_rx: mpsc::Receiver<AppEvent>,
 }
 
-fn start_runtime(params: RuntimeParams) -> anyhow::Result<(Runtime, JoinHandle<()>)> {
-    let mut osc_query_service_builder = OscQueryServiceBuilder::new(OscHostInfo::new(
-        "VRC OSC Manager".to_string(),
-        "127.0.0.1".to_string(),
-        params.osc_listener_port,
-    ));
-    osc_query_service_builder.add_endpoint(
-        "/avatar/change".to_string(),
-        "s".to_string(),
-        OscAccess::Read,
-        "".to_string(),
-    );
+/// Forwards events to the current generation of background tasks until it stops receiving them.
+///
+/// An event which could not be handed over yet is kept in `pending`, so that it is forwarded to the next generation
+/// instead of being lost when the current one stops.
+async fn relay<T>(
+    rx: &mut mpsc::Receiver<T>,
+    tx: mpsc::Sender<T>,
+    pending: &mut Option<T>,
+    mut inspect: impl FnMut(&T),
+) {
+    loop {
+        if pending.is_none() {
+            let Some(event) = rx.recv().await else {
+                break;
+            };
+            inspect(&event);
+            *pending = Some(event);
+        }
+
+        let Ok(permit) = tx.reserve().await else {
+            break;
+        };
+
+        if let Some(event) = pending.take() {
+            permit.send(event);
+        }
+    }
+}
+
+/// Returns the crash report for a failure of the background tasks, writing one unless a panic already did.
+fn report_crash(logs_dir: &Path, error: &GracefulShutdownError) -> Option<PathBuf> {
+    let errors = error.get_subsystem_errors();
+
+    if errors
+        .iter()
+        .any(|error| matches!(error, SubsystemError::Panicked(_)))
+    {
+        if let Some(path) = crash::take_panic_report() {
+            return Some(path);
+        }
+    }
+
+    let details = errors
+        .iter()
+        .map(|error| match error {
+            SubsystemError::Failed(name, failure) => {
+                let error: &(dyn Error + 'static) = failure.get_error().as_ref();
+                let causes = iter::successors(Some(error), |error| (*error).source())
+                    .map(|error| error.to_string())
+                    .collect::<Vec<_>>();
+
+                format!("{}: {}", name, causes.join(": "))
+            }
+            SubsystemError::Panicked(name) => format!("{}: panicked", name),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
 
-    for plugin in params.plugins.values() {
-        plugin.register_osc_parameters(&mut osc_query_service_builder);
+    match crash::write_report(logs_dir, "Background tasks crashed", &details) {
+        Ok(path) => Some(path),
+        Err(error) => {
+            error!("Failed to write crash report: {:#}", error);
+            None
+        }
     }
+}
 
-    let osc_query_service = osc_query_service_builder.build();
+fn start_runtime(
+    params: RuntimeParams,
+    mut receivers: EventReceivers,
+) -> anyhow::Result<(Runtime, JoinHandle<()>)> {
+    let parameter_mappings = params.config.blocking_read().parameters.clone();
+    let osc_query_service = OscQueryService::new(
+        OscHostInfo::new(
+            "VRC OSC Manager".to_string(),
+            "127.0.0.1".to_string(),
+            params.osc_listener_port,
+        ),
+        osc_query_endpoints(&params.plugins, &parameter_mappings),
+    );
 
     let runtime = Runtime::new()?;
     let _guard = runtime.enter();
 
     let join_handle = runtime.spawn(async move {
-        let osc_target_port = params.config.read().await.osc.send_port;
+        let mut crashes: VecDeque<Instant> = VecDeque::new();
+        let mut crash_report = None;
+        let mut pending_config_write = None;
+        let mut pending_ui_event = None;
+        let mut pending_app_event = None;
 
-        let (plugin_manager_tx, plugin_manager_rx) = mpsc::channel(1);
-        let (osc_receiver_tx, _) = broadcast::channel(64);
-        let (osc_sender_tx, osc_sender_rx) = mpsc::channel(16);
-        let (tray_property_tx, tray_property_rx) = mpsc::channel(1);
+        loop {
+            let (config_writer_tx, config_writer_rx) = mpsc::channel(8);
+            let (ui_event_tx, ui_event_rx) = mpsc::channel(8);
+            let (app_event_tx, app_event_rx) = mpsc::channel(8);
+            let mut shutdown_requested = false;
 
-        let dark_mode = match dark_light::detect() {
-            Ok(dark_light::Mode::Dark | dark_light::Mode::Unspecified) | Err(_) => true,
-            Ok(dark_light::Mode::Light) => false,
-        };
+            let mut tasks = pin!(run_tasks(
+                params.clone(),
+                EventReceivers {
+                    config_writer_rx,
+                    ui_event_rx,
+                    app_event_rx,
+                },
+                osc_query_service.clone(),
+                crash_report.take(),
+            ));
+            let relays = async {
+                join!(
+                    relay(
+                        &mut receivers.config_writer_rx,
+                        config_writer_tx,
+                        &mut pending_config_write,
+                        |_| {}
+                    ),
+                    relay(
+                        &mut receivers.ui_event_rx,
+                        ui_event_tx,
+                        &mut pending_ui_event,
+                        |_| {}
+                    ),
+                    relay(
+                        &mut receivers.app_event_rx,
+                        app_event_tx,
+                        &mut pending_app_event,
+                        |event| {
+                            shutdown_requested |= matches!(event, AppEvent::ShutdownRequested);
+                        }
+                    ),
+                )
+            };
 
-        let channel_manager = ChannelManager::new(osc_receiver_tx.clone(), osc_sender_tx);
+            let result = select! {
+                result = &mut tasks => result,
+                _ = relays => tasks.await,
+            };
 
-        let orchestrate_task = OrchestrateTask::new(
-            params.app_event_rx,
-            params.ui_event_rx,
-            plugin_manager_tx,
-            tray_property_tx,
-            params.app_window,
-            params.config.clone(),
-            params.logs_dir,
-        );
-        let broadcaster_task =
-            BroadcasterTask::new(params.osc_listener_port, params.osc_query_port);
-        let config_writer_task = ConfigWriterTask::new(params.config_writer_rx);
-        let vrchat_monitor_task = VrchatMonitorTask::new(params.app_event_tx.clone());
-        let tray_task = TrayTask::new(tray_property_rx, params.app_event_tx.clone(), dark_mode);
-        let osc_query_task = OscQueryTask::new(params.osc_query_port, osc_query_service);
-        let osc_receiver_task = OscReceiverTask::new(params.osc_listener_port, osc_receiver_tx);
-        let osc_sender_task = OscSenderTask::new(osc_target_port, osc_sender_rx);
-        let plugin_manager_task = PluginManagerTask::new(
-            plugin_manager_rx,
-            params.config.clone(),
-            params.plugins,
-            channel_manager,
-        );
-        let update_checker_task =
-            match UpdateCheckerTask::new(params.app_event_tx.clone(), params.config) {
-                Ok(task) => Some(task),
-                Err(error) => {
-                    error!("Failed to initialize update checker: {}", error);
-                    None
-                }
+            let Err(error) = result else {
+                break;
             };
 
-        let result = Toplevel::new(async |s: &mut SubsystemHandle| {
-            s.start(SubsystemBuilder::new(
-                "Orchestrate",
-                orchestrate_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "Broadcaster",
-                broadcaster_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new("Tray", tray_task.into_subsystem()));
-            s.start(SubsystemBuilder::new(
-                "ConfigWriter",
-                config_writer_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "VrchatMonitor",
-                vrchat_monitor_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "OscQuery",
-                osc_query_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "OscReceiver",
-                osc_receiver_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "OscSender",
-                osc_sender_task.into_subsystem(),
-            ));
-            s.start(SubsystemBuilder::new(
-                "PluginManager",
-                plugin_manager_task.into_subsystem(),
-            ));
+            error!("Background process crashed: {}", error);
 
-            if let Some(task) = update_checker_task {
-                s.start(SubsystemBuilder::new(
-                    "UpdateChecker",
-                    task.into_subsystem(),
-                ));
+            if shutdown_requested {
+                break;
             }
-        })
-        .handle_shutdown_requests(Duration::from_millis(1000))
-        .await;
 
-        if let Err(error) = result {
-            slint::quit_event_loop().unwrap();
-            error!("Background process crashed: {}", error);
+            let now = Instant::now();
+            crashes.retain(|crashed_at| now.duration_since(*crashed_at) < CRASH_WINDOW);
+            crashes.push_back(now);
+            crash_report = report_crash(&params.logs_dir, &error);
+
+            if crashes.len() > MAX_RESTARTS {
+                error!(
+                    "Background process crashed {} times within {} minutes, giving up",
+                    crashes.len(),
+                    CRASH_WINDOW.as_secs() / 60
+                );
+                let _ = slint::quit_event_loop();
+                break;
+            }
+
+            warn!("Restarting background process");
         }
     });
 
     Ok((runtime, join_handle))
 }
 
+/// Runs one generation of the background tasks until they are shut down or one of them fails.
+async fn run_tasks(
+    params: RuntimeParams,
+    receivers: EventReceivers,
+    osc_query_service: OscQueryService,
+    crash_report: Option<PathBuf>,
+) -> Result<(), GracefulShutdownError> {
+    let (osc_target_port, metrics_config) = {
+        let config = params.config.read().await;
+        (config.osc.send_port, config.metrics.clone())
+    };
+
+    let (plugin_manager_tx, plugin_manager_rx) = mpsc::channel(1);
+    let (osc_receiver_tx, _) = broadcast::channel(64);
+    let (osc_received_tx, _) = broadcast::channel(64);
+    let (osc_sender_tx, osc_sender_rx) = mpsc::channel(16);
+    let (osc_sent_tx, _) = broadcast::channel(64);
+    let (tray_property_tx, tray_property_rx) = mpsc::channel(8);
+    let (inspector_tx, inspector_rx) = mpsc::channel(8);
+    let (recorder_tx, recorder_rx) = mpsc::channel(8);
+    let (simulator_tx, simulator_rx) = mpsc::channel(8);
+    let (config_monitor_tx, config_monitor_rx) = mpsc::channel(8);
+    let (profiles_tx, profiles_rx) = mpsc::channel(8);
+    let (avatars_tx, avatars_rx) = mpsc::channel(8);
+    let (setups_tx, setups_rx) = mpsc::channel(8);
+    let (logs_tx, logs_rx) = mpsc::channel(8);
+    let (diagnostics_tx, diagnostics_rx) = mpsc::channel(8);
+    let (notifications_tx, notifications_rx) = mpsc::channel(16);
+    let (update_checker_tx, update_checker_rx) = mpsc::channel(1);
+
+    let dark_mode = match dark_light::detect() {
+        Ok(dark_light::Mode::Dark | dark_light::Mode::Unspecified) | Err(_) => true,
+        Ok(dark_light::Mode::Light) => false,
+    };
+
+    if let Some(path) = crash_report {
+        let _ = notifications_tx.try_send(
+            Notification::new(
+                NotificationCategory::Crashes,
+                "VRC OSC Manager recovered from a crash",
+                "The background tasks were restarted. Please attach the crash report to a bug report.",
+            )
+            .with_action(NotificationAction::OpenCrashReport(path)),
+        );
+    }
+
+    let channel_manager = ChannelManager::new(
+        osc_receiver_tx.clone(),
+        osc_sender_tx,
+        notifications_tx.clone(),
+    );
+
+    let orchestrate_task = OrchestrateTask::new(OrchestrateParams {
+        app_event_rx: receivers.app_event_rx,
+        ui_event_rx: receivers.ui_event_rx,
+        plugin_manager_tx: plugin_manager_tx.clone(),
+        tray_property_tx: tray_property_tx.clone(),
+        inspector_tx,
+        recorder_tx,
+        simulator_tx,
+        config_monitor_tx,
+        profiles_tx,
+        avatars_tx,
+        setups_tx,
+        logs_tx,
+        diagnostics_tx,
+        update_checker_tx,
+        app_window: params.app_window.clone(),
+        config: params.config.clone(),
+        logs_dir: params.logs_dir.clone(),
+        config_dir: params.config_registry.config_dir().to_path_buf(),
+    });
+    let broadcaster_task = BroadcasterTask::new(
+        params.osc_listener_port,
+        params.osc_query_port,
+        notifications_tx.clone(),
+    );
+    let config_writer_task =
+        ConfigWriterTask::new(receivers.config_writer_rx, params.config_registry.clone());
+    let config_monitor_task = ConfigMonitorTask::new(
+        config_monitor_rx,
+        params.config_registry.clone(),
+        params.app_window.clone(),
+        notifications_tx.clone(),
+    );
+    let notifications_task = NotificationsTask::new(
+        notifications_rx,
+        params.config.clone(),
+        params.app_event_tx.clone(),
+    );
+    let vrchat_monitor_task = RestartingTask::new(
+        VrchatMonitorTask::new(params.app_event_tx.clone()),
+        params.app_window.clone(),
+    );
+    let profiles_task = ProfilesTask::new(
+        profiles_rx,
+        params.config.clone(),
+        params.config_registry.clone(),
+        plugin_manager_tx.clone(),
+        tray_property_tx.clone(),
+        params.app_window.clone(),
+    );
+    let setups_task = SetupsTask::new(
+        setups_rx,
+        params.config.clone(),
+        params.config_registry.clone(),
+        params.secrets.clone(),
+        plugin_manager_tx.clone(),
+        params.app_window.clone(),
+        params.data_dir.join("exports"),
+    );
+    let mut plugin_titles: Vec<(String, String)> = params
+        .plugins
+        .iter()
+        .map(|(id, plugin)| (id.to_string(), plugin.title().to_string()))
+        .collect();
+    plugin_titles.sort_by(|a, b| a.1.cmp(&b.1));
+    let tray_task = TrayTask::new(
+        tray_property_rx,
+        params.app_event_tx.clone(),
+        params.config.clone(),
+        dark_mode,
+        plugin_titles.clone(),
+        list_profiles(&params.config_registry.config_dir().join("profiles")),
+    );
+    let avatars_task = AvatarsTask::new(
+        avatars_rx,
+        osc_receiver_tx.subscribe(),
+        params.avatars_config,
+        params.config_registry.clone(),
+        plugin_manager_tx.clone(),
+        params.app_window.clone(),
+        plugin_titles.clone(),
+    );
+    let logs_task = LogsTask::new(
+        logs_rx,
+        params.config.clone(),
+        params.logger,
+        params.app_window.clone(),
+        plugin_titles,
+    );
+    let diagnostics_task = DiagnosticsTask::new(DiagnosticsParams {
+        rx: diagnostics_rx,
+        incoming_rx: osc_receiver_tx.subscribe(),
+        outgoing_rx: osc_sent_tx.subscribe(),
+        registry: params.config_registry.clone(),
+        plugin_manager_tx,
+        app_window: params.app_window.clone(),
+        logs_dir: params.logs_dir,
+        diagnostics_dir: params.data_dir.join("diagnostics"),
+        osc_query_port: params.osc_query_port,
+    });
+    let osc_query_task = RestartingTask::new(
+        OscQueryTask::new(
+            params.osc_query_port,
+            osc_query_service.clone(),
+            params.config.subscribe(),
+            params.plugins.clone(),
+        ),
+        params.app_window.clone(),
+    );
+    let inspector_task = InspectorTask::new(
+        inspector_rx,
+        osc_receiver_tx.subscribe(),
+        osc_sent_tx.subscribe(),
+        params.app_window.clone(),
+        params.data_dir.join("captures"),
+    );
+    let simulator_task = SimulatorTask::new(
+        simulator_rx,
+        osc_receiver_tx.clone(),
+        osc_sent_tx.subscribe(),
+        params.app_window.clone(),
+        osc_query_service,
+    );
+    let recorder_task = RecorderTask::new(
+        recorder_rx,
+        osc_receiver_tx.clone(),
+        osc_received_tx.clone(),
+        params.app_window.clone(),
+        params.data_dir.join("recordings"),
+    );
+    let osc_receiver_task = RestartingTask::new(
+        OscReceiverTask::new(params.osc_listener_port, osc_receiver_tx, osc_received_tx),
+        params.app_window.clone(),
+    );
+    let osc_sender_task = RestartingTask::new(
+        OscSenderTask::new(osc_target_port, osc_sender_rx, osc_sent_tx),
+        params.app_window.clone(),
+    );
+    let plugin_manager_task = PluginManagerTask::new(
+        plugin_manager_rx,
+        params.config.clone(),
+        params.plugins,
+        channel_manager,
+        tray_property_tx,
+        &params.config_registry,
+    );
+    let metrics_task = metrics_config
+        .enabled
+        .then(|| MetricsTask::new(metrics_config.listen_address));
+    let update_checker_task = match UpdateCheckerTask::new(
+        update_checker_rx,
+        params.app_event_tx.clone(),
+        notifications_tx,
+        params.config,
+        params.data_dir.join("updates"),
+    ) {
+        Ok(task) => Some(task),
+        Err(error) => {
+            error!("Failed to initialize update checker: {}", error);
+            None
+        }
+    };
+
+    Toplevel::new(async |s: &mut SubsystemHandle| {
+        s.start(SubsystemBuilder::new(
+            "Orchestrate",
+            orchestrate_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Broadcaster",
+            broadcaster_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new("Tray", tray_task.into_subsystem()));
+        s.start(SubsystemBuilder::new(
+            "ConfigWriter",
+            config_writer_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "ConfigMonitor",
+            config_monitor_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Notifications",
+            notifications_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Profiles",
+            profiles_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Setups",
+            setups_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Avatars",
+            avatars_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new("Logs", logs_task.into_subsystem()));
+        s.start(SubsystemBuilder::new(
+            "Diagnostics",
+            diagnostics_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "VrchatMonitor",
+            vrchat_monitor_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "OscQuery",
+            osc_query_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "OscReceiver",
+            osc_receiver_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "OscSender",
+            osc_sender_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Inspector",
+            inspector_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Simulator",
+            simulator_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "Recorder",
+            recorder_task.into_subsystem(),
+        ));
+        s.start(SubsystemBuilder::new(
+            "PluginManager",
+            plugin_manager_task.into_subsystem(),
+        ));
+
+        if let Some(task) = metrics_task {
+            s.start(SubsystemBuilder::new("Metrics", task.into_subsystem()));
+        }
+
+        if let Some(task) = update_checker_task {
+            s.start(SubsystemBuilder::new(
+                "UpdateChecker",
+                task.into_subsystem(),
+            ));
+        }
+    })
+    .handle_shutdown_requests(Duration::from_millis(1000))
+    .await
+}
+
+pub struct BackgroundParams {
+    pub config: ConfigHandle<RootConfig>,
+    pub avatars_config: ConfigHandle<AvatarsConfig>,
+    pub plugins: HashMap<&'static str, Arc<dyn Plugin>>,
+    pub config_writer_rx: mpsc::Receiver<WriteConfigRequest>,
+    pub config_registry: ConfigRegistry,
+    pub secrets: Secrets,
+    pub logger: LoggerHandle,
+    pub logs_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub ui_event_rx: mpsc::Receiver<UiEvent>,
+    pub app_window: Weak<AppWindow>,
+    pub instance_listener: Listener,
+}
+
 pub struct BackgroundTasks {
     runtime: Runtime,
     join_handle: JoinHandle<()>,
@@ -179,30 +575,33 @@ pub struct BackgroundTasks {
 }
 
 impl BackgroundTasks {
-    pub fn new(
-        config: ConfigHandle<RootConfig>,
-        plugins: HashMap<&'static str, Arc<dyn Plugin>>,
-        config_writer_rx: mpsc::Receiver<WriteConfigRequest>,
-        logs_dir: PathBuf,
-        ui_event_rx: mpsc::Receiver<UiEvent>,
-        app_window: Weak<AppWindow>,
-    ) -> anyhow::Result<Self> {
+    pub fn new(params: BackgroundParams) -> anyhow::Result<Self> {
         let osc_listener_port = get_available_udp_port()?;
         let osc_query_port = get_available_tcp_port()?;
         let (app_event_tx, app_event_rx) = mpsc::channel(8);
+        instance::serve(params.instance_listener, app_event_tx.clone());
 
-        let (runtime, join_handle) = start_runtime(RuntimeParams {
-            osc_listener_port,
-            osc_query_port,
-            config,
-            logs_dir,
-            plugins,
-            config_writer_rx,
-            app_window,
-            ui_event_rx,
-            app_event_tx: app_event_tx.clone(),
-            app_event_rx,
-        })?;
+        let (runtime, join_handle) = start_runtime(
+            RuntimeParams {
+                osc_listener_port,
+                osc_query_port,
+                config: params.config,
+                avatars_config: params.avatars_config,
+                logs_dir: params.logs_dir,
+                data_dir: params.data_dir,
+                plugins: params.plugins,
+                config_registry: params.config_registry,
+                secrets: params.secrets,
+                logger: params.logger,
+                app_window: params.app_window,
+                app_event_tx: app_event_tx.clone(),
+            },
+            EventReceivers {
+                config_writer_rx: params.config_writer_rx,
+                ui_event_rx: params.ui_event_rx,
+                app_event_rx,
+            },
+        )?;
 
         Ok(Self {
             runtime,
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::tasks::recorder::REPLAY_SPEEDS;
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Start without opening the window, only showing the tray icon
+    #[arg(long)]
+    pub minimized: bool,
+
+    /// Wait for a running instance to exit instead of handing over to it, used when restarting after an update
+    #[arg(long, hide = true)]
+    pub takeover: bool,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// Record incoming OSC traffic to a JSON Lines file
+    Record {
+        /// Target file, defaults to a timestamped file in the recordings folder
+        path: Option<PathBuf>,
+    },
+    /// Replay a recorded OSC session into the plugins
+    Replay {
+        /// Recording created through the UI or the record command
+        path: PathBuf,
+
+        /// Playback speed multiplier, between 0.01 and 100
+        #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+        speed: f32,
+    },
+    /// Restore the executable replaced by the last installed update
+    Rollback,
+}
+
+fn parse_speed(value: &str) -> Result<f32, String> {
+    let speed: f32 = value.parse().map_err(|error| format!("{}", error))?;
+
+    if !REPLAY_SPEEDS.contains(&speed) {
+        return Err(format!(
+            "must be between {} and {}",
+            REPLAY_SPEEDS.start(),
+            REPLAY_SPEEDS.end()
+        ));
+    }
+
+    Ok(speed)
+}
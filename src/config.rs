@@ -1,7 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::net::SocketAddr;
 
 use serde::{Deserialize, Serialize};
 
+use crate::utils::config::Config;
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DarkLight {
@@ -36,22 +39,260 @@ impl Default for OscConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: SocketAddr::from(([127, 0, 0, 1], 9464)),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Off,
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "Off",
+            LogLevel::Error => "Error",
+            LogLevel::Warn => "Warn",
+            LogLevel::Info => "Info",
+            LogLevel::Debug => "Debug",
+            LogLevel::Trace => "Trace",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.label() == label)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Level for messages of the application itself.
+    pub level: LogLevel,
+    /// Levels for individual modules, keyed by module path, e.g. `vrc_osc_manager::plugins::pishock`.
+    pub modules: BTreeMap<String, LogLevel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    Updates,
+    ServiceDiscovery,
+    PluginFailures,
+    PluginErrors,
+    ConfigProblems,
+    Crashes,
+}
+
+impl NotificationCategory {
+    pub const ALL: [NotificationCategory; 6] = [
+        NotificationCategory::Updates,
+        NotificationCategory::ServiceDiscovery,
+        NotificationCategory::PluginFailures,
+        NotificationCategory::PluginErrors,
+        NotificationCategory::ConfigProblems,
+        NotificationCategory::Crashes,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            NotificationCategory::Updates => "updates",
+            NotificationCategory::ServiceDiscovery => "service_discovery",
+            NotificationCategory::PluginFailures => "plugin_failures",
+            NotificationCategory::PluginErrors => "plugin_errors",
+            NotificationCategory::ConfigProblems => "config_problems",
+            NotificationCategory::Crashes => "crashes",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotificationCategory::Updates => "Available updates",
+            NotificationCategory::ServiceDiscovery => "Service discovery problems",
+            NotificationCategory::PluginFailures => "Plugins stopping after a failure",
+            NotificationCategory::PluginErrors => "Errors reported by plugins",
+            NotificationCategory::ConfigProblems => "Configuration files which cannot be loaded",
+            NotificationCategory::Crashes => "Recovering from a crash",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.id() == id)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub disabled: BTreeSet<NotificationCategory>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    PreRelease,
+}
+
+impl UpdateChannel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "Stable",
+            UpdateChannel::PreRelease => "Pre-release",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [UpdateChannel::Stable, UpdateChannel::PreRelease]
+            .into_iter()
+            .find(|channel| channel.label() == label)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    pub channel: UpdateChannel,
+    /// Version the user chose to skip, newer versions are offered again.
+    pub skipped_version: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStartMethod {
+    /// XDG autostart desktop entry on Linux, the registry run key on Windows.
+    #[default]
+    DesktopEntry,
+    /// Systemd user service, restarted when the application fails. Linux only.
+    SystemdService,
+}
+
+impl AutoStartMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AutoStartMethod::DesktopEntry => "Desktop entry",
+            AutoStartMethod::SystemdService => "Systemd user service",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [
+            AutoStartMethod::DesktopEntry,
+            AutoStartMethod::SystemdService,
+        ]
+        .into_iter()
+        .find(|method| method.label() == label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoStartConfig {
+    pub method: AutoStartMethod,
+    pub start_minimized: bool,
+}
+
+impl Default for AutoStartConfig {
+    fn default() -> Self {
+        Self {
+            method: AutoStartMethod::default(),
+            start_minimized: true,
+        }
+    }
+}
+
+pub type ParameterMappings = BTreeMap<String, BTreeMap<String, String>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct RootConfig {
     pub osc: OscConfig,
+    pub metrics: MetricsConfig,
+    pub logging: LoggingConfig,
+    pub notifications: NotificationsConfig,
     pub dark_light: DarkLight,
+    pub auto_start: AutoStartConfig,
     pub enabled_plugins: HashSet<String>,
     pub check_for_updates: bool,
+    pub updates: UpdatesConfig,
+    pub active_profile: Option<String>,
+    /// Custom addresses for plugin parameters, keyed by plugin ID and parameter name.
+    pub parameters: ParameterMappings,
 }
 
+impl Config for RootConfig {}
+
 impl Default for RootConfig {
     fn default() -> Self {
         Self {
             osc: OscConfig::default(),
+            metrics: MetricsConfig::default(),
+            logging: LoggingConfig::default(),
+            notifications: NotificationsConfig::default(),
             dark_light: DarkLight::default(),
+            auto_start: AutoStartConfig::default(),
             enabled_plugins: HashSet::new(),
             check_for_updates: true,
+            updates: UpdatesConfig::default(),
+            active_profile: None,
+            parameters: ParameterMappings::new(),
         }
     }
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvatarEntry {
+    pub name: String,
+    pub disabled_plugins: BTreeSet<String>,
+}
+
+/// Avatars seen so far, keyed by avatar ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvatarsConfig {
+    pub avatars: BTreeMap<String, AvatarEntry>,
+}
+
+impl Config for AvatarsConfig {}
@@ -3,19 +3,32 @@
     windows_subsystem = "windows"
 )]
 
-use crate::background::BackgroundTasks;
-use crate::config::RootConfig;
+use crate::background::{BackgroundParams, BackgroundTasks};
+use crate::cli::{Cli, CliCommand};
+use crate::config::{AvatarsConfig, RootConfig};
+use crate::platform::SERVICE_RESTART_EXIT_CODE;
 use crate::plugins::get_plugins;
+use crate::tasks::orchestrate::UiEvent;
+use crate::tasks::recorder::RecorderCommand;
 use crate::ui::run_ui;
-use crate::utils::config::ConfigManager;
+use crate::utils::config::{ConfigManager, Secrets};
+use crate::utils::crash;
+use crate::utils::instance::{self, InstanceRequest};
+use crate::utils::logging::file_format;
+use crate::utils::self_update;
 use anyhow::Context;
+use clap::Parser;
 use directories::BaseDirs;
-use flexi_logger::{Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming};
+use flexi_logger::{Cleanup, Criterion, Duplicate, FileSpec, Logger, LoggerHandle, Naming};
 use log::info;
+use std::env;
+use std::path::{self, Path};
 use tokio::sync::mpsc;
 
 mod background;
+mod cli;
 mod config;
+mod metrics;
 mod osc_query;
 mod platform;
 mod plugins;
@@ -28,45 +41,88 @@ slint::include_modules!();
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     let base_dirs = BaseDirs::new().context("Base directories not available")?;
     let config_dir = base_dirs.config_dir().join("vrc-osc-manager");
     let data_dir = base_dirs.data_dir().join("vrc-osc-manager");
     let logs_dir = data_dir.join("logs");
 
-    Logger::try_with_env_or_str("error, vrc_osc_manager=info")?
-        .log_to_file(FileSpec::default().directory(logs_dir.clone()))
-        .duplicate_to_stdout(Duplicate::All)
-        .set_palette("b1;3;2;4;6".into())
-        .rotate(
-            Criterion::Size(1024 * 1024),
-            Naming::Timestamps,
-            Cleanup::KeepLogFiles(5),
-        )
-        .start()?;
+    if let Some(CliCommand::Rollback) = cli.command {
+        let _logger = start_logger(&logs_dir)?;
+        self_update::rollback(&env::current_exe()?.canonicalize()?)?;
+        return Ok(());
+    }
+
+    let request = match &cli.command {
+        Some(CliCommand::Record { path }) => Some(InstanceRequest::Record {
+            path: path.as_deref().map(path::absolute).transpose()?,
+        }),
+        Some(CliCommand::Replay { path, speed }) => Some(InstanceRequest::Replay {
+            path: path::absolute(path)?,
+            speed: *speed,
+        }),
+        _ if cli.minimized => None,
+        _ => Some(InstanceRequest::ShowWindow),
+    };
+
+    // Claimed before logging to a file, as starting the logger rotates the log file of the running instance.
+    let Some(instance_listener) = instance::claim(request.as_ref(), cli.takeover)? else {
+        eprintln!("VRC OSC Manager is already running, handed over to the running instance");
+        return Ok(());
+    };
+
+    let logger = start_logger(&logs_dir)?;
+    crash::install_panic_hook(logs_dir.clone());
 
     info!("Starting VRC OSC Manager v{}", VERSION);
 
     let (config_writer_tx, config_writer_rx) = mpsc::channel(8);
-    let config_manager = ConfigManager::new(config_dir, config_writer_tx);
+    let secrets = Secrets::new(config_dir.join("secrets.enc"), data_dir.join("secrets.key"));
+    let config_manager = ConfigManager::new(config_dir, config_writer_tx, secrets.clone());
+    let config_registry = config_manager.registry();
     let root_config = config_manager.load_config::<RootConfig>(None, None);
+    let avatars_config = config_manager.load_config::<AvatarsConfig>(Some("avatars"), None);
     let plugins = get_plugins(config_manager);
     let enabled_plugins = root_config.blocking_read().enabled_plugins.clone();
 
     let app_window = AppWindow::new()?;
     let (ui_event_tx, ui_event_rx) = mpsc::channel(8);
-    let background_tasks = BackgroundTasks::new(
-        root_config.clone(),
-        plugins.clone(),
+    let background_tasks = BackgroundTasks::new(BackgroundParams {
+        config: root_config.clone(),
+        avatars_config,
+        plugins: plugins.clone(),
         config_writer_rx,
+        config_registry,
+        secrets,
+        logger,
         logs_dir,
+        data_dir,
         ui_event_rx,
-        app_window.as_weak(),
-    )?;
+        app_window: app_window.as_weak(),
+        instance_listener,
+    })?;
+
+    match cli.command {
+        Some(CliCommand::Record { path }) => {
+            ui_event_tx.blocking_send(UiEvent::Recorder(RecorderCommand::StartRecording(path)))?;
+        }
+        Some(CliCommand::Replay { path, speed }) => {
+            ui_event_tx.blocking_send(UiEvent::Recorder(RecorderCommand::StartReplay {
+                path,
+                speed,
+            }))?;
+        }
+        Some(CliCommand::Rollback) | None => {}
+    }
 
     for plugin in plugins.values() {
         plugin.clone().register_settings_callbacks(&app_window)?
     }
 
+    if !cli.minimized {
+        app_window.show()?;
+    }
+
     run_ui(
         app_window,
         plugins,
@@ -76,5 +132,23 @@ fn main() -> anyhow::Result<()> {
     )?;
     background_tasks.shutdown();
 
+    if self_update::restart_on_exit_requested() {
+        std::process::exit(SERVICE_RESTART_EXIT_CODE);
+    }
+
     Ok(())
 }
+
+fn start_logger(logs_dir: &Path) -> anyhow::Result<LoggerHandle> {
+    Ok(Logger::try_with_env_or_str("error, vrc_osc_manager=info")?
+        .log_to_file(FileSpec::default().directory(logs_dir))
+        .format_for_files(file_format)
+        .duplicate_to_stdout(Duplicate::All)
+        .set_palette("b1;3;2;4;6".into())
+        .rotate(
+            Criterion::Size(1024 * 1024),
+            Naming::Timestamps,
+            Cleanup::KeepLogFiles(5),
+        )
+        .start()?)
+}
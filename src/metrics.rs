@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+const PREFIX: &str = "vrc_osc_manager";
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10.];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Reduces an OSC address to its first two segments, e.g. `/avatar/parameters`, to keep the label
+/// cardinality bounded.
+fn address_prefix(address: &str) -> &str {
+    match address.match_indices('/').nth(2) {
+        Some((index, _)) => &address[..index],
+        None => address,
+    }
+}
+
+struct MetricVec {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+}
+
+impl MetricVec {
+    fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn add(&self, label_value: &str, amount: u64) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(label_value.to_string())
+            .or_insert(0) += amount;
+    }
+
+    fn set(&self, label_value: &str, value: u64) {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(label_value.to_string(), value);
+    }
+
+    fn render(&self, output: &mut String, kind: &str) {
+        let _ = writeln!(output, "# HELP {}_{} {}", PREFIX, self.name, self.help);
+        let _ = writeln!(output, "# TYPE {}_{} {}", PREFIX, self.name, kind);
+
+        for (label_value, value) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(
+                output,
+                "{}_{}{{{}=\"{}\"}} {}",
+                PREFIX,
+                self.name,
+                self.label,
+                escape_label(label_value),
+                value
+            );
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+struct HistogramData {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: Mutex<BTreeMap<String, HistogramData>>,
+}
+
+impl HistogramVec {
+    fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn observe(&self, label_value: &str, value: f64) {
+        let mut values = self.values.lock().unwrap();
+        let data = values
+            .entry(label_value.to_string())
+            .or_insert_with(|| HistogramData {
+                buckets: vec![0; LATENCY_BUCKETS.len()],
+                ..Default::default()
+            });
+
+        for (index, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            if value <= *bound {
+                data.buckets[index] += 1;
+            }
+        }
+
+        data.sum += value;
+        data.count += 1;
+    }
+
+    fn render(&self, output: &mut String) {
+        let _ = writeln!(output, "# HELP {}_{} {}", PREFIX, self.name, self.help);
+        let _ = writeln!(output, "# TYPE {}_{} histogram", PREFIX, self.name);
+
+        for (label_value, data) in self.values.lock().unwrap().iter() {
+            let label_value = escape_label(label_value);
+
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&data.buckets) {
+                let _ = writeln!(
+                    output,
+                    "{}_{}_bucket{{{}=\"{}\",le=\"{}\"}} {}",
+                    PREFIX, self.name, self.label, label_value, bound, count
+                );
+            }
+
+            let _ = writeln!(
+                output,
+                "{}_{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}",
+                PREFIX, self.name, self.label, label_value, data.count
+            );
+            let _ = writeln!(
+                output,
+                "{}_{}_sum{{{}=\"{}\"}} {}",
+                PREFIX, self.name, self.label, label_value, data.sum
+            );
+            let _ = writeln!(
+                output,
+                "{}_{}_count{{{}=\"{}\"}} {}",
+                PREFIX, self.name, self.label, label_value, data.count
+            );
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub struct Metrics {
+    osc_received: MetricVec,
+    osc_sent: MetricVec,
+    broadcast_lagged: MetricVec,
+    broadcast_dropped: MetricVec,
+    plugin_running: MetricVec,
+    plugin_starts: MetricVec,
+    pishock_requests: HistogramVec,
+    pishock_failures: MetricVec,
+    vrchat_running: AtomicBool,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            osc_received: MetricVec::new(
+                "osc_messages_received_total",
+                "OSC messages received from VRChat",
+                "prefix",
+            ),
+            osc_sent: MetricVec::new(
+                "osc_messages_sent_total",
+                "OSC messages sent to VRChat",
+                "prefix",
+            ),
+            broadcast_lagged: MetricVec::new(
+                "osc_broadcast_lagged_total",
+                "Times a plugin fell behind the OSC broadcast channel",
+                "plugin",
+            ),
+            broadcast_dropped: MetricVec::new(
+                "osc_broadcast_dropped_messages_total",
+                "OSC messages dropped because a plugin fell behind",
+                "plugin",
+            ),
+            plugin_running: MetricVec::new(
+                "plugin_running",
+                "Whether a plugin is currently running",
+                "plugin",
+            ),
+            plugin_starts: MetricVec::new(
+                "plugin_starts_total",
+                "Times a plugin has been started, including restarts",
+                "plugin",
+            ),
+            pishock_requests: HistogramVec::new(
+                "pishock_request_duration_seconds",
+                "Latency of PiShock API requests",
+                "endpoint",
+            ),
+            pishock_failures: MetricVec::new(
+                "pishock_request_failures_total",
+                "Failed PiShock API requests",
+                "endpoint",
+            ),
+            vrchat_running: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_osc_received(&self, address: &str) {
+        self.osc_received.add(address_prefix(address), 1);
+    }
+
+    pub fn record_osc_sent(&self, address: &str) {
+        self.osc_sent.add(address_prefix(address), 1);
+    }
+
+    pub fn record_broadcast_lag(&self, plugin_id: &str, skipped: u64) {
+        self.broadcast_lagged.add(plugin_id, 1);
+        self.broadcast_dropped.add(plugin_id, skipped);
+    }
+
+    pub fn set_plugin_running(&self, plugin_id: &str, running: bool) {
+        self.plugin_running.set(plugin_id, running as u64);
+
+        if running {
+            self.plugin_starts.add(plugin_id, 1);
+        }
+    }
+
+    pub fn record_pishock_request(&self, endpoint: &str, elapsed: Duration, success: bool) {
+        self.pishock_requests
+            .observe(endpoint, elapsed.as_secs_f64());
+
+        if !success {
+            self.pishock_failures.add(endpoint, 1);
+        }
+    }
+
+    pub fn set_vrchat_running(&self, running: bool) {
+        self.vrchat_running.store(running, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        self.osc_received.render(&mut output, "counter");
+        self.osc_sent.render(&mut output, "counter");
+        self.broadcast_lagged.render(&mut output, "counter");
+        self.broadcast_dropped.render(&mut output, "counter");
+        self.plugin_running.render(&mut output, "gauge");
+        self.plugin_starts.render(&mut output, "counter");
+        self.pishock_requests.render(&mut output);
+        self.pishock_failures.render(&mut output, "counter");
+
+        let _ = writeln!(
+            output,
+            "# HELP {}_vrchat_running Whether the VRChat process is running",
+            PREFIX
+        );
+        let _ = writeln!(output, "# TYPE {}_vrchat_running gauge", PREFIX);
+        let _ = writeln!(
+            output,
+            "{}_vrchat_running {}",
+            PREFIX,
+            self.vrchat_running.load(Ordering::Relaxed) as u8
+        );
+
+        output
+    }
+}
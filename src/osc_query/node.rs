@@ -2,7 +2,7 @@ use serde::Serialize;
 use serde_repr::Serialize_repr;
 use std::collections::{HashMap, VecDeque};
 
-#[derive(Serialize_repr)]
+#[derive(Clone, Copy, Serialize_repr)]
 #[repr(u8)]
 #[allow(dead_code)]
 pub enum OscAccess {
@@ -94,6 +94,24 @@ impl OscQueryNode {
             .add_recursive_node(node, address);
     }
 
+    pub fn endpoints(&self) -> Vec<&OscQueryNode> {
+        let mut endpoints = vec![];
+        self.collect_endpoints(&mut endpoints);
+        endpoints
+    }
+
+    fn collect_endpoints<'a>(&'a self, endpoints: &mut Vec<&'a OscQueryNode>) {
+        if self.osc_type.is_some() {
+            endpoints.push(self);
+        }
+
+        if let Some(contents) = &self.contents {
+            for node in contents.values() {
+                node.collect_endpoints(endpoints);
+            }
+        }
+    }
+
     pub fn get(&self, path: String) -> Option<&OscQueryNode> {
         let mut address: VecDeque<_> = path.split('/').collect();
 
@@ -8,6 +8,7 @@ use std::convert::Infallible;
 use std::future::{ready, Ready};
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use tokio::sync::watch;
 use tower::Service;
 
 use crate::osc_query::node::{OscAccess, OscQueryNode};
@@ -46,16 +47,21 @@ impl OscHostInfo {
     }
 }
 
-pub struct OscQueryServiceBuilder {
+pub struct OscEndpoint {
+    pub full_path: String,
+    pub osc_type: String,
+    pub description: String,
+}
+
+/// Tree of endpoints advertised by the service.
+pub struct OscQueryEndpoints {
     root_node: OscQueryNode,
-    host_info: OscHostInfo,
 }
 
-impl OscQueryServiceBuilder {
-    pub fn new(host_info: OscHostInfo) -> Self {
+impl OscQueryEndpoints {
+    pub fn new() -> Self {
         Self {
             root_node: OscQueryNode::root(),
-            host_info,
         }
     }
 
@@ -69,21 +75,49 @@ impl OscQueryServiceBuilder {
         let node = OscQueryNode::new(full_path, osc_type, access, description);
         self.root_node.add_node(node);
     }
-
-    pub fn build(self) -> OscQueryService {
-        OscQueryService {
-            root_node: Arc::new(self.root_node),
-            host_info: Arc::new(self.host_info),
-        }
-    }
 }
 
 #[derive(Clone)]
 pub struct OscQueryService {
-    root_node: Arc<OscQueryNode>,
+    root_node: Arc<watch::Sender<Arc<OscQueryNode>>>,
     host_info: Arc<OscHostInfo>,
 }
 
+impl OscQueryService {
+    pub fn new(host_info: OscHostInfo, endpoints: OscQueryEndpoints) -> Self {
+        Self {
+            root_node: Arc::new(watch::Sender::new(Arc::new(endpoints.root_node))),
+            host_info: Arc::new(host_info),
+        }
+    }
+
+    /// Replaces the advertised endpoints, e.g. after parameters were renamed.
+    pub fn set_endpoints(&self, endpoints: OscQueryEndpoints) {
+        self.root_node.send_replace(Arc::new(endpoints.root_node));
+    }
+
+    /// Notifies about every replacement of the endpoints.
+    pub fn subscribe(&self) -> watch::Receiver<impl Sized> {
+        self.root_node.subscribe()
+    }
+
+    pub fn endpoints(&self) -> Vec<OscEndpoint> {
+        let root_node = self.root_node.borrow().clone();
+        let mut endpoints: Vec<_> = root_node
+            .endpoints()
+            .into_iter()
+            .map(|node| OscEndpoint {
+                full_path: node.full_path.clone(),
+                osc_type: node.osc_type.clone().unwrap_or_default(),
+                description: node.description.clone(),
+            })
+            .collect();
+
+        endpoints.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+        endpoints
+    }
+}
+
 impl Service<Request<Body>> for OscQueryService {
     type Response = Response<Body>;
     type Error = Infallible;
@@ -94,7 +128,8 @@ impl Service<Request<Body>> for OscQueryService {
     }
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
-        let node = match self.root_node.get(req.uri().path().to_string()) {
+        let root_node = self.root_node.borrow().clone();
+        let node = match root_node.get(req.uri().path().to_string()) {
             None => {
                 return ready(Ok(Response::builder()
                     .status(404)
@@ -1,66 +1,441 @@
-use crate::platform::Platform;
-use anyhow::Context;
+use crate::config::{AutoStartConfig, AutoStartMethod};
+use crate::platform::{Platform, SERVICE_RESTART_EXIT_CODE};
+use anyhow::{bail, Context};
 use directories::BaseDirs;
 use indoc::indoc;
 use log::debug;
+use std::collections::HashMap;
 use std::env;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::fs;
 use tokio::process::Command;
+use tokio::select;
+use tokio::time::timeout;
+use tokio_stream::StreamExt;
+use zbus::fdo::DBusProxy;
+use zbus::{proxy, Connection};
+
+const NOTIFICATION_ACTION: &str = "default-action";
+/// How long the action of a notification stays available to the user.
+const NOTIFICATION_ACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const SERVICE_NAME: &str = "vrc-osc-manager.service";
+const SERVICE_TARGET: &str = "graphical-session.target";
+/// Set in the environment of the systemd user service, so that the application knows systemd restarts it.
+const SERVICE_VARIABLE: &str = "VRC_OSC_MANAGER_SERVICE";
+
+const DESKTOP_VARIABLES: &[&str] = &[
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_DESKTOP",
+    "XDG_SESSION_TYPE",
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "APPIMAGE",
+    "FLATPAK_ID",
+];
+
+/// Session bus services the application relies on.
+const DBUS_SERVICES: &[(&str, &str)] = &[
+    (
+        "Tray (StatusNotifierWatcher)",
+        "org.kde.StatusNotifierWatcher",
+    ),
+    ("Notifications", "org.freedesktop.Notifications"),
+    ("Secret Service", "org.freedesktop.secrets"),
+    ("Systemd user manager", "org.freedesktop.systemd1"),
+];
+
+/// Quotes an argument for the `Exec` key of a desktop entry.
+fn desktop_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+
+    let arg = arg.replace('%', "%%");
+
+    if !arg.contains(RESERVED) {
+        return arg;
+    }
+
+    let mut quoted = String::from("\"");
+
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+
+        quoted.push(c);
+    }
+
+    quoted.push('"');
+
+    // Desktop entry values have their own escaping, which is applied before the quoting.
+    quoted.replace('\\', "\\\\")
+}
+
+/// Quotes an argument for the `ExecStart` key of a systemd unit.
+fn systemd_exec_arg(arg: &str) -> String {
+    format!(
+        "\"{}\"",
+        arg.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+            .replace('$', "$$")
+    )
+}
+
+fn flatpak_id() -> Option<String> {
+    env::var("FLATPAK_ID").ok().filter(|id| !id.is_empty())
+}
 
 pub struct LinuxPlatform;
 
 impl LinuxPlatform {
     fn auto_start_path(&self) -> anyhow::Result<PathBuf> {
         let base_dirs = BaseDirs::new().context("Base directories not available")?;
-        let mut auto_start_path = base_dirs.config_dir().to_path_buf();
+
+        // Inside Flatpak the config directory points into the sandbox, which the session does not look at.
+        let mut auto_start_path = if flatpak_id().is_some() {
+            base_dirs.home_dir().join(".config")
+        } else {
+            base_dirs.config_dir().to_path_buf()
+        };
         auto_start_path.push("autostart");
         auto_start_path.push("vrc-osc-manager.desktop");
         debug!("Auto start path: {:?}", auto_start_path);
 
         Ok(auto_start_path)
     }
-}
 
-impl Platform for LinuxPlatform {
-    fn open_folder(&self, path: &Path) {
-        let _ = Command::new("xdg-open").arg(path).spawn();
+    fn systemd_dir(&self) -> anyhow::Result<PathBuf> {
+        let base_dirs = BaseDirs::new().context("Base directories not available")?;
+        Ok(base_dirs.config_dir().join("systemd").join("user"))
     }
 
-    fn has_auto_start(&self) -> bool {
-        self.auto_start_path().unwrap().exists()
+    /// Returns the command which starts this application from outside, which differs from the executable path when
+    /// running as AppImage or Flatpak.
+    fn launch_command(&self, minimized: bool) -> anyhow::Result<Vec<String>> {
+        let mut command = if let Some(flatpak_id) = flatpak_id() {
+            vec!["flatpak".to_string(), "run".to_string(), flatpak_id]
+        } else if let Some(app_image) = env::var_os("APPIMAGE").filter(|path| !path.is_empty()) {
+            // The executable path points into the mount of the AppImage, which only exists while it is running.
+            vec![app_image
+                .into_string()
+                .ok()
+                .context("Invalid AppImage path")?]
+        } else {
+            vec![env::current_exe()?
+                .to_str()
+                .context("Invalid executable path")?
+                .to_string()]
+        };
+
+        if minimized {
+            command.push("--minimized".to_string());
+        }
+
+        Ok(command)
     }
 
-    async fn add_auto_start(&self) -> anyhow::Result<()> {
+    async fn add_desktop_entry(&self, command: &[String]) -> anyhow::Result<()> {
         let path = self.auto_start_path()?;
         fs::create_dir_all(&path.parent().unwrap()).await?;
 
-        let exec_path = env::current_exe()?
-            .to_str()
-            .context("Invalid executable path")?
-            .to_string();
+        let exec = command
+            .iter()
+            .map(|arg| desktop_exec_arg(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
 
         let desktop_entry = indoc! {"
             [Desktop Entry]
             Type=Application
             Name=VRC OSC Manager
-            Exec={exec_path}
+            Exec={exec}
             X-GNOME-Autostart-enabled=true
         "};
-        let desktop_entry = desktop_entry.replace("{exec_path}", &exec_path);
+        let desktop_entry = desktop_entry.replace("{exec}", &exec);
 
         fs::write(&path, desktop_entry).await?;
 
         Ok(())
     }
 
-    async fn remove_auto_start(&self) -> anyhow::Result<()> {
+    async fn add_systemd_service(&self, command: &[String]) -> anyhow::Result<()> {
+        if flatpak_id().is_some() {
+            bail!("The systemd user service is not available when running as Flatpak");
+        }
+
+        let dir = self.systemd_dir()?;
+        fs::create_dir_all(&dir).await?;
+
+        let exec_start = command
+            .iter()
+            .map(|arg| systemd_exec_arg(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let unit = indoc! {"
+            [Unit]
+            Description=VRC OSC Manager
+            PartOf={target}
+            After={target}
+
+            [Service]
+            Type=simple
+            ExecStart={exec_start}
+            Environment={variable}=1
+            Restart=on-failure
+            RestartSec=5
+            SuccessExitStatus={restart_code}
+            RestartForceExitStatus={restart_code}
+
+            [Install]
+            WantedBy={target}
+        "};
+        let unit = unit
+            .replace("{target}", SERVICE_TARGET)
+            .replace("{exec_start}", &exec_start)
+            .replace("{variable}", SERVICE_VARIABLE)
+            .replace("{restart_code}", &SERVICE_RESTART_EXIT_CODE.to_string());
+
+        fs::write(dir.join(SERVICE_NAME), unit).await?;
+        systemctl(&["daemon-reload"]).await?;
+        systemctl(&["enable", SERVICE_NAME]).await?;
+
+        Ok(())
+    }
+
+    async fn remove_systemd_service(&self) -> anyhow::Result<()> {
+        let path = self.systemd_dir()?.join(SERVICE_NAME);
+
+        if !path.exists() {
+            return Ok(());
+        }
+
+        systemctl(&["disable", SERVICE_NAME]).await?;
+        fs::remove_file(&path).await?;
+        systemctl(&["daemon-reload"]).await?;
+
+        Ok(())
+    }
+}
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+async fn session_bus_names() -> zbus::Result<Vec<String>> {
+    let connection = Connection::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
+
+    Ok(dbus
+        .list_names()
+        .await?
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect())
+}
+
+async fn systemctl(args: &[&str]) -> anyhow::Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run systemctl")?;
+
+    if !output.status.success() {
+        bail!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+impl Platform for LinuxPlatform {
+    fn open_folder(&self, path: &Path) {
+        let _ = Command::new("xdg-open").arg(path).spawn();
+    }
+
+    fn has_auto_start(&self) -> bool {
+        let service_enabled = self.systemd_dir().is_ok_and(|dir| {
+            dir.join(format!("{}.wants", SERVICE_TARGET))
+                .join(SERVICE_NAME)
+                .symlink_metadata()
+                .is_ok()
+        });
+
+        service_enabled || self.auto_start_path().unwrap().exists()
+    }
+
+    fn auto_start_methods(&self) -> &'static [AutoStartMethod] {
+        // The systemd user service cannot start an application inside the Flatpak sandbox.
+        if flatpak_id().is_some() {
+            return &[];
+        }
+
+        &[
+            AutoStartMethod::DesktopEntry,
+            AutoStartMethod::SystemdService,
+        ]
+    }
+
+    async fn add_auto_start(&self, config: &AutoStartConfig) -> anyhow::Result<()> {
+        let command = self.launch_command(config.start_minimized)?;
+
+        match config.method {
+            AutoStartMethod::SystemdService if flatpak_id().is_none() => {
+                self.add_systemd_service(&command).await
+            }
+            _ => self.add_desktop_entry(&command).await,
+        }
+    }
+
+    fn runs_as_service(&self) -> bool {
+        env::var_os(SERVICE_VARIABLE).is_some()
+    }
+
+    async fn remove_auto_start(&self, except: Option<AutoStartMethod>) -> anyhow::Result<()> {
+        let except = except.map(|method| match method {
+            AutoStartMethod::SystemdService if flatpak_id().is_some() => {
+                AutoStartMethod::DesktopEntry
+            }
+            method => method,
+        });
         let path = self.auto_start_path()?;
 
-        if path.exists() {
+        if except != Some(AutoStartMethod::DesktopEntry) && path.exists() {
             fs::remove_file(&path).await?;
         }
 
+        if except != Some(AutoStartMethod::SystemdService) {
+            self.remove_systemd_service().await?;
+        }
+
         Ok(())
     }
+
+    async fn capabilities(&self) -> String {
+        let mut lines: Vec<String> = DESKTOP_VARIABLES
+            .iter()
+            .map(|name| {
+                format!(
+                    "{}: {}",
+                    name,
+                    env::var(name).unwrap_or_else(|_| "(not set)".to_string())
+                )
+            })
+            .collect();
+
+        match session_bus_names().await {
+            Ok(names) => {
+                lines.push("D-Bus session bus: available".to_string());
+
+                for (label, service) in DBUS_SERVICES {
+                    let available = names.iter().any(|name| name == service);
+                    lines.push(format!(
+                        "{}: {}",
+                        label,
+                        if available { "available" } else { "missing" }
+                    ));
+                }
+
+                let players: Vec<&str> = names
+                    .iter()
+                    .filter_map(|name| name.strip_prefix("org.mpris.MediaPlayer2."))
+                    .collect();
+                lines.push(format!("MPRIS players: {}", players.join(", ")));
+            }
+            Err(error) => lines.push(format!("D-Bus session bus: unavailable ({})", error)),
+        }
+
+        lines.push(format!("Autostart: {}", self.has_auto_start()));
+
+        lines.join("\n")
+    }
+
+    async fn show_notification(
+        &self,
+        summary: &str,
+        body: &str,
+        action: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        let connection = Connection::session().await?;
+        let notifications = NotificationsProxy::new(&connection).await?;
+        let actions = match action {
+            Some(label) => vec![NOTIFICATION_ACTION, label],
+            None => vec![],
+        };
+
+        // Subscribed before showing the notification, so that no signal about it can be missed.
+        let mut action_invoked = notifications.receive_action_invoked().await?;
+        let mut notification_closed = notifications.receive_notification_closed().await?;
+        let id = notifications
+            .notify(
+                "VRC OSC Manager",
+                0,
+                "",
+                summary,
+                body,
+                &actions,
+                HashMap::new(),
+                -1,
+            )
+            .await?;
+
+        if action.is_none() {
+            return Ok(false);
+        }
+
+        // Not every notification server closes notifications, so waiting for the action gives up after a while.
+        let wait_for_action = async {
+            loop {
+                select! {
+                    Some(signal) = action_invoked.next() => {
+                        let args = signal.args()?;
+                        if args.id == id {
+                            return Ok(args.action_key == NOTIFICATION_ACTION);
+                        }
+                    }
+                    Some(signal) = notification_closed.next() => {
+                        if signal.args()?.id == id {
+                            return Ok(false);
+                        }
+                    }
+                    else => return Ok(false),
+                }
+            }
+        };
+
+        timeout(NOTIFICATION_ACTION_TIMEOUT, wait_for_action)
+            .await
+            .unwrap_or(Ok(false))
+    }
 }
@@ -5,14 +5,39 @@ mod windows;
 
 use std::path::Path;
 
+use crate::config::{AutoStartConfig, AutoStartMethod};
+
+/// Exit code telling the service manager to start the application again, which replaces spawning a new instance when
+/// running as a service.
+pub const SERVICE_RESTART_EXIT_CODE: i32 = 75;
+
 pub trait Platform {
     fn open_folder(&self, path: &Path);
 
     fn has_auto_start(&self) -> bool;
 
-    async fn add_auto_start(&self) -> anyhow::Result<()>;
+    /// Methods the user can choose between, empty if the platform only has one.
+    fn auto_start_methods(&self) -> &'static [AutoStartMethod];
+
+    async fn add_auto_start(&self, config: &AutoStartConfig) -> anyhow::Result<()>;
+
+    /// Whether the application was started by a service manager, which restarts it when it exits with
+    /// [`SERVICE_RESTART_EXIT_CODE`].
+    fn runs_as_service(&self) -> bool;
+
+    /// Removes the auto start entries of all methods, except for the given one.
+    async fn remove_auto_start(&self, except: Option<AutoStartMethod>) -> anyhow::Result<()>;
+
+    /// Describes the desktop environment and which of its services are available, for bug reports.
+    async fn capabilities(&self) -> String;
 
-    async fn remove_auto_start(&self) -> anyhow::Result<()>;
+    /// Shows a desktop notification and returns whether the user chose its action, if the platform supports actions.
+    async fn show_notification(
+        &self,
+        summary: &str,
+        body: &str,
+        action: Option<&str>,
+    ) -> anyhow::Result<bool>;
 }
 
 #[cfg(target_os = "linux")]
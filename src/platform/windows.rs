@@ -1,8 +1,10 @@
+use crate::config::{AutoStartConfig, AutoStartMethod};
 use crate::platform::Platform;
 use anyhow::Context;
 use std::env;
 use std::path::Path;
 use tokio::process::Command;
+use tokio::task::spawn_blocking;
 use winreg::enums::*;
 use winreg::RegKey;
 
@@ -24,7 +26,11 @@ impl Platform for WindowsPlatform {
         }
     }
 
-    async fn add_auto_start(&self) -> anyhow::Result<()> {
+    fn auto_start_methods(&self) -> &'static [AutoStartMethod] {
+        &[]
+    }
+
+    async fn add_auto_start(&self, config: &AutoStartConfig) -> anyhow::Result<()> {
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
         let path = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
 
@@ -32,14 +38,28 @@ impl Platform for WindowsPlatform {
             .to_str()
             .context("Invalid executable path")?
             .to_string();
+        let mut command = format!("\"{}\"", exec_path);
+
+        if config.start_minimized {
+            command.push_str(" --minimized");
+        }
 
         let (key, _) = hkcu.create_subkey(path)?;
-        key.set_value("vrc-osc-manager", &exec_path)?;
+        key.set_value("vrc-osc-manager", &command)?;
 
         Ok(())
     }
 
-    async fn remove_auto_start(&self) -> anyhow::Result<()> {
+    fn runs_as_service(&self) -> bool {
+        false
+    }
+
+    async fn remove_auto_start(&self, except: Option<AutoStartMethod>) -> anyhow::Result<()> {
+        // The run key is the only method, so it is the one being kept.
+        if except.is_some() {
+            return Ok(());
+        }
+
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
         let path = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
         let key = hkcu.open_subkey(path)?;
@@ -48,4 +68,32 @@ impl Platform for WindowsPlatform {
 
         Ok(())
     }
+
+    async fn capabilities(&self) -> String {
+        [
+            "Tray: notification area".to_string(),
+            "Notifications: toast notifications without actions".to_string(),
+            "Secret store: Windows Credential Manager".to_string(),
+            format!("Autostart: {}", self.has_auto_start()),
+        ]
+        .join("\n")
+    }
+
+    async fn show_notification(
+        &self,
+        summary: &str,
+        body: &str,
+        _action: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        let mut notification = notify_rust::Notification::new();
+        notification
+            .appname("VRC OSC Manager")
+            .summary(summary)
+            .body(body);
+
+        // Toast notifications do not report actions back, so they are left out.
+        spawn_blocking(move || notification.show()).await??;
+
+        Ok(false)
+    }
 }
@@ -7,6 +7,7 @@ use enigo::{Enigo, Key, Keyboard, Settings};
 use log::warn;
 use tokio::sync::broadcast::error::RecvError;
 
+use crate::metrics::metrics;
 use crate::plugins::ChannelManager;
 
 pub(super) async fn run(channels: Arc<ChannelManager>) -> anyhow::Result<()> {
@@ -32,6 +33,7 @@ pub(super) async fn run(channels: Arc<ChannelManager>) -> anyhow::Result<()> {
             },
             Err(RecvError::Closed) => break,
             Err(RecvError::Lagged(skipped)) => {
+                metrics().record_broadcast_lag("media_control", skipped);
                 warn!(
                     "MediaControl lagging behind, {} messages have been dropped",
                     skipped
@@ -42,3 +44,8 @@ pub(super) async fn run(channels: Arc<ChannelManager>) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+pub(super) fn play_pause() -> anyhow::Result<()> {
+    Enigo::new(&Settings::default())?.key(Key::MediaPlayPause, Click)?;
+    Ok(())
+}
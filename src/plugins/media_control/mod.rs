@@ -6,8 +6,8 @@ use slint::Weak;
 use tokio_graceful_shutdown::SubsystemHandle;
 
 use crate::osc_query::node::OscAccess;
-use crate::osc_query::service::OscQueryServiceBuilder;
-use crate::plugins::{ChannelManager, Plugin};
+use crate::plugins::parameters::OscParameter;
+use crate::plugins::{ChannelManager, Plugin, PluginTrayAction};
 #[cfg(target_os = "linux")]
 use crate::utils::config::ConfigHandle;
 use crate::utils::config::ConfigManager;
@@ -22,6 +22,30 @@ mod mpris;
 #[cfg(target_os = "linux")]
 use mpris::MediaControlConfig;
 
+const OSC_PARAMETERS: &[OscParameter] = &[
+    OscParameter::new(
+        "MC_PrevTrack",
+        "b",
+        OscAccess::Read,
+        "Media Control: Previous Track",
+    ),
+    OscParameter::new(
+        "MC_NextTrack",
+        "b",
+        OscAccess::Read,
+        "Media Control: Next Track",
+    ),
+    OscParameter::new(
+        "MC_PlayPause",
+        "b",
+        OscAccess::Read,
+        "Media Control: Play/Pause",
+    ),
+    OscParameter::new("MC_Stop", "b", OscAccess::Read, "Media Control: Stop"),
+];
+
+const PLAY_PAUSE_ACTION: &str = "play_pause";
+
 pub struct MediaControl {
     #[cfg(target_os = "linux")]
     config: Arc<ConfigHandle<MediaControlConfig>>,
@@ -69,31 +93,37 @@ impl Plugin for MediaControl {
         }
     }
 
-    fn register_osc_parameters(&self, service: &mut OscQueryServiceBuilder) {
-        service.add_endpoint(
-            "/avatar/parameters/MC_PrevTrack".to_string(),
-            "b".to_string(),
-            OscAccess::Read,
-            "Media Control: Previous Track".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/MC_NextTrack".to_string(),
-            "b".to_string(),
-            OscAccess::Read,
-            "Media Control: Next Track".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/MC_PlayPause".to_string(),
-            "b".to_string(),
-            OscAccess::Read,
-            "Media Control: Play/Pause".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/MC_Stop".to_string(),
-            "b".to_string(),
-            OscAccess::Read,
-            "Media Control: Stop".to_string(),
-        );
+    fn osc_parameters(&self) -> &'static [OscParameter] {
+        OSC_PARAMETERS
+    }
+
+    async fn tray_actions(&self) -> Vec<PluginTrayAction> {
+        vec![PluginTrayAction::new(PLAY_PAUSE_ACTION, "Play/pause")]
+    }
+
+    async fn invoke_tray_action(
+        &self,
+        action_id: &str,
+        _channels: Arc<ChannelManager>,
+    ) -> anyhow::Result<()> {
+        if action_id != PLAY_PAUSE_ACTION {
+            return Ok(());
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            mpris::play_pause(&self.config).await
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            enigo::play_pause()
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn reacts_to_config_changes(&self) -> bool {
+        true
     }
 
     #[cfg(target_os = "linux")]
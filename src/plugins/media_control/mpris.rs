@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use async_osc::prelude::OscMessageExt;
 use async_osc::{OscMessage, OscType};
-use log::warn;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use slint::{ComponentHandle, ModelRc, SharedString, VecModel, Weak};
 use tokio::select;
@@ -17,8 +17,9 @@ use zbus::proxy::CacheProperties;
 use zbus::zvariant::{OwnedValue, Value};
 use zbus::{proxy, Connection, MatchRule, MessageStream};
 
+use crate::metrics::metrics;
 use crate::plugins::ChannelManager;
-use crate::utils::config::ConfigHandle;
+use crate::utils::config::{Config, ConfigHandle};
 use crate::{AppWindow, MediaControlSettings, Router};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -28,6 +29,8 @@ pub(super) struct MediaControlConfig {
     mpris_target: Option<String>,
 }
 
+impl Config for MediaControlConfig {}
+
 impl MediaControlConfig {
     pub(super) fn target(&self) -> Option<String> {
         self.mpris_target.clone()
@@ -74,7 +77,8 @@ pub(super) async fn run(
     config: &ConfigHandle<MediaControlConfig>,
     channels: Arc<ChannelManager>,
 ) -> anyhow::Result<()> {
-    let target = config.read().await.mpris_target.clone();
+    let mut config_rx = config.subscribe();
+    let mut target = config_rx.borrow_and_update().mpris_target.clone();
     let connection = Connection::session().await?;
     let dbus = DBusProxy::new(&connection).await?;
 
@@ -114,6 +118,9 @@ pub(super) async fn run(
     loop {
         select! {
             _ = subsys.on_shutdown_requested() => break,
+            Ok(()) = config_rx.changed() => {
+                target = config_rx.borrow_and_update().mpris_target.clone();
+            }
             osc = osc_rx.recv() => {
                 match osc {
                     Ok(message) => {
@@ -134,6 +141,7 @@ pub(super) async fn run(
                     }
                     Err(RecvError::Closed) => break,
                     Err(RecvError::Lagged(skipped)) => {
+                        metrics().record_broadcast_lag("media_control", skipped);
                         warn!(
                             "MediaControl lagging behind, {} messages have been dropped",
                             skipped
@@ -336,46 +344,57 @@ async fn dispatch_command(
     }
 }
 
-async fn test_play_pause(target: Option<String>) -> String {
-    let outcome: zbus::Result<Option<String>> = async {
-        let connection = Connection::session().await?;
-        let dbus = DBusProxy::new(&connection).await?;
-
-        let players: Vec<String> = dbus
-            .list_names()
-            .await?
-            .into_iter()
-            .map(|name| name.to_string())
-            .filter(|name| is_mpris_player(name))
-            .collect();
-
-        let resolved = match &target {
-            Some(target) => resolve_pinned(players.iter(), target),
-            None => {
-                let mut state = MprisState::default();
-
-                for name in &players {
-                    let playing = query_playing(&connection, name).await;
-                    state.on_appear(name.clone(), playing);
-                }
+/// Sends play/pause to the pinned player, or the active one if none is pinned, and returns its label.
+async fn send_play_pause(target: Option<String>) -> zbus::Result<Option<String>> {
+    let connection = Connection::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
 
-                state.active
+    let players: Vec<String> = dbus
+        .list_names()
+        .await?
+        .into_iter()
+        .map(|name| name.to_string())
+        .filter(|name| is_mpris_player(name))
+        .collect();
+
+    let resolved = match &target {
+        Some(target) => resolve_pinned(players.iter(), target),
+        None => {
+            let mut state = MprisState::default();
+
+            for name in &players {
+                let playing = query_playing(&connection, name).await;
+                state.on_appear(name.clone(), playing);
             }
-        };
 
-        let Some(name) = resolved else {
-            return Ok(None);
-        };
+            state.active
+        }
+    };
+
+    let Some(name) = resolved else {
+        return Ok(None);
+    };
+
+    dispatch_command(&connection, &name, MediaCommand::PlayPause).await?;
+    let label = identity(&connection, &name)
+        .await
+        .unwrap_or_else(|| player_segment(&name).to_string());
+    Ok(Some(label))
+}
+
+pub(super) async fn play_pause(config: &ConfigHandle<MediaControlConfig>) -> anyhow::Result<()> {
+    let target = config.read().await.target();
 
-        dispatch_command(&connection, &name, MediaCommand::PlayPause).await?;
-        let label = identity(&connection, &name)
-            .await
-            .unwrap_or_else(|| player_segment(&name).to_string());
-        Ok(Some(label))
+    match send_play_pause(target).await? {
+        Some(label) => info!("Sent play/pause to {}", label),
+        None => warn!("No matching player is running"),
     }
-    .await;
 
-    match outcome {
+    Ok(())
+}
+
+async fn test_play_pause(target: Option<String>) -> String {
+    match send_play_pause(target).await {
         Ok(Some(label)) => format!("Sent play/pause to {}", label),
         Ok(None) => "No matching player is running".to_string(),
         Err(error) => format!("Failed: {}", error),
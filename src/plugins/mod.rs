@@ -1,19 +1,50 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::osc_query::service::OscQueryServiceBuilder;
+use crate::plugins::parameters::{OscParameter, ParameterMap};
+use crate::tasks::notifications::Notification;
 use crate::utils::config::ConfigManager;
 use crate::AppWindow;
 use async_osc::OscMessage;
 use async_trait::async_trait;
+use log::warn;
 use slint::Weak;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::{broadcast, mpsc};
 use tokio_graceful_shutdown::SubsystemHandle;
 
 pub mod media_control;
+pub mod parameters;
 pub mod pishock;
 pub mod watch;
 
+/// An action a running plugin offers in its tray submenu.
+#[derive(Clone, PartialEq)]
+pub struct PluginTrayAction {
+    pub id: &'static str,
+    pub label: String,
+    pub enabled: bool,
+    /// Whether the action is shown with a check mark, `None` for plain actions.
+    pub checked: Option<bool>,
+}
+
+impl PluginTrayAction {
+    pub fn new(id: &'static str, label: impl Into<String>) -> Self {
+        Self {
+            id,
+            label: label.into(),
+            enabled: true,
+            checked: None,
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
 #[async_trait]
 pub trait Plugin: Send + Sync {
     fn new(config_manager: ConfigManager) -> Self
@@ -32,12 +63,39 @@ pub trait Plugin: Send + Sync {
         channels: Arc<ChannelManager>,
     ) -> anyhow::Result<()>;
 
-    fn register_osc_parameters(&self, service: &mut OscQueryServiceBuilder);
+    /// Parameters the plugin works with, addressed by their default address within the plugin.
+    fn osc_parameters(&self) -> &'static [OscParameter];
+
+    /// Whether the plugin picks up config changes on its own. Otherwise it is restarted whenever its config is replaced,
+    /// by reloading it from disk or by applying a profile or setup.
+    fn reacts_to_config_changes(&self) -> bool {
+        false
+    }
 
     fn has_settings(&self) -> bool {
         false
     }
 
+    /// Actions shown in the tray menu while the plugin is running. Queried again after each invocation and whenever
+    /// [`Plugin::tray_actions_changed`] completes.
+    async fn tray_actions(&self) -> Vec<PluginTrayAction> {
+        vec![]
+    }
+
+    /// Completes when the tray actions may have changed, so that they are queried again. Never completes by default.
+    async fn tray_actions_changed(&self) {
+        std::future::pending().await
+    }
+
+    /// Invoked when one of the tray actions was clicked, only while the plugin is running.
+    async fn invoke_tray_action(
+        &self,
+        _action_id: &str,
+        _channels: Arc<ChannelManager>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn register_settings_callbacks(self: Arc<Self>, _app_window: &AppWindow) -> anyhow::Result<()> {
         Ok(())
     }
@@ -50,26 +108,86 @@ pub trait Plugin: Send + Sync {
 pub struct ChannelManager {
     osc_broadcast: broadcast::Sender<OscMessage>,
     osc_sender: mpsc::Sender<OscMessage>,
+    notifications: mpsc::Sender<Notification>,
+    parameters: Arc<ParameterMap>,
 }
 
 impl ChannelManager {
     pub fn new(
         osc_broadcast: broadcast::Sender<OscMessage>,
         osc_sender: mpsc::Sender<OscMessage>,
+        notifications: mpsc::Sender<Notification>,
     ) -> Self {
         Self {
             osc_broadcast,
             osc_sender,
+            notifications,
+            parameters: Default::default(),
+        }
+    }
+
+    /// Creates channels which translate addresses according to the given parameter map.
+    pub fn with_parameters(&self, parameters: ParameterMap) -> Self {
+        Self {
+            osc_broadcast: self.osc_broadcast.clone(),
+            osc_sender: self.osc_sender.clone(),
+            notifications: self.notifications.clone(),
+            parameters: Arc::new(parameters),
         }
     }
 
-    pub fn subscribe_to_osc(&self) -> broadcast::Receiver<OscMessage> {
-        self.osc_broadcast.subscribe()
+    /// Shows a desktop notification, unless the user turned off its category.
+    pub fn notify(&self, notification: Notification) {
+        if let Err(error) = self.notifications.try_send(notification) {
+            warn!("Dropping notification: {}", error);
+        }
     }
 
-    pub fn create_osc_sender(&self) -> mpsc::Sender<OscMessage> {
-        self.osc_sender.clone()
+    pub fn subscribe_to_osc(&self) -> OscReceiver {
+        OscReceiver {
+            rx: self.osc_broadcast.subscribe(),
+            parameters: self.parameters.clone(),
+        }
     }
+
+    pub fn create_osc_sender(&self) -> OscSender {
+        OscSender {
+            tx: self.osc_sender.clone(),
+            parameters: self.parameters.clone(),
+        }
+    }
+}
+
+pub struct OscReceiver {
+    rx: broadcast::Receiver<OscMessage>,
+    parameters: Arc<ParameterMap>,
+}
+
+impl OscReceiver {
+    pub async fn recv(&mut self) -> Result<OscMessage, RecvError> {
+        loop {
+            if let Some(message) = self.parameters.incoming(self.rx.recv().await?) {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OscSender {
+    tx: mpsc::Sender<OscMessage>,
+    parameters: Arc<ParameterMap>,
+}
+
+impl OscSender {
+    pub async fn send(&self, message: OscMessage) -> Result<(), SendError<OscMessage>> {
+        self.tx.send(self.parameters.outgoing(message)).await
+    }
+}
+
+/// Returns the module path a plugin logs under, which the plugin ID is named after.
+pub fn log_module(plugin_id: &str) -> String {
+    format!("{}::{}", module_path!(), plugin_id)
 }
 
 macro_rules! define_plugins {
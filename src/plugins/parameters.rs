@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap};
+
+use async_osc::OscMessage;
+
+use crate::config::ParameterMappings;
+use crate::osc_query::node::OscAccess;
+
+pub const AVATAR_PARAMETERS_PREFIX: &str = "/avatar/parameters/";
+
+/// A logical parameter declared by a plugin. Users may map it to a different address.
+pub struct OscParameter {
+    pub name: &'static str,
+    pub osc_type: &'static str,
+    pub access: OscAccess,
+    pub description: &'static str,
+}
+
+impl OscParameter {
+    pub const fn new(
+        name: &'static str,
+        osc_type: &'static str,
+        access: OscAccess,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            osc_type,
+            access,
+            description,
+        }
+    }
+
+    pub fn default_address(&self) -> String {
+        expand_address(self.name)
+    }
+}
+
+/// Turns a bare parameter name into an avatar parameter address, full addresses are kept as is.
+pub fn expand_address(value: &str) -> String {
+    if value.starts_with('/') {
+        value.to_string()
+    } else {
+        format!("{}{}", AVATAR_PARAMETERS_PREFIX, value)
+    }
+}
+
+/// Sets the custom address of a parameter, an empty address or the parameter name itself restores the default.
+pub fn set_custom_address(
+    parameter_mappings: &mut ParameterMappings,
+    plugin_id: &str,
+    name: &str,
+    address: &str,
+) {
+    let mappings = parameter_mappings.entry(plugin_id.to_string()).or_default();
+
+    if address.is_empty() || address == name {
+        mappings.remove(name);
+    } else {
+        mappings.insert(name.to_string(), address.to_string());
+    }
+
+    if mappings.is_empty() {
+        parameter_mappings.remove(plugin_id);
+    }
+}
+
+/// Translates between the default addresses plugins work with and the addresses chosen by the user.
+#[derive(Default)]
+pub struct ParameterMap {
+    to_custom: HashMap<String, String>,
+    from_custom: HashMap<String, String>,
+    /// Custom addresses which are ignored as they clash with another parameter, keyed by parameter name.
+    conflicts: HashMap<&'static str, String>,
+}
+
+impl ParameterMap {
+    pub fn new(parameters: &[OscParameter], mappings: Option<&BTreeMap<String, String>>) -> Self {
+        let mut map = Self::default();
+
+        let Some(mappings) = mappings else {
+            return map;
+        };
+
+        let default_names: HashMap<String, &str> = parameters
+            .iter()
+            .map(|parameter| (parameter.default_address(), parameter.name))
+            .collect();
+
+        for parameter in parameters {
+            let Some(custom) = mappings
+                .get(parameter.name)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+            else {
+                continue;
+            };
+
+            let default_address = parameter.default_address();
+            let custom_address = expand_address(custom);
+
+            if custom_address == default_address {
+                continue;
+            }
+
+            // Messages on a shared address could not be told apart, so only the first parameter gets it.
+            let taken_by = default_names.get(&custom_address).or_else(|| {
+                map.from_custom
+                    .get(&custom_address)
+                    .and_then(|address| default_names.get(address))
+            });
+
+            if let Some(other) = taken_by {
+                map.conflicts.insert(
+                    parameter.name,
+                    format!("{} is already used by {}", custom_address, other),
+                );
+                continue;
+            }
+
+            map.to_custom
+                .insert(default_address.clone(), custom_address.clone());
+            map.from_custom.insert(custom_address, default_address);
+        }
+
+        map
+    }
+
+    /// Describes why the custom address of the parameter is ignored, if it is.
+    pub fn conflict(&self, parameter: &OscParameter) -> Option<&str> {
+        self.conflicts.get(parameter.name).map(String::as_str)
+    }
+
+    pub fn address(&self, parameter: &OscParameter) -> String {
+        let default_address = parameter.default_address();
+
+        match self.to_custom.get(&default_address) {
+            Some(custom_address) => custom_address.clone(),
+            None => default_address,
+        }
+    }
+
+    pub(super) fn outgoing(&self, mut message: OscMessage) -> OscMessage {
+        if let Some(custom_address) = self.to_custom.get(&message.addr) {
+            message.addr = custom_address.clone();
+        }
+
+        message
+    }
+
+    /// Returns `None` for messages on a default address which has been mapped elsewhere.
+    pub(super) fn incoming(&self, mut message: OscMessage) -> Option<OscMessage> {
+        if let Some(default_address) = self.from_custom.get(&message.addr) {
+            message.addr = default_address.clone();
+            return Some(message);
+        }
+
+        if self.to_custom.contains_key(&message.addr) {
+            return None;
+        }
+
+        Some(message)
+    }
+}
@@ -1,12 +1,16 @@
 use std::collections::HashSet;
 use std::convert::Infallible;
+use std::future::Future;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::config::NotificationCategory;
+use crate::metrics::metrics;
 use crate::osc_query::node::OscAccess;
-use crate::osc_query::service::OscQueryServiceBuilder;
-use crate::plugins::{ChannelManager, Plugin};
-use crate::utils::config::{ConfigHandle, ConfigManager};
+use crate::plugins::parameters::OscParameter;
+use crate::plugins::{ChannelManager, OscSender, Plugin, PluginTrayAction};
+use crate::tasks::notifications::Notification;
+use crate::utils::config::{Config, ConfigHandle, ConfigManager, Secrets};
 use crate::{AppWindow, PishockSettings, Router};
 use anyhow::anyhow;
 use async_osc::{prelude::OscMessageExt, OscMessage, OscType};
@@ -17,7 +21,7 @@ use serde_repr::Serialize_repr;
 use slint::{ComponentHandle, Weak};
 use tokio::select;
 use tokio::sync::broadcast::error::RecvError;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
 use tokio::task::JoinSet;
 use tokio::time::sleep;
 use tokio_graceful_shutdown::{
@@ -25,6 +29,44 @@ use tokio_graceful_shutdown::{
 };
 use tokio_util::sync::CancellationToken;
 
+const API_KEY_SECRET: &str = "api_key";
+const RESET_INTENSITY_ACTION: &str = "reset_intensity";
+
+const OSC_PARAMETERS: &[OscParameter] = &[
+    OscParameter::new(
+        "PS_Minus_Pressed",
+        "b",
+        OscAccess::Write,
+        "Minus button pressed",
+    ),
+    OscParameter::new(
+        "PS_Plus_Pressed",
+        "b",
+        OscAccess::Write,
+        "Plus button pressed",
+    ),
+    OscParameter::new(
+        "PS_ShockLeft_Pressed",
+        "b",
+        OscAccess::Write,
+        "Left shock button pressed",
+    ),
+    OscParameter::new(
+        "PS_ShockRight_Pressed",
+        "b",
+        OscAccess::Write,
+        "Right shock button pressed",
+    ),
+    OscParameter::new("PS_Intensity", "d", OscAccess::ReadWrite, "Shock intensity"),
+    OscParameter::new(
+        "PS_IntensityCap",
+        "d",
+        OscAccess::ReadWrite,
+        "Shock intensity cap",
+    ),
+    OscParameter::new("PS_QuickShock", "d", OscAccess::ReadWrite, "Quick shock"),
+];
+
 #[derive(Clone, Copy, Serialize_repr)]
 #[repr(u8)]
 #[allow(dead_code)]
@@ -66,26 +108,41 @@ struct ShockerInfo {
     shocker_id: u64,
 }
 
+#[derive(Clone)]
 struct ApiContext {
     client: reqwest::Client,
+    channels: Arc<ChannelManager>,
     api_key: String,
     shocker_ids: Arc<Vec<u64>>,
     duration: u8,
 }
 
+async fn timed_request<T>(
+    endpoint: &str,
+    request: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let started_at = Instant::now();
+    let result = request.await;
+    metrics().record_pishock_request(endpoint, started_at.elapsed(), result.is_ok());
+    result
+}
+
 async fn fetch_user_id(
     client: &reqwest::Client,
     api_key: &str,
     username: &str,
 ) -> anyhow::Result<u64> {
-    let response = client
-        .get("https://auth.pishock.com/Auth/GetUserIfAPIKeyValid")
-        .query(&[("apikey", api_key), ("username", username)])
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<UserIdResponse>()
-        .await?;
+    let response = timed_request("get_user", async {
+        Ok(client
+            .get("https://auth.pishock.com/Auth/GetUserIfAPIKeyValid")
+            .query(&[("apikey", api_key), ("username", username)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<UserIdResponse>()
+            .await?)
+    })
+    .await?;
 
     Ok(response.user_id)
 }
@@ -96,18 +153,21 @@ async fn fetch_shocker_ids(
     user_id: u64,
 ) -> anyhow::Result<Vec<u64>> {
     let user_id_str = user_id.to_string();
-    let devices = client
-        .get("https://ps.pishock.com/PiShock/GetUserDevices")
-        .query(&[
-            ("UserId", user_id_str.as_str()),
-            ("Token", api_key),
-            ("api", "true"),
-        ])
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<Vec<DeviceResponse>>()
-        .await?;
+    let devices = timed_request("get_devices", async {
+        Ok(client
+            .get("https://ps.pishock.com/PiShock/GetUserDevices")
+            .query(&[
+                ("UserId", user_id_str.as_str()),
+                ("Token", api_key),
+                ("api", "true"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<DeviceResponse>>()
+            .await?)
+    })
+    .await?;
 
     let shocker_ids: Vec<u64> = devices
         .into_iter()
@@ -139,30 +199,34 @@ async fn send_shock(
         intensity,
     };
 
-    let response = client
-        .post(format!("https://api.pishock.com/Shockers/{}", shocker_id))
-        .header("X-PiShock-Api-Key", &api_key)
-        .json(&body)
-        .send()
-        .await;
+    timed_request("operate", async {
+        let response = client
+            .post(format!("https://api.pishock.com/Shockers/{}", shocker_id))
+            .header("X-PiShock-Api-Key", &api_key)
+            .json(&body)
+            .send()
+            .await;
 
-    match response {
-        Ok(response) => {
-            if response.status().is_success() {
-                Ok(())
-            } else {
-                Err(anyhow!(
-                    "Shock request failed with status {}",
-                    response.status()
-                ))
+        match response {
+            Ok(response) => {
+                if response.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(anyhow!(
+                        "Shock request failed with status {}",
+                        response.status()
+                    ))
+                }
             }
+            Err(_) => Err(anyhow!("Failed to contact PiShock API")),
         }
-        Err(_) => Err(anyhow!("Failed to contact PiShock API")),
-    }
+    })
+    .await
 }
 
 async fn send_shocks(
     client: &reqwest::Client,
+    channels: &ChannelManager,
     api_key: &str,
     shocker_ids: &[u64],
     intensity: f32,
@@ -187,6 +251,7 @@ async fn send_shocks(
     }
 
     let mut succeeded = false;
+    let mut last_error = None;
 
     while let Some(res) = set.join_next().await {
         match res {
@@ -196,6 +261,7 @@ async fn send_shocks(
             }
             Ok(Err(error)) => {
                 warn!("{}", error);
+                last_error = Some(error.to_string());
             }
             Err(error) => {
                 error!("{}", error);
@@ -205,6 +271,12 @@ async fn send_shocks(
 
     if succeeded {
         let _ = activity_tx.send(duration).await;
+    } else if let Some(error) = last_error {
+        channels.notify(Notification::new(
+            NotificationCategory::PluginErrors,
+            "PiShock shock failed",
+            error,
+        ));
     }
 }
 
@@ -269,10 +341,7 @@ async fn run_connection_test(username: String, api_key: String) -> anyhow::Resul
 }
 
 struct ContinuousShockSender {
-    client: reqwest::Client,
-    api_key: String,
-    shocker_ids: Arc<Vec<u64>>,
-    duration: u8,
+    api: ApiContext,
     session_config: Arc<ConfigHandle<SessionConfig>>,
     cancellation_token: CancellationToken,
     activity_tx: mpsc::Sender<u8>,
@@ -280,19 +349,13 @@ struct ContinuousShockSender {
 
 impl ContinuousShockSender {
     pub fn new(
-        client: reqwest::Client,
-        api_key: String,
-        shocker_ids: Arc<Vec<u64>>,
-        duration: u8,
+        api: ApiContext,
         session_config: Arc<ConfigHandle<SessionConfig>>,
         cancellation_token: CancellationToken,
         activity_tx: mpsc::Sender<u8>,
     ) -> Self {
         Self {
-            client,
-            api_key,
-            shocker_ids,
-            duration,
+            api,
             session_config,
             cancellation_token,
             activity_tx,
@@ -304,18 +367,19 @@ impl ContinuousShockSender {
             let intensity = self.session_config.read().await.intensity;
 
             send_shocks(
-                &self.client,
-                &self.api_key,
-                &self.shocker_ids,
+                &self.api.client,
+                &self.api.channels,
+                &self.api.api_key,
+                &self.api.shocker_ids,
                 intensity,
-                self.duration,
+                self.api.duration,
                 &self.activity_tx,
             )
             .await;
 
             select! {
                 _ = self.cancellation_token.cancelled() => break,
-                _ = sleep(Duration::from_secs(self.duration as u64)) => continue,
+                _ = sleep(Duration::from_secs(self.api.duration as u64)) => continue,
             }
         }
     }
@@ -330,7 +394,7 @@ impl IntoSubsystem<Infallible> for ContinuousShockSender {
 
 struct IntensityModifier {
     base: f32,
-    osc_tx: mpsc::Sender<OscMessage>,
+    osc_tx: OscSender,
     session_config: Arc<ConfigHandle<SessionConfig>>,
     cancellation_token: CancellationToken,
 }
@@ -338,7 +402,7 @@ struct IntensityModifier {
 impl IntensityModifier {
     pub fn new(
         base: f32,
-        osc_tx: mpsc::Sender<OscMessage>,
+        osc_tx: OscSender,
         session_config: Arc<ConfigHandle<SessionConfig>>,
         cancellation_token: CancellationToken,
     ) -> Self {
@@ -401,11 +465,11 @@ impl IntoSubsystem<Infallible> for IntensityModifier {
 
 struct ActivityMonitor {
     activity_rx: mpsc::Receiver<u8>,
-    osc_tx: mpsc::Sender<OscMessage>,
+    osc_tx: OscSender,
 }
 
 impl ActivityMonitor {
-    fn new(activity_rx: mpsc::Receiver<u8>, osc_tx: mpsc::Sender<OscMessage>) -> Self {
+    fn new(activity_rx: mpsc::Receiver<u8>, osc_tx: OscSender) -> Self {
         Self {
             activity_rx,
             osc_tx,
@@ -461,11 +525,17 @@ impl IntoSubsystem<Infallible> for ActivityMonitor {
 #[serde(default)]
 pub struct CoreConfig {
     pub username: String,
+    /// Only read to migrate keys from older versions, the API key lives in the secret store.
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub api_key: String,
     pub duration: u8,
     pub user_id: Option<u64>,
 }
 
+impl Config for CoreConfig {
+    const SECRET_KEYS: &'static [&'static str] = &["api_key"];
+}
+
 impl Default for CoreConfig {
     fn default() -> Self {
         Self {
@@ -484,6 +554,8 @@ struct SessionConfig {
     intensity_cap: f32,
 }
 
+impl Config for SessionConfig {}
+
 impl SessionConfig {
     fn set_intensity(&mut self, intensity: f32) -> Option<f32> {
         self.intensity = intensity.clamp(0., 1.);
@@ -543,6 +615,9 @@ impl State {
 pub struct PiShock {
     core_config: Arc<ConfigHandle<CoreConfig>>,
     session_config: Arc<ConfigHandle<SessionConfig>>,
+    secrets: Secrets,
+    /// The API key from the secret store, `None` until it was loaded.
+    api_key: watch::Sender<Option<String>>,
     state: Arc<RwLock<State>>,
 }
 
@@ -564,60 +639,180 @@ impl PiShock {
             }
         }));
 
-        let config = self.core_config.read().await.clone();
-        let shocker_ids = if config.username.is_empty() || config.api_key.is_empty() {
+        let mut config_rx = self.core_config.subscribe();
+        let mut api_key_rx = self.api_key.subscribe();
+        let mut secrets_rx = self.secrets.subscribe_changes();
+        let api_key = self.load_api_key().await;
+        let mut config = config_rx.borrow_and_update().clone();
+        let mut api = self
+            .create_api_context(client, channels.clone(), &config, &api_key)
+            .await;
+
+        self.send_state(&osc_tx).await;
+
+        loop {
+            select! {
+                message = osc_rx.recv() => match message {
+                    Ok(message) => {
+                        self.handle_osc_messages(message, &osc_tx, subsys, &activity_tx, &api)
+                            .await?;
+                    }
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(skipped)) => {
+                        metrics().record_broadcast_lag("pishock", skipped);
+                        warn!(
+                            "PiShock lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                    }
+                },
+                result = config_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    let new_config = config_rx.borrow_and_update().clone();
+
+                    // A config file edited by hand or imported from a setup may bring back a plaintext key.
+                    if !new_config.api_key.is_empty() {
+                        self.migrate_api_key(&new_config.api_key).await;
+                    }
+
+                    // Resolving the shockers stores the user ID, which must not trigger another resolve.
+                    if new_config.username != config.username
+                        || new_config.duration != config.duration
+                    {
+                        info!("PiShock settings changed, reconnecting");
+                        api = self
+                            .create_api_context(
+                                api.client,
+                                api.channels,
+                                &new_config,
+                                &api.api_key.clone(),
+                            )
+                            .await;
+                    }
+
+                    config = new_config;
+                }
+                result = secrets_rx.recv() => match result {
+                    // A setup import stores the key without going through the plugin.
+                    Ok(name) if self.secrets.is_named(&name, API_KEY_SECRET) => {
+                        self.load_api_key().await;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                result = api_key_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    let Some(api_key) = api_key_rx.borrow_and_update().clone() else {
+                        continue;
+                    };
+
+                    if api_key != api.api_key {
+                        info!("PiShock credentials changed, reconnecting");
+                        config = config_rx.borrow_and_update().clone();
+                        api = self
+                            .create_api_context(api.client, api.channels, &config, &api_key)
+                            .await;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the API key, moving a plaintext key out of the config file first.
+    async fn load_api_key(&self) -> String {
+        let plaintext = self.core_config.read().await.api_key.clone();
+
+        if !plaintext.is_empty() {
+            self.migrate_api_key(&plaintext).await;
+            return plaintext;
+        }
+
+        let api_key = match self.secrets.get(API_KEY_SECRET).await {
+            Ok(api_key) => api_key.unwrap_or_default(),
+            Err(error) => {
+                error!("Failed to read PiShock API key: {:#}", error);
+                "".to_string()
+            }
+        };
+
+        self.api_key.send_replace(Some(api_key.clone()));
+        api_key
+    }
+
+    async fn migrate_api_key(&self, api_key: &str) {
+        // The key stays in use even if it could not be moved, in which case it is kept in the config file.
+        self.api_key.send_replace(Some(api_key.to_string()));
+
+        if let Err(error) = self.secrets.set(API_KEY_SECRET, api_key).await {
+            error!("Failed to store PiShock API key: {:#}", error);
+            return;
+        }
+
+        let _ = self
+            .core_config
+            .remove_secrets(|config| {
+                config.api_key.clear();
+                config.user_id = None;
+            })
+            .await;
+        info!("Moved PiShock API key into the secret store");
+    }
+
+    async fn create_api_context(
+        &self,
+        client: reqwest::Client,
+        channels: Arc<ChannelManager>,
+        config: &CoreConfig,
+        api_key: &str,
+    ) -> ApiContext {
+        let shocker_ids = if config.username.is_empty() || api_key.is_empty() {
             warn!("PiShock credentials not configured");
             vec![]
         } else {
-            match self.resolve_shocker_ids(&client, &config).await {
+            match self.resolve_shocker_ids(&client, config, api_key).await {
                 Ok(ids) => {
                     info!("Found {} shocker(s)", ids.len());
                     ids
                 }
                 Err(error) => {
                     warn!("Failed to initialize PiShock API: {}", error);
+                    channels.notify(Notification::new(
+                        NotificationCategory::PluginErrors,
+                        "Could not connect to PiShock",
+                        error.to_string(),
+                    ));
                     vec![]
                 }
             }
         };
 
-        let api = ApiContext {
+        ApiContext {
             client,
-            api_key: config.api_key.clone(),
+            channels,
+            api_key: api_key.to_string(),
             shocker_ids: Arc::new(shocker_ids),
             duration: config.duration,
-        };
-
-        self.send_state(&osc_tx).await;
-
-        loop {
-            match osc_rx.recv().await {
-                Ok(message) => {
-                    self.handle_osc_messages(message, &osc_tx, subsys, &activity_tx, &api)
-                        .await?;
-                }
-                Err(RecvError::Closed) => break,
-                Err(RecvError::Lagged(skipped)) => {
-                    warn!(
-                        "PiShock lagging behind, {} messages have been dropped",
-                        skipped
-                    );
-                }
-            }
         }
-
-        Ok(())
     }
 
     async fn resolve_shocker_ids(
         &self,
         client: &reqwest::Client,
         config: &CoreConfig,
+        api_key: &str,
     ) -> anyhow::Result<Vec<u64>> {
         let user_id = match config.user_id {
             Some(id) => id,
             None => {
-                let id = fetch_user_id(client, &config.api_key, &config.username).await?;
+                let id = fetch_user_id(client, api_key, &config.username).await?;
                 let _ = self
                     .core_config
                     .update(|c| {
@@ -628,13 +823,13 @@ impl PiShock {
             }
         };
 
-        fetch_shocker_ids(client, &config.api_key, user_id).await
+        fetch_shocker_ids(client, api_key, user_id).await
     }
 
     async fn handle_osc_messages(
         &self,
         message: OscMessage,
-        osc_tx: &mpsc::Sender<OscMessage>,
+        osc_tx: &OscSender,
         subsys: &SubsystemHandle,
         activity_tx: &mpsc::Sender<u8>,
         api: &ApiContext,
@@ -691,6 +886,7 @@ impl PiShock {
 
                 send_shocks(
                     &api.client,
+                    &api.channels,
                     &api.api_key,
                     &api.shocker_ids,
                     value.clamp(0., state.intensity_cap),
@@ -718,11 +914,7 @@ impl PiShock {
         }
     }
 
-    async fn check_modifier_state(
-        &self,
-        subsys: &SubsystemHandle,
-        osc_tx: &mpsc::Sender<OscMessage>,
-    ) {
+    async fn check_modifier_state(&self, subsys: &SubsystemHandle, osc_tx: &OscSender) {
         let mut state = self.state.write().await;
 
         match (
@@ -750,7 +942,7 @@ impl PiShock {
         &self,
         subsys: &SubsystemHandle,
         base: f32,
-        osc_tx: mpsc::Sender<OscMessage>,
+        osc_tx: OscSender,
     ) -> CancellationToken {
         let cancellation_token = CancellationToken::new();
 
@@ -785,20 +977,14 @@ impl PiShock {
                 let cancellation_token = CancellationToken::new();
 
                 subsys.start(SubsystemBuilder::new("ContinuousShockSender", {
-                    let client = api.client.clone();
-                    let api_key = api.api_key.clone();
-                    let shocker_ids = api.shocker_ids.clone();
-                    let duration = api.duration;
+                    let api = api.clone();
                     let session_config = self.session_config.clone();
                     let cancellation_token = cancellation_token.clone();
                     let activity_tx = activity_tx.clone();
 
                     async move |s: &mut SubsystemHandle| {
                         ContinuousShockSender::new(
-                            client,
-                            api_key,
-                            shocker_ids,
-                            duration,
+                            api,
                             session_config,
                             cancellation_token,
                             activity_tx,
@@ -818,7 +1004,7 @@ impl PiShock {
         }
     }
 
-    async fn send_state(&self, osc_tx: &mpsc::Sender<OscMessage>) {
+    async fn send_state(&self, osc_tx: &OscSender) {
         let state = self.session_config.read().await;
 
         let _ = osc_tx
@@ -835,24 +1021,62 @@ impl PiShock {
             .await;
     }
 
-    fn store_settings(&self, settings: &PishockSettings) -> anyhow::Result<()> {
+    fn store_settings(self: &Arc<Self>, app_window: &AppWindow) -> anyhow::Result<()> {
+        let settings = app_window.global::<PishockSettings>();
         let username = settings.get_username().to_string().trim().to_string();
         let api_key = settings.get_api_key().to_string().trim().to_string();
         let duration = settings.get_duration().clamp(1, 15) as u8;
 
+        // The field is empty while the key is still being loaded, which must not clear it.
+        let api_key_changed = match self.api_key.borrow().as_deref() {
+            Some(current) => current != api_key,
+            None => !api_key.is_empty(),
+        };
+
         self.core_config.blocking_update(|config| {
-            if config.username != username || config.api_key != api_key {
+            if config.username != username || api_key_changed {
                 config.user_id = None;
             }
             config.username = username.clone();
-            config.api_key = api_key.clone();
             config.duration = duration;
         })?;
 
+        if api_key_changed {
+            // The secret store may block for a while, e.g. while it is being unlocked, so keep it off the UI thread.
+            let plugin = self.clone();
+            let api_key = api_key.clone();
+            let app_window = app_window.as_weak();
+
+            std::thread::spawn(move || {
+                match plugin.secrets.blocking_set(API_KEY_SECRET, &api_key) {
+                    Ok(()) => {
+                        plugin.api_key.send_replace(Some(api_key));
+                    }
+                    Err(error) => {
+                        error!("Failed to store PiShock API key: {:#}", error);
+
+                        // Brings the settings back in case they were closed already, so the key can be saved again.
+                        let _ = app_window.upgrade_in_event_loop(move |handle| {
+                            let settings = handle.global::<PishockSettings>();
+                            settings.set_api_key(api_key.into());
+                            settings.set_is_dirty(true);
+                            settings.set_store_status(
+                                format!("Failed to store API key: {:#}", error).into(),
+                            );
+                            handle
+                                .global::<Router>()
+                                .set_settings_page("pishock".into());
+                        });
+                    }
+                }
+            });
+        }
+
         settings.set_username(username.into());
         settings.set_api_key(api_key.into());
         settings.set_duration(duration as i32);
         settings.set_is_dirty(false);
+        settings.set_store_status("".into());
 
         Ok(())
     }
@@ -864,6 +1088,8 @@ impl Plugin for PiShock {
         Self {
             core_config: Arc::new(config_manager.load_config(None, None)),
             session_config: Arc::new(config_manager.load_config(Some("state"), None)),
+            secrets: config_manager.secrets(),
+            api_key: watch::Sender::new(None),
             state: Arc::new(RwLock::new(State::default())),
         }
     }
@@ -900,55 +1126,67 @@ impl Plugin for PiShock {
         Ok(())
     }
 
-    fn register_osc_parameters(&self, service: &mut OscQueryServiceBuilder) {
-        service.add_endpoint(
-            "/avatar/parameters/PS_Minus_Pressed".to_string(),
-            "b".to_string(),
-            OscAccess::Write,
-            "Minus button pressed".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_Plus_Pressed".to_string(),
-            "b".to_string(),
-            OscAccess::Write,
-            "Plus button pressed".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_ShockLeft_Pressed".to_string(),
-            "b".to_string(),
-            OscAccess::Write,
-            "Left shock button pressed".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_ShockRight_Pressed".to_string(),
-            "b".to_string(),
-            OscAccess::Write,
-            "Right shock button pressed".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_Intensity".to_string(),
-            "d".to_string(),
-            OscAccess::ReadWrite,
-            "Shock intensity".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_IntensityCap".to_string(),
-            "d".to_string(),
-            OscAccess::ReadWrite,
-            "Shock intensity cap".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/PS_QuickShock".to_string(),
-            "d".to_string(),
-            OscAccess::ReadWrite,
-            "Quick shock".to_string(),
-        );
+    fn reacts_to_config_changes(&self) -> bool {
+        true
+    }
+
+    fn osc_parameters(&self) -> &'static [OscParameter] {
+        OSC_PARAMETERS
     }
 
     fn has_settings(&self) -> bool {
         true
     }
 
+    async fn tray_actions(&self) -> Vec<PluginTrayAction> {
+        let intensity = self.session_config.read().await.intensity;
+
+        vec![
+            PluginTrayAction::new(RESET_INTENSITY_ACTION, "Reset intensity to zero")
+                .enabled(intensity > 0.),
+        ]
+    }
+
+    async fn tray_actions_changed(&self) {
+        // Resetting the intensity is only offered while it is above zero.
+        let mut session_rx = self.session_config.subscribe();
+        let resettable = session_rx.borrow_and_update().intensity > 0.;
+
+        while session_rx.changed().await.is_ok() {
+            if (session_rx.borrow_and_update().intensity > 0.) != resettable {
+                return;
+            }
+        }
+
+        std::future::pending().await
+    }
+
+    async fn invoke_tray_action(
+        &self,
+        action_id: &str,
+        channels: Arc<ChannelManager>,
+    ) -> anyhow::Result<()> {
+        if action_id != RESET_INTENSITY_ACTION {
+            return Ok(());
+        }
+
+        self.session_config
+            .update(|config| {
+                config.set_intensity(0.);
+            })
+            .await?;
+
+        let _ = channels
+            .create_osc_sender()
+            .send(OscMessage {
+                addr: "/avatar/parameters/PS_Intensity".to_string(),
+                args: vec![OscType::Float(0.)],
+            })
+            .await;
+
+        Ok(())
+    }
+
     fn register_settings_callbacks(self: Arc<Self>, app_window: &AppWindow) -> anyhow::Result<()> {
         let settings = app_window.global::<PishockSettings>();
 
@@ -968,10 +1206,7 @@ impl Plugin for PiShock {
             let plugin = self.clone();
 
             move || {
-                let app_window = app_window.unwrap();
-                let settings = app_window.global::<PishockSettings>();
-
-                let _ = plugin.store_settings(&settings);
+                let _ = plugin.store_settings(&app_window.unwrap());
             }
         });
 
@@ -984,7 +1219,7 @@ impl Plugin for PiShock {
                 let settings = app_window.global::<PishockSettings>();
 
                 if settings.get_is_dirty() {
-                    let _ = plugin.store_settings(&settings);
+                    let _ = plugin.store_settings(&app_window);
                 }
 
                 app_window.global::<Router>().set_settings_page("".into());
@@ -1036,9 +1271,10 @@ impl Plugin for PiShock {
         let config = self.core_config.blocking_read().clone();
 
         settings.set_username(config.username.into());
-        settings.set_api_key(config.api_key.into());
+        settings.set_api_key(self.api_key.borrow().clone().unwrap_or_default().into());
         settings.set_duration(config.duration as i32);
         settings.set_is_dirty(false);
+        settings.set_store_status("".into());
         settings.set_test_status("".into());
         settings.set_test_running(false);
 
@@ -1046,6 +1282,23 @@ impl Plugin for PiShock {
             .global::<Router>()
             .set_settings_page("pishock".into());
 
+        // The key may have been changed by a setup import while the plugin was stopped, so it is always loaded again.
+        // The secret store may block for a while, e.g. while it is being unlocked, so keep it off the UI thread.
+        let app_window = app_window.as_weak();
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            let api_key = rt.block_on(self.load_api_key());
+
+            let _ = app_window.upgrade_in_event_loop(move |handle| {
+                let settings = handle.global::<PishockSettings>();
+
+                if !settings.get_is_dirty() {
+                    settings.set_api_key(api_key.into());
+                }
+            });
+        });
+
         Ok(())
     }
 }
@@ -8,10 +8,46 @@ use tokio::time::sleep;
 use tokio_graceful_shutdown::SubsystemHandle;
 
 use crate::osc_query::node::OscAccess;
-use crate::osc_query::service::OscQueryServiceBuilder;
-use crate::plugins::{ChannelManager, Plugin};
+use crate::plugins::parameters::OscParameter;
+use crate::plugins::{ChannelManager, OscSender, Plugin, PluginTrayAction};
 use crate::utils::config::ConfigManager;
 
+const OSC_PARAMETERS: &[OscParameter] = &[
+    OscParameter::new(
+        "RMBA_WatchHours",
+        "d",
+        OscAccess::Write,
+        "RMBA encoded hours",
+    ),
+    OscParameter::new(
+        "RMBA_WatchMinutes",
+        "d",
+        OscAccess::Write,
+        "RMBA encoded minutes",
+    ),
+];
+
+const SEND_TIME_ACTION: &str = "send_time";
+
+async fn send_time(sender: &OscSender) {
+    let now = Local::now();
+    let hour = ((now.hour() % 12) as f32 + now.minute() as f32 / 60.) / 6. - 1.;
+    let minute = (now.minute() as f32 + now.second() as f32 / 60.) / 30. - 1.;
+
+    let _ = sender
+        .send(OscMessage {
+            addr: "/avatar/parameters/RMBA_WatchHours".to_string(),
+            args: vec![OscType::Float(hour)],
+        })
+        .await;
+    let _ = sender
+        .send(OscMessage {
+            addr: "/avatar/parameters/RMBA_WatchMinutes".to_string(),
+            args: vec![OscType::Float(minute)],
+        })
+        .await;
+}
+
 pub struct Watch;
 
 #[async_trait]
@@ -40,39 +76,28 @@ impl Plugin for Watch {
         let sender = channels.create_osc_sender();
 
         loop {
-            let now = Local::now();
-            let hour = ((now.hour() % 12) as f32 + now.minute() as f32 / 60.) / 6. - 1.;
-            let minute = (now.minute() as f32 + now.second() as f32 / 60.) / 30. - 1.;
-
-            let _ = sender
-                .send(OscMessage {
-                    addr: "/avatar/parameters/RMBA_WatchHours".to_string(),
-                    args: vec![OscType::Float(hour)],
-                })
-                .await;
-            let _ = sender
-                .send(OscMessage {
-                    addr: "/avatar/parameters/RMBA_WatchMinutes".to_string(),
-                    args: vec![OscType::Float(minute)],
-                })
-                .await;
-
+            send_time(&sender).await;
             sleep(Duration::from_secs(10)).await;
         }
     }
 
-    fn register_osc_parameters(&self, service: &mut OscQueryServiceBuilder) {
-        service.add_endpoint(
-            "/avatar/parameters/RMBA_WatchHours".to_string(),
-            "d".to_string(),
-            OscAccess::Write,
-            "RMBA encoded hours".to_string(),
-        );
-        service.add_endpoint(
-            "/avatar/parameters/RMBA_WatchMinutes".to_string(),
-            "d".to_string(),
-            OscAccess::Write,
-            "RMBA encoded minutes".to_string(),
-        );
+    fn osc_parameters(&self) -> &'static [OscParameter] {
+        OSC_PARAMETERS
+    }
+
+    async fn tray_actions(&self) -> Vec<PluginTrayAction> {
+        vec![PluginTrayAction::new(SEND_TIME_ACTION, "Send time now")]
+    }
+
+    async fn invoke_tray_action(
+        &self,
+        action_id: &str,
+        channels: Arc<ChannelManager>,
+    ) -> anyhow::Result<()> {
+        if action_id == SEND_TIME_ACTION {
+            send_time(&channels.create_osc_sender()).await;
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,638 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_osc::{OscMessage, OscType};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::AvatarsConfig;
+use crate::tasks::plugin_manager::Command;
+use crate::utils::config::{ConfigHandle, ConfigIssue, ConfigRegistry, ManagedConfig};
+use crate::{AppWindow, AvatarItem, AvatarPluginToggle, Avatars};
+
+/// Where the regular values of overridden plugin settings are kept while an avatar's overrides are applied.
+const BASE_DIR: &str = ".base";
+
+/// Overrides applied over a plugin config, together with the regular values they replaced.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Layer {
+    /// Regular values of the overridden settings, where a missing key was not set at all.
+    base: toml::Table,
+    applied: toml::Table,
+}
+
+impl Layer {
+    /// Returns the given settings with the overridden values replaced by the regular ones.
+    fn restore(&self, table: &toml::Table) -> toml::Table {
+        let mut table = table.clone();
+
+        for key in self.applied.keys() {
+            match self.base.get(key) {
+                Some(value) => table.insert(key.clone(), value.clone()),
+                None => table.remove(key),
+            };
+        }
+
+        table
+    }
+}
+
+async fn read_table<D: for<'de> Deserialize<'de>>(path: &Path) -> anyhow::Result<D> {
+    Ok(toml::from_str(&read_to_string(path).await?)?)
+}
+
+async fn write_table<S: Serialize>(path: &Path, value: &S) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    write(path, toml::to_string_pretty(value)?).await?;
+    Ok(())
+}
+
+async fn live_table(config: &dyn ManagedConfig) -> anyhow::Result<toml::Table> {
+    Ok(toml::from_str(&config.snapshot().await)?)
+}
+
+pub enum AvatarCommand {
+    Rename(String, String),
+    SaveOverrides(String),
+    ClearOverrides(String),
+    SetPluginEnabled(String, String, bool),
+    Forget(String),
+}
+
+fn is_valid_avatar_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub struct AvatarsTask {
+    rx: mpsc::Receiver<AvatarCommand>,
+    osc_rx: broadcast::Receiver<OscMessage>,
+    config: ConfigHandle<AvatarsConfig>,
+    registry: ConfigRegistry,
+    plugin_manager_tx: mpsc::Sender<Command>,
+    app_window: Weak<AppWindow>,
+    plugins: Vec<(String, String)>,
+    avatars_dir: PathBuf,
+    current: Option<String>,
+    /// Regular plugin settings, without overrides, as of changing into the current avatar or the config being replaced.
+    regular: HashMap<PathBuf, toml::Table>,
+    changes_rx: broadcast::Receiver<PathBuf>,
+}
+
+impl AvatarsTask {
+    pub fn new(
+        rx: mpsc::Receiver<AvatarCommand>,
+        osc_rx: broadcast::Receiver<OscMessage>,
+        config: ConfigHandle<AvatarsConfig>,
+        registry: ConfigRegistry,
+        plugin_manager_tx: mpsc::Sender<Command>,
+        app_window: Weak<AppWindow>,
+        plugins: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            rx,
+            osc_rx,
+            config,
+            avatars_dir: registry.config_dir().join("avatars"),
+            changes_rx: registry.subscribe_changes(),
+            registry,
+            plugin_manager_tx,
+            app_window,
+            plugins,
+            current: None,
+            regular: HashMap::new(),
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        // A previous run may have ended while overrides were applied.
+        self.restore_base().await;
+        self.publish(None).await?;
+
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => {
+                        let status = match self.handle_command(command).await {
+                            Ok(()) => None,
+                            Err(error) => {
+                                warn!("Avatar operation failed: {:#}", error);
+                                Some(format!("{:#}", error))
+                            }
+                        };
+
+                        self.publish(status).await?;
+                    }
+                    None => break,
+                },
+                message = self.osc_rx.recv() => match message {
+                    Ok(message) => {
+                        if let ("/avatar/change", [OscType::String(avatar_id)]) =
+                            (message.addr.as_str(), message.args.as_slice())
+                        {
+                            if let Err(error) = self.change_avatar(avatar_id).await {
+                                warn!("Failed to apply avatar settings: {:#}", error);
+                            }
+
+                            self.publish(None).await?;
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Avatar monitor lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                    }
+                },
+                path = self.changes_rx.recv() => match path {
+                    Ok(path) => {
+                        if let Err(error) = self.rebase(&path).await {
+                            warn!("Failed to keep avatar settings applied: {:#}", error);
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => {}
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn base_dir(&self) -> PathBuf {
+        self.avatars_dir.join(BASE_DIR)
+    }
+
+    fn avatar_dir(&self, avatar_id: &str) -> PathBuf {
+        self.avatars_dir.join(avatar_id)
+    }
+
+    /// Returns the plugin configs along with their path relative to the config directory, which is also their path
+    /// within the directory of an avatar.
+    fn plugin_configs(&self) -> Vec<(PathBuf, Arc<dyn ManagedConfig>)> {
+        let config_dir = self.registry.config_dir();
+        let plugins_dir = config_dir.join("plugins");
+
+        self.registry
+            .configs()
+            .into_iter()
+            .filter(|config| config.path().starts_with(&plugins_dir))
+            .filter_map(|config| {
+                let relative_path = config.path().strip_prefix(config_dir).ok()?.to_path_buf();
+                Some((relative_path, config))
+            })
+            .collect()
+    }
+
+    async fn apply_table(
+        &self,
+        config: &dyn ManagedConfig,
+        table: &toml::Table,
+    ) -> anyhow::Result<()> {
+        if config.apply(&toml::to_string_pretty(table)?).await? {
+            self.registry.clear_issue(config.path());
+        }
+
+        Ok(())
+    }
+
+    /// Remembers the current plugin settings as the regular ones, before any overrides are applied.
+    async fn capture_regular(&mut self) -> anyhow::Result<()> {
+        self.regular.clear();
+
+        for (relative_path, config) in self.plugin_configs() {
+            self.regular
+                .insert(relative_path, live_table(&*config).await?);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the overrides of the given avatar over the current plugin settings.
+    async fn apply_overrides(&self, avatar_id: &str) -> anyhow::Result<bool> {
+        let avatar_dir = self.avatar_dir(avatar_id);
+        let mut applied = false;
+
+        for (relative_path, config) in self.plugin_configs() {
+            let source = avatar_dir.join(&relative_path);
+
+            if !source.is_file() {
+                continue;
+            }
+
+            let overrides: toml::Table = read_table(&source).await?;
+            let mut table = live_table(&*config).await?;
+            let mut layer = Layer::default();
+
+            for (key, value) in overrides {
+                if let Some(base) = table.insert(key.clone(), value.clone()) {
+                    layer.base.insert(key.clone(), base);
+                }
+
+                layer.applied.insert(key, value);
+            }
+
+            // Written first, so that the regular values are restored on the next start if applying is interrupted.
+            write_table(&self.base_dir().join(&relative_path), &layer).await?;
+            self.apply_table(&*config, &table).await?;
+            applied = true;
+        }
+
+        Ok(applied)
+    }
+
+    /// Saves the plugin settings which differ from the regular ones as overrides of the given avatar.
+    async fn save_overrides(&self, avatar_id: &str) -> anyhow::Result<()> {
+        let avatar_dir = self.avatar_dir(avatar_id);
+        let mut saved = false;
+
+        for (relative_path, config) in self.plugin_configs() {
+            let table = live_table(&*config).await?;
+            let regular = self.regular.get(&relative_path).unwrap_or(&table);
+            let mut layer = Layer::default();
+
+            for (key, value) in &table {
+                if regular.get(key) == Some(value) {
+                    continue;
+                }
+
+                if let Some(base) = regular.get(key) {
+                    layer.base.insert(key.clone(), base.clone());
+                }
+
+                layer.applied.insert(key.clone(), value.clone());
+            }
+
+            let target = avatar_dir.join(&relative_path);
+            let layer_path = self.base_dir().join(&relative_path);
+
+            if layer.applied.is_empty() {
+                for path in [target, layer_path] {
+                    if path.is_file() {
+                        remove_file(&path).await?;
+                    }
+                }
+
+                continue;
+            }
+
+            write_table(&target, &layer.applied).await?;
+            write_table(&layer_path, &layer).await?;
+            saved = true;
+        }
+
+        if !saved {
+            for dir in [avatar_dir, self.base_dir()] {
+                if dir.is_dir() {
+                    remove_dir_all(&dir).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves changes made to overridden settings while the given avatar was worn back to its overrides.
+    async fn update_overrides(&self, avatar_id: &str) -> anyhow::Result<()> {
+        let avatar_dir = self.avatar_dir(avatar_id);
+
+        for (relative_path, config) in self.plugin_configs() {
+            let layer_path = self.base_dir().join(&relative_path);
+
+            if !layer_path.is_file() {
+                continue;
+            }
+
+            let mut layer: Layer = read_table(&layer_path).await?;
+            let table = live_table(&*config).await?;
+
+            for (key, value) in layer.applied.iter_mut() {
+                if let Some(current) = table.get(key) {
+                    *value = current.clone();
+                }
+            }
+
+            write_table(&avatar_dir.join(&relative_path), &layer.applied).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Layers the applied overrides over a plugin config which was replaced, e.g. by a profile or an edit on disk, and
+    /// keeps its new values as the regular settings.
+    async fn rebase(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Some((relative_path, config)) = self
+            .plugin_configs()
+            .into_iter()
+            .find(|(_, config)| config.path() == path)
+        else {
+            return Ok(());
+        };
+
+        let mut table = live_table(&*config).await?;
+        let layer_path = self.base_dir().join(&relative_path);
+
+        if !layer_path.is_file() {
+            self.regular.insert(relative_path, table);
+            return Ok(());
+        }
+
+        let mut layer: Layer = read_table(&layer_path).await?;
+        let mut changed = false;
+
+        for (key, value) in &layer.applied {
+            if table.get(key) == Some(value) {
+                continue;
+            }
+
+            match table.insert(key.clone(), value.clone()) {
+                Some(base) => layer.base.insert(key.clone(), base),
+                None => layer.base.remove(key),
+            };
+            changed = true;
+        }
+
+        self.regular.insert(relative_path, layer.restore(&table));
+
+        if changed {
+            write_table(&layer_path, &layer).await?;
+            self.apply_table(&*config, &table).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Restores the regular values of all overridden settings, keeping all other changes.
+    ///
+    /// A layer which cannot be restored is reported and dropped, so that it does not keep the task from running.
+    async fn restore_base(&self) {
+        let base_dir = self.base_dir();
+
+        if !base_dir.is_dir() {
+            return;
+        }
+
+        for (relative_path, config) in self.plugin_configs() {
+            let layer_path = base_dir.join(&relative_path);
+
+            if !layer_path.is_file() {
+                continue;
+            }
+
+            if let Err(error) = self.restore_layer(&*config, &layer_path).await {
+                error!(
+                    "Failed to restore regular settings from {}: {:#}",
+                    layer_path.to_string_lossy(),
+                    error
+                );
+                self.registry.report_issue(ConfigIssue {
+                    path: config.path().to_path_buf(),
+                    name: config.name().to_string(),
+                    summary: format!(
+                        "Could not restore the regular settings of {}",
+                        config.name()
+                    ),
+                    message: format!(
+                        "The settings of the previous avatar may still be in place. {:#}",
+                        error
+                    ),
+                    line: None,
+                    preserved_path: None,
+                });
+            }
+        }
+
+        match remove_dir_all(&base_dir).await {
+            Ok(()) => info!("Restored regular plugin settings"),
+            Err(error) => error!("Failed to remove {}: {}", base_dir.to_string_lossy(), error),
+        }
+    }
+
+    async fn restore_layer(
+        &self,
+        config: &dyn ManagedConfig,
+        layer_path: &Path,
+    ) -> anyhow::Result<()> {
+        let layer: Layer = read_table(layer_path).await?;
+        let table = live_table(config).await?;
+        self.apply_table(config, &layer.restore(&table)).await
+    }
+
+    async fn change_avatar(&mut self, avatar_id: &str) -> anyhow::Result<()> {
+        if self.current.as_deref() == Some(avatar_id) {
+            return Ok(());
+        }
+
+        if !is_valid_avatar_id(avatar_id) {
+            bail!("Ignoring invalid avatar ID: {}", avatar_id);
+        }
+
+        if let Some(previous) = self.current.take() {
+            if self.base_dir().is_dir() {
+                self.update_overrides(&previous).await?;
+                self.restore_base().await;
+            }
+        }
+
+        info!("Avatar changed to {}", avatar_id);
+        self.current = Some(avatar_id.to_string());
+
+        if !self.config.read().await.avatars.contains_key(avatar_id) {
+            self.config
+                .update(|config| {
+                    config
+                        .avatars
+                        .insert(avatar_id.to_string(), Default::default());
+                })
+                .await?;
+        }
+
+        self.capture_regular().await?;
+
+        if self.avatar_dir(avatar_id).is_dir() && self.apply_overrides(avatar_id).await? {
+            info!("Applied plugin settings for avatar {}", avatar_id);
+        }
+
+        self.apply_suppressed_plugins().await
+    }
+
+    async fn apply_suppressed_plugins(&self) -> anyhow::Result<()> {
+        let suppressed: HashSet<String> = match &self.current {
+            Some(current) => self
+                .config
+                .read()
+                .await
+                .avatars
+                .get(current)
+                .map(|entry| entry.disabled_plugins.iter().cloned().collect())
+                .unwrap_or_default(),
+            None => HashSet::new(),
+        };
+
+        self.plugin_manager_tx
+            .send(Command::SetSuppressed(suppressed))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn handle_command(&mut self, command: AvatarCommand) -> anyhow::Result<()> {
+        match command {
+            AvatarCommand::Rename(avatar_id, name) => {
+                self.config
+                    .update(|config| {
+                        if let Some(entry) = config.avatars.get_mut(&avatar_id) {
+                            entry.name = name.trim().to_string();
+                        }
+                    })
+                    .await?;
+            }
+            AvatarCommand::SaveOverrides(avatar_id) => {
+                if self.current.as_deref() != Some(avatar_id.as_str()) {
+                    bail!("Settings can only be saved for the avatar you are currently wearing");
+                }
+
+                self.save_overrides(&avatar_id).await?;
+                info!("Saved plugin settings for avatar {}", avatar_id);
+            }
+            AvatarCommand::ClearOverrides(avatar_id) => self.clear_overrides(&avatar_id).await?,
+            AvatarCommand::SetPluginEnabled(avatar_id, plugin_id, enabled) => {
+                self.config
+                    .update(|config| {
+                        if let Some(entry) = config.avatars.get_mut(&avatar_id) {
+                            if enabled {
+                                entry.disabled_plugins.remove(&plugin_id);
+                            } else {
+                                entry.disabled_plugins.insert(plugin_id);
+                            }
+                        }
+                    })
+                    .await?;
+
+                if self.current.as_deref() == Some(avatar_id.as_str()) {
+                    self.apply_suppressed_plugins().await?;
+                }
+            }
+            AvatarCommand::Forget(avatar_id) => {
+                self.clear_overrides(&avatar_id).await?;
+                self.config
+                    .update(|config| {
+                        config.avatars.remove(&avatar_id);
+                    })
+                    .await?;
+
+                if self.current.as_deref() == Some(avatar_id.as_str()) {
+                    self.apply_suppressed_plugins().await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn clear_overrides(&mut self, avatar_id: &str) -> anyhow::Result<()> {
+        if !is_valid_avatar_id(avatar_id) {
+            bail!("Invalid avatar ID: {}", avatar_id);
+        }
+
+        if self.current.as_deref() == Some(avatar_id) {
+            self.restore_base().await;
+        }
+
+        let avatar_dir = self.avatar_dir(avatar_id);
+
+        if avatar_dir.is_dir() {
+            remove_dir_all(&avatar_dir).await?;
+            info!("Cleared plugin settings for avatar {}", avatar_id);
+        }
+
+        Ok(())
+    }
+
+    async fn publish(&self, status: Option<String>) -> anyhow::Result<()> {
+        let config = self.config.read().await;
+        let mut avatar_ids: Vec<&String> = config.avatars.keys().collect();
+
+        // Show the current avatar first, then the rest by name.
+        avatar_ids.sort_by_cached_key(|id| {
+            let name = &config.avatars[*id].name;
+            (
+                self.current.as_ref() != Some(*id),
+                if name.is_empty() { *id } else { name }.to_lowercase(),
+            )
+        });
+
+        // Models are not Send, so only plain data is moved into the event loop.
+        let rows: Vec<_> = avatar_ids
+            .into_iter()
+            .map(|id| {
+                let entry = &config.avatars[id];
+                let plugins: Vec<AvatarPluginToggle> = self
+                    .plugins
+                    .iter()
+                    .map(|(plugin_id, title)| AvatarPluginToggle {
+                        id: plugin_id.clone().into(),
+                        title: title.clone().into(),
+                        enabled: !entry.disabled_plugins.contains(plugin_id),
+                    })
+                    .collect();
+
+                (
+                    id.clone(),
+                    entry.name.clone(),
+                    self.current.as_ref() == Some(id),
+                    self.avatar_dir(id).is_dir(),
+                    plugins,
+                )
+            })
+            .collect();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let items: Vec<AvatarItem> = rows
+                .into_iter()
+                .map(|(id, name, current, has_overrides, plugins)| AvatarItem {
+                    id: id.into(),
+                    name: name.into(),
+                    current,
+                    has_overrides,
+                    plugins: ModelRc::new(VecModel::from(plugins)),
+                })
+                .collect();
+
+            let avatars = handle.global::<Avatars>();
+            avatars.set_avatars(ModelRc::new(VecModel::from(items)));
+            avatars.set_status(status.unwrap_or_default().into());
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for AvatarsTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -4,10 +4,14 @@ use searchlight::net::IpVersion;
 use std::net::{IpAddr, Ipv4Addr};
 use std::str::FromStr;
 use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 use tokio::time::sleep;
 use tokio_graceful_shutdown::errors::CancelledByShutdown;
 use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
 
+use crate::config::NotificationCategory;
+use crate::tasks::notifications::Notification;
+
 const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
 const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 const NOTIFY_AFTER: Duration = Duration::from_secs(60);
@@ -15,13 +19,19 @@ const NOTIFY_AFTER: Duration = Duration::from_secs(60);
 pub struct BroadcasterTask {
     osc_listener_port: u16,
     osc_query_port: u16,
+    notifications_tx: mpsc::Sender<Notification>,
 }
 
 impl BroadcasterTask {
-    pub fn new(osc_listener_port: u16, osc_query_port: u16) -> Self {
+    pub fn new(
+        osc_listener_port: u16,
+        osc_query_port: u16,
+        notifications_tx: mpsc::Sender<Notification>,
+    ) -> Self {
         Self {
             osc_listener_port,
             osc_query_port,
+            notifications_tx,
         }
     }
 
@@ -76,7 +86,16 @@ impl BroadcasterTask {
                     }
 
                     if !notified && started_at.elapsed() >= NOTIFY_AFTER {
-                        notify_failure(&error.to_string());
+                        let _ = self
+                            .notifications_tx
+                            .send(Notification::new(
+                                NotificationCategory::ServiceDiscovery,
+                                "Service discovery unavailable",
+                                format!(
+                                    "Could not start the mDNS broadcaster. VRChat will not auto-discover the manager until the network comes up.\n\n{error}"
+                                ),
+                            ))
+                            .await;
                         notified = true;
                     }
 
@@ -88,20 +107,6 @@ impl BroadcasterTask {
     }
 }
 
-fn notify_failure(detail: &str) {
-    let result = notify_rust::Notification::new()
-        .appname("VRC OSC Manager")
-        .summary("VRC OSC Manager: service discovery unavailable")
-        .body(&format!(
-            "Could not start the mDNS broadcaster. VRChat will not auto-discover the manager until the network comes up.\n\n{detail}"
-        ))
-        .show();
-
-    if let Err(error) = result {
-        warn!("Failed to show broadcaster failure notification: {error}");
-    }
-}
-
 impl IntoSubsystem<anyhow::Error> for BroadcasterTask {
     async fn run(self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
         let handle = match self.start_with_retry().cancel_on_shutdown(subsys).await {
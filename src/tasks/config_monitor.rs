@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+use std::future::pending;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::select;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::NotificationCategory;
+use crate::tasks::notifications::{Notification, NotificationAction};
+use crate::utils::config::{ConfigIssue, ConfigRegistry};
+use crate::{AppWindow, ConfigFileItem, ConfigIssueItem, ConfigStatus};
+
+pub enum ConfigCommand {
+    Retry(PathBuf),
+    Reset(PathBuf),
+    RestorePrevious(PathBuf),
+}
+
+/// Editors tend to save in several steps, so wait for things to settle before reloading.
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => pending().await,
+    }
+}
+
+pub struct ConfigMonitorTask {
+    rx: mpsc::Receiver<ConfigCommand>,
+    registry: ConfigRegistry,
+    issues_rx: watch::Receiver<Vec<ConfigIssue>>,
+    app_window: Weak<AppWindow>,
+    notifications_tx: mpsc::Sender<Notification>,
+    changed_paths: HashSet<PathBuf>,
+    reload_deadline: Option<Instant>,
+    /// Files with issues which have already been notified about.
+    notified_issues: HashSet<PathBuf>,
+}
+
+impl ConfigMonitorTask {
+    pub fn new(
+        rx: mpsc::Receiver<ConfigCommand>,
+        registry: ConfigRegistry,
+        app_window: Weak<AppWindow>,
+        notifications_tx: mpsc::Sender<Notification>,
+    ) -> Self {
+        Self {
+            rx,
+            issues_rx: registry.subscribe_issues(),
+            registry,
+            app_window,
+            notifications_tx,
+            changed_paths: HashSet::new(),
+            reload_deadline: None,
+            notified_issues: HashSet::new(),
+        }
+    }
+
+    fn watch_config_dir(
+        &self,
+        tx: mpsc::UnboundedSender<PathBuf>,
+    ) -> anyhow::Result<RecommendedWatcher> {
+        let config_dir = self.registry.config_dir();
+        std::fs::create_dir_all(config_dir)?;
+
+        let mut watcher = recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) => {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+            Err(error) => warn!("Config watcher error: {}", error),
+        })?;
+        watcher.watch(config_dir, RecursiveMode::Recursive)?;
+
+        Ok(watcher)
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        self.publish_files()?;
+        self.publish_issues()?;
+
+        let (changed_tx, mut changed_rx) = mpsc::unbounded_channel();
+        let _watcher = match self.watch_config_dir(changed_tx) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                error!(
+                    "Failed to watch config directory, hot reload is disabled: {}",
+                    error
+                );
+                None
+            }
+        };
+
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => break,
+                },
+                Some(path) = changed_rx.recv() => {
+                    if self.registry.get(&path).is_some() {
+                        self.changed_paths.insert(path);
+                        self.reload_deadline = Some(Instant::now() + RELOAD_DELAY);
+                    }
+                }
+                _ = wait_until(self.reload_deadline) => {
+                    self.reload_deadline = None;
+                    self.reload_changed().await;
+                }
+                result = self.issues_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    self.publish_issues()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn reload_changed(&mut self) {
+        for path in self.changed_paths.drain() {
+            let Some(config) = self.registry.get(&path) else {
+                continue;
+            };
+
+            if !config.changed_on_disk() {
+                debug!("Ignoring own write to {}", path.to_string_lossy());
+                continue;
+            }
+
+            match config.reload().await {
+                Ok(()) => self.registry.clear_issue(&path),
+                Err(issue) => {
+                    warn!(
+                        "Failed to reload config file {}: {}",
+                        path.to_string_lossy(),
+                        issue.message
+                    );
+                    self.registry.report_issue(issue);
+                }
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, command: ConfigCommand) {
+        let path = match &command {
+            ConfigCommand::Retry(path)
+            | ConfigCommand::Reset(path)
+            | ConfigCommand::RestorePrevious(path) => path,
+        };
+
+        let Some(config) = self.registry.get(path) else {
+            warn!("Unknown config file: {}", path.to_string_lossy());
+            return;
+        };
+
+        match command {
+            ConfigCommand::Retry(path) => match config.reload().await {
+                Ok(()) => self.registry.clear_issue(&path),
+                Err(issue) => self.registry.report_issue(issue),
+            },
+            ConfigCommand::Reset(path) => match config.reset().await {
+                Ok(()) => self.registry.clear_issue(&path),
+                Err(error) => error!(
+                    "Failed to reset config file {}: {}",
+                    path.to_string_lossy(),
+                    error
+                ),
+            },
+            ConfigCommand::RestorePrevious(path) => match config.restore_previous().await {
+                Ok(()) => {
+                    self.registry.clear_issue(&path);
+                    self.set_status(format!("Restored previous version of {}", config.name()));
+                }
+                Err(error) => {
+                    info!(
+                        "Could not restore config file {}: {:#}",
+                        path.to_string_lossy(),
+                        error
+                    );
+                    self.set_status(format!("Could not restore {}: {:#}", config.name(), error));
+                }
+            },
+        }
+    }
+
+    fn set_status(&self, status: String) {
+        let _ = self.app_window.upgrade_in_event_loop(move |handle| {
+            handle.global::<ConfigStatus>().set_status(status.into());
+        });
+    }
+
+    fn publish_files(&self) -> anyhow::Result<()> {
+        let mut files: Vec<ConfigFileItem> = self
+            .registry
+            .configs()
+            .iter()
+            .map(|config| ConfigFileItem {
+                path: config.path().to_string_lossy().to_string().into(),
+                name: config.name().into(),
+            })
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            handle
+                .global::<ConfigStatus>()
+                .set_files(ModelRc::new(VecModel::from(files)));
+        })?;
+
+        Ok(())
+    }
+
+    fn publish_issues(&mut self) -> anyhow::Result<()> {
+        let issues = self.issues_rx.borrow_and_update().clone();
+        self.notified_issues
+            .retain(|path| issues.iter().any(|issue| &issue.path == path));
+
+        for issue in &issues {
+            if !self.notified_issues.insert(issue.path.clone()) {
+                continue;
+            }
+
+            let _ = self.notifications_tx.try_send(
+                Notification::new(
+                    NotificationCategory::ConfigProblems,
+                    issue.summary.clone(),
+                    issue.message.clone(),
+                )
+                .with_action(NotificationAction::ShowWindow),
+            );
+        }
+
+        let items: Vec<ConfigIssueItem> = issues
+            .iter()
+            .map(|issue| ConfigIssueItem {
+                path: issue.path.to_string_lossy().to_string().into(),
+                name: issue.name.clone().into(),
+                summary: issue.summary.clone().into(),
+                message: match issue.line {
+                    Some(line) => format!("Line {}: {}", line, issue.message),
+                    None => issue.message.clone(),
+                }
+                .into(),
+                preserved_path: issue
+                    .preserved_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default()
+                    .into(),
+            })
+            .collect();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            handle
+                .global::<ConfigStatus>()
+                .set_issues(ModelRc::new(VecModel::from(items)));
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for ConfigMonitorTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
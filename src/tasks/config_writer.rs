@@ -1,8 +1,12 @@
-use log::error;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::utils::config::{backup_path, ConfigIssue, ConfigRegistry, FileStamp, BACKUP_COUNT};
+use anyhow::Context;
+use log::{error, warn};
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
-use tokio::fs::{create_dir_all, write};
+use tokio::fs::{copy, create_dir_all, rename, File};
+use tokio::io::AsyncWriteExt;
 use tokio::select;
 use tokio::sync::mpsc;
 use tokio::time::interval;
@@ -13,18 +17,26 @@ pub struct WriteConfigRequest {
     pub path: PathBuf,
     pub config: String,
     pub debounce: Option<Duration>,
+    pub stamp: FileStamp,
+    pub generation: u64,
+    /// Whether the current file may be rotated into the backups before it is replaced.
+    pub rotate_backups: bool,
 }
 
 pub struct ConfigWriterTask {
     rx: mpsc::Receiver<WriteConfigRequest>,
+    registry: ConfigRegistry,
     debounced: HashMap<PathBuf, (Instant, WriteConfigRequest)>,
+    backed_up: HashSet<PathBuf>,
 }
 
 impl ConfigWriterTask {
-    pub fn new(rx: mpsc::Receiver<WriteConfigRequest>) -> Self {
+    pub fn new(rx: mpsc::Receiver<WriteConfigRequest>, registry: ConfigRegistry) -> Self {
         Self {
             rx,
+            registry,
             debounced: HashMap::new(),
+            backed_up: HashSet::new(),
         }
     }
 
@@ -52,17 +64,17 @@ impl ConfigWriterTask {
                 },
                 _ = interval.tick() => {
                     let now = Instant::now();
-                    let mut expired_paths = vec![];
-
-                    for (path, (expire_time, request)) in &self.debounced {
-                        if now >= *expire_time {
-                            self.write_config(request).await;
-                            expired_paths.push(path.clone());
-                        }
-                    }
+                    let expired_paths: Vec<_> = self
+                        .debounced
+                        .iter()
+                        .filter(|(_, (expire_time, _))| now >= *expire_time)
+                        .map(|(path, _)| path.clone())
+                        .collect();
 
                     for path in expired_paths {
-                        self.debounced.remove(path.as_path());
+                        if let Some((_, request)) = self.debounced.remove(path.as_path()) {
+                            self.write_config(&request).await;
+                        }
                     }
                 }
             }
@@ -84,17 +96,103 @@ impl ConfigWriterTask {
         }
     }
 
-    async fn write_config(&self, request: &WriteConfigRequest) {
+    async fn write_config(&mut self, request: &WriteConfigRequest) {
         if let Some(parent_dir) = request.path.parent() {
             if let Err(error) = create_dir_all(parent_dir).await {
                 error!("Failed to create config directory: {}", error);
             }
         }
 
-        if let Err(error) = write(&request.path, &request.config).await {
-            error!("Failed to write config: {}", error);
+        if !request
+            .stamp
+            .allows_write(&request.path, request.generation)
+        {
+            // A reload since the request already replaced the config with what is on disk, so nothing is lost.
+            if !request.stamp.superseded(request.generation) {
+                warn!(
+                    "Not writing config file {} as it was changed on disk",
+                    request.path.to_string_lossy()
+                );
+                self.report_refused_write(&request.path);
+            }
+            return;
+        }
+
+        // Backups are only rotated once per session, so they reflect previous runs rather than every single change.
+        if request.rotate_backups && self.backed_up.insert(request.path.clone()) {
+            if let Err(error) = rotate_backups(&request.path).await {
+                error!("Failed to back up config: {:#}", error);
+            }
+        }
+
+        match write_atomically(&request.path, &request.config).await {
+            Ok(()) => request.stamp.mark_written(&request.path),
+            Err(error) => error!("Failed to write config: {:#}", error),
+        }
+    }
+
+    /// Tells the user that changes were not saved, so they can load the file from disk or reset it.
+    fn report_refused_write(&self, path: &Path) {
+        let Some(config) = self.registry.get(path) else {
+            return;
+        };
+
+        self.registry.report_issue(ConfigIssue {
+            path: path.to_path_buf(),
+            name: config.name().to_string(),
+            summary: format!("Could not save {}, it was changed on disk", config.name()),
+            message: "Changes made in the application since the file was loaded were not saved. Retry loads the \
+                file from disk, which discards them."
+                .to_string(),
+            line: None,
+            preserved_path: None,
+        });
+    }
+}
+
+async fn rotate_backups(path: &Path) -> anyhow::Result<()> {
+    for index in (1..BACKUP_COUNT).rev() {
+        match rename(backup_path(path, index), backup_path(path, index + 1)).await {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
         }
     }
+
+    match copy(path, backup_path(path, 1)).await {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Writes to a temporary file first and renames it over the target, so a crash never leaves a truncated file behind.
+async fn write_atomically(path: &Path, content: &str) -> anyhow::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut file = File::create(&temp_path)
+        .await
+        .with_context(|| format!("Could not create {}", temp_path.to_string_lossy()))?;
+    file.write_all(content.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    rename(&temp_path, path)
+        .await
+        .with_context(|| format!("Could not replace {}", path.to_string_lossy()))?;
+
+    // The rename itself only becomes durable once the directory entry is flushed, which is not possible through a file
+    // handle on Windows.
+    #[cfg(unix)]
+    if let Some(parent_dir) = path.parent() {
+        File::open(parent_dir)
+            .await
+            .with_context(|| format!("Could not open {}", parent_dir.to_string_lossy()))?
+            .sync_all()
+            .await?;
+    }
+
+    Ok(())
 }
 
 impl IntoSubsystem<anyhow::Error> for ConfigWriterTask {
@@ -0,0 +1,380 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use async_osc::OscMessage;
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use slint::{ComponentHandle, Weak};
+use sysinfo::System;
+use tokio::fs::{self, create_dir_all};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::spawn_blocking;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::platform::{get_platform, Platform};
+use crate::tasks::plugin_manager::Command;
+use crate::utils::config::ConfigRegistry;
+use crate::utils::osc::format_arguments;
+use crate::{AppWindow, Diagnostics, VERSION};
+
+/// Number of recent OSC messages included in a bundle.
+const MESSAGE_BUFFER_SIZE: usize = 500;
+/// Number of most recent log files included in a bundle.
+const MAX_LOG_FILES: usize = 3;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const REDACTED: &str = "<redacted>";
+
+pub enum DiagnosticsCommand {
+    /// Creates a bundle at the given path, or in the diagnostics folder if none is given.
+    Create(Option<PathBuf>),
+}
+
+struct RecordedMessage {
+    timestamp: DateTime<Local>,
+    outgoing: bool,
+    message: OscMessage,
+}
+
+/// Replaces the values of secret keys, so that a bundle can be shared publicly.
+fn redact(source: &str, secret_keys: &[&str]) -> String {
+    if secret_keys.is_empty() {
+        return source.to_string();
+    }
+
+    let Ok(mut table) = source.parse::<toml::Table>() else {
+        // Secrets cannot be told apart in a file which does not parse, so leave it out entirely.
+        return "# Left out, as the file could not be parsed and may contain credentials\n"
+            .to_string();
+    };
+
+    for key in secret_keys {
+        if let Some(value) = table.get_mut(*key) {
+            *value = toml::Value::String(REDACTED.to_string());
+        }
+    }
+
+    toml::to_string_pretty(&table).unwrap_or_default()
+}
+
+fn write_archive(path: &Path, entries: Vec<(String, Vec<u8>)>) -> anyhow::Result<()> {
+    let mut writer = ZipWriter::new(File::create(path)?);
+
+    for (name, content) in entries {
+        writer.start_file(name, SimpleFileOptions::default())?;
+        writer.write_all(&content)?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+pub struct DiagnosticsTask {
+    rx: mpsc::Receiver<DiagnosticsCommand>,
+    incoming_rx: broadcast::Receiver<OscMessage>,
+    outgoing_rx: broadcast::Receiver<OscMessage>,
+    registry: ConfigRegistry,
+    plugin_manager_tx: mpsc::Sender<Command>,
+    app_window: Weak<AppWindow>,
+    logs_dir: PathBuf,
+    diagnostics_dir: PathBuf,
+    osc_query_port: u16,
+    messages: VecDeque<RecordedMessage>,
+}
+
+pub struct DiagnosticsParams {
+    pub rx: mpsc::Receiver<DiagnosticsCommand>,
+    pub incoming_rx: broadcast::Receiver<OscMessage>,
+    pub outgoing_rx: broadcast::Receiver<OscMessage>,
+    pub registry: ConfigRegistry,
+    pub plugin_manager_tx: mpsc::Sender<Command>,
+    pub app_window: Weak<AppWindow>,
+    pub logs_dir: PathBuf,
+    pub diagnostics_dir: PathBuf,
+    pub osc_query_port: u16,
+}
+
+impl DiagnosticsTask {
+    pub fn new(params: DiagnosticsParams) -> Self {
+        Self {
+            rx: params.rx,
+            incoming_rx: params.incoming_rx,
+            outgoing_rx: params.outgoing_rx,
+            registry: params.registry,
+            plugin_manager_tx: params.plugin_manager_tx,
+            app_window: params.app_window,
+            logs_dir: params.logs_dir,
+            diagnostics_dir: params.diagnostics_dir,
+            osc_query_port: params.osc_query_port,
+            messages: VecDeque::with_capacity(MESSAGE_BUFFER_SIZE),
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(DiagnosticsCommand::Create(path)) => self.handle_create(path).await?,
+                    None => break,
+                },
+                message = self.incoming_rx.recv() => {
+                    if !self.record(false, message) {
+                        break;
+                    }
+                }
+                message = self.outgoing_rx.recv() => {
+                    if !self.record(true, message) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_create(&mut self, path: Option<PathBuf>) -> anyhow::Result<()> {
+        let status = match self.create(path).await {
+            Ok(path) => format!("Saved diagnostics bundle to {}", path.to_string_lossy()),
+            Err(error) => {
+                warn!("Failed to create diagnostics bundle: {:#}", error);
+                format!("Failed to create diagnostics bundle: {:#}", error)
+            }
+        };
+
+        self.publish(status, false)
+    }
+
+    fn record(&mut self, outgoing: bool, message: Result<OscMessage, RecvError>) -> bool {
+        let message = match message {
+            Ok(message) => message,
+            Err(RecvError::Closed) => return false,
+            // Only the most recent messages are kept anyway.
+            Err(RecvError::Lagged(_)) => return true,
+        };
+
+        if self.messages.len() == MESSAGE_BUFFER_SIZE {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back(RecordedMessage {
+            timestamp: Local::now(),
+            outgoing,
+            message,
+        });
+
+        true
+    }
+
+    async fn create(&mut self, path: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+        self.publish("Creating diagnostics bundle…".to_string(), true)?;
+
+        let mut entries = vec![
+            ("system.txt".to_string(), self.system_info().into_bytes()),
+            (
+                "capabilities.txt".to_string(),
+                get_platform().capabilities().await.into_bytes(),
+            ),
+            (
+                "plugins.txt".to_string(),
+                self.plugin_states().await?.into_bytes(),
+            ),
+            (
+                "osc-messages.log".to_string(),
+                self.message_log().into_bytes(),
+            ),
+        ];
+
+        entries.extend(self.osc_query_entries().await);
+        entries.extend(self.config_entries().await);
+        entries.extend(self.log_entries().await?);
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                create_dir_all(&self.diagnostics_dir).await?;
+                self.diagnostics_dir.join(format!(
+                    "diagnostics-{}.zip",
+                    Local::now().format("%Y%m%d-%H%M%S")
+                ))
+            }
+        };
+
+        spawn_blocking({
+            let path = path.clone();
+            move || write_archive(&path, entries)
+        })
+        .await??;
+
+        info!("Saved diagnostics bundle to {}", path.to_string_lossy());
+
+        if let Some(parent) = path.parent() {
+            get_platform().open_folder(parent);
+        }
+
+        Ok(path)
+    }
+
+    fn system_info(&self) -> String {
+        let executable = env::current_exe()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        [
+            format!("Version: {}", VERSION),
+            format!(
+                "OS: {}",
+                System::long_os_version().unwrap_or_else(|| env::consts::OS.to_string())
+            ),
+            format!("Kernel: {}", System::kernel_version().unwrap_or_default()),
+            format!("Architecture: {}", env::consts::ARCH),
+            format!("Executable: {}", executable),
+            format!("OSCQuery port: {}", self.osc_query_port),
+            format!("Created: {}", Local::now().to_rfc3339()),
+        ]
+        .join("\n")
+    }
+
+    async fn plugin_states(&self) -> anyhow::Result<String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.plugin_manager_tx
+            .send(Command::ReportStates(reply_tx))
+            .await?;
+
+        let mut output = String::new();
+
+        for state in reply_rx.await? {
+            let _ = writeln!(
+                output,
+                "{}: enabled={}, running={}, suppressed={}",
+                state.id, state.enabled, state.running, state.suppressed
+            );
+        }
+
+        Ok(output)
+    }
+
+    fn message_log(&self) -> String {
+        let mut output = String::new();
+
+        for recorded in &self.messages {
+            let _ = writeln!(
+                output,
+                "{}\t{}\t{}\t{}",
+                recorded.timestamp.to_rfc3339(),
+                if recorded.outgoing { "OUT" } else { "IN" },
+                recorded.message.addr,
+                format_arguments(&recorded.message.args)
+            );
+        }
+
+        output
+    }
+
+    /// Fetches the tree as served to VRChat, so that the bundle shows exactly what VRChat sees.
+    async fn osc_query_entries(&self) -> Vec<(String, Vec<u8>)> {
+        let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(error) => {
+                return vec![(
+                    "oscquery/error.txt".to_string(),
+                    error.to_string().into_bytes(),
+                )]
+            }
+        };
+
+        let mut entries = vec![];
+
+        for (name, query) in [("tree.json", ""), ("host-info.json", "?HOST_INFO")] {
+            let url = format!("http://127.0.0.1:{}/{}", self.osc_query_port, query);
+            let content = match client.get(url).send().await {
+                Ok(response) => response
+                    .text()
+                    .await
+                    .unwrap_or_else(|error| error.to_string()),
+                Err(error) => error.to_string(),
+            };
+
+            entries.push((format!("oscquery/{}", name), content.into_bytes()));
+        }
+
+        entries
+    }
+
+    async fn config_entries(&self) -> Vec<(String, Vec<u8>)> {
+        let mut entries = vec![];
+
+        for config in self.registry.configs() {
+            let Ok(relative_path) = config.path().strip_prefix(self.registry.config_dir()) else {
+                continue;
+            };
+            let name = relative_path
+                .iter()
+                .map(|component| component.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let content = redact(&config.snapshot().await, config.secret_keys());
+            entries.push((format!("config/{}", name), content.into_bytes()));
+        }
+
+        entries
+    }
+
+    async fn log_entries(&self) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        let mut files: Vec<(SystemTime, PathBuf)> = vec![];
+        let mut read_dir = fs::read_dir(&self.logs_dir).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+
+            if metadata.is_file() {
+                files.push((
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    entry.path(),
+                ));
+            }
+        }
+
+        files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        let mut entries = vec![];
+
+        for (_, path) in files.into_iter().take(MAX_LOG_FILES) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            entries.push((format!("logs/{}", name), fs::read(&path).await?));
+        }
+
+        Ok(entries)
+    }
+
+    fn publish(&self, status: String, busy: bool) -> anyhow::Result<()> {
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let diagnostics = handle.global::<Diagnostics>();
+            diagnostics.set_status(status.into());
+            diagnostics.set_busy(busy);
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for DiagnosticsTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
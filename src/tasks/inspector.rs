@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_osc::OscMessage;
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::fs::{create_dir_all, write};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::interval;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::platform::{get_platform, Platform};
+use crate::utils::osc::format_arguments;
+use crate::{AppWindow, Inspector, InspectorEntry};
+
+const BUFFER_SIZE: usize = 2000;
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum InspectorCommand {
+    SetPaused(bool),
+    SetFilter(String),
+    Clear,
+    /// Exports the captured messages to the given path, or to the captures folder if none is given. Relative paths are
+    /// resolved against the captures folder.
+    Export(Option<PathBuf>),
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+impl Direction {
+    fn label(&self) -> &'static str {
+        match self {
+            Direction::Incoming => "IN",
+            Direction::Outgoing => "OUT",
+        }
+    }
+}
+
+struct CapturedMessage {
+    timestamp: DateTime<Local>,
+    direction: Direction,
+    message: OscMessage,
+}
+
+pub struct InspectorTask {
+    rx: mpsc::Receiver<InspectorCommand>,
+    incoming_rx: broadcast::Receiver<OscMessage>,
+    outgoing_rx: broadcast::Receiver<OscMessage>,
+    app_window: Weak<AppWindow>,
+    captures_dir: PathBuf,
+    buffer: VecDeque<CapturedMessage>,
+    paused: bool,
+    filter: String,
+    dirty: bool,
+}
+
+impl InspectorTask {
+    pub fn new(
+        rx: mpsc::Receiver<InspectorCommand>,
+        incoming_rx: broadcast::Receiver<OscMessage>,
+        outgoing_rx: broadcast::Receiver<OscMessage>,
+        app_window: Weak<AppWindow>,
+        captures_dir: PathBuf,
+    ) -> Self {
+        Self {
+            rx,
+            incoming_rx,
+            outgoing_rx,
+            app_window,
+            captures_dir,
+            buffer: VecDeque::with_capacity(BUFFER_SIZE),
+            paused: false,
+            filter: String::new(),
+            dirty: false,
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        let mut refresh = interval(REFRESH_INTERVAL);
+
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => break,
+                },
+                message = self.incoming_rx.recv() => {
+                    if !self.capture(Direction::Incoming, message) {
+                        break;
+                    }
+                }
+                message = self.outgoing_rx.recv() => {
+                    if !self.capture(Direction::Outgoing, message) {
+                        break;
+                    }
+                }
+                _ = refresh.tick() => {
+                    if self.dirty {
+                        self.refresh_view()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn capture(&mut self, direction: Direction, message: Result<OscMessage, RecvError>) -> bool {
+        let message = match message {
+            Ok(message) => message,
+            Err(RecvError::Closed) => return false,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "Inspector lagging behind, {} messages have been dropped",
+                    skipped
+                );
+                return true;
+            }
+        };
+
+        if self.paused {
+            return true;
+        }
+
+        if self.buffer.len() == BUFFER_SIZE {
+            self.buffer.pop_front();
+        }
+
+        self.buffer.push_back(CapturedMessage {
+            timestamp: Local::now(),
+            direction,
+            message,
+        });
+        self.dirty = true;
+
+        true
+    }
+
+    async fn handle_command(&mut self, command: InspectorCommand) {
+        match command {
+            InspectorCommand::SetPaused(paused) => {
+                self.paused = paused;
+            }
+            InspectorCommand::SetFilter(filter) => {
+                self.filter = filter;
+                self.dirty = true;
+            }
+            InspectorCommand::Clear => {
+                self.buffer.clear();
+                self.dirty = true;
+            }
+            InspectorCommand::Export(path) => {
+                let status = match self.export(path).await {
+                    Ok(path) => format!("Exported captured messages to {}", path.to_string_lossy()),
+                    Err(error) => {
+                        warn!("Failed to export captured OSC messages: {:#}", error);
+                        format!("Failed to export captured messages: {:#}", error)
+                    }
+                };
+
+                self.set_status(status);
+            }
+        }
+    }
+
+    fn matches_filter(&self, captured: &CapturedMessage) -> bool {
+        self.filter.is_empty()
+            || captured
+                .message
+                .addr
+                .to_lowercase()
+                .contains(&self.filter.to_lowercase())
+    }
+
+    fn refresh_view(&mut self) -> anyhow::Result<()> {
+        let entries: Vec<InspectorEntry> = self
+            .buffer
+            .iter()
+            .rev()
+            .filter(|captured| self.matches_filter(captured))
+            .map(|captured| InspectorEntry {
+                timestamp: captured.timestamp.format("%H:%M:%S%.3f").to_string().into(),
+                direction: captured.direction.label().into(),
+                address: captured.message.addr.clone().into(),
+                arguments: format_arguments(&captured.message.args).into(),
+            })
+            .collect();
+        let captured_count = self.buffer.len() as i32;
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let inspector = handle.global::<Inspector>();
+            inspector.set_entries(ModelRc::new(VecModel::from(entries)));
+            inspector.set_captured_count(captured_count);
+        })?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn set_status(&self, status: String) {
+        let _ = self.app_window.upgrade_in_event_loop(move |handle| {
+            handle.global::<Inspector>().set_status(status.into());
+        });
+    }
+
+    async fn export(&self, path: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+        let mut output = String::new();
+
+        for captured in self
+            .buffer
+            .iter()
+            .filter(|captured| self.matches_filter(captured))
+        {
+            let _ = writeln!(
+                output,
+                "{}\t{}\t{}\t{}",
+                captured.timestamp.to_rfc3339(),
+                captured.direction.label(),
+                captured.message.addr,
+                format_arguments(&captured.message.args)
+            );
+        }
+
+        // The working directory depends on how the application was started, so it is no base for relative paths.
+        let path = self.captures_dir.join(path.unwrap_or_else(|| {
+            format!("osc-capture-{}.log", Local::now().format("%Y%m%d-%H%M%S")).into()
+        }));
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+
+        write(&path, output)
+            .await
+            .with_context(|| format!("Could not write {}", path.to_string_lossy()))?;
+
+        info!(
+            "Exported captured OSC messages to {}",
+            path.to_string_lossy()
+        );
+        get_platform().open_folder(path.parent().unwrap_or(&self.captures_dir));
+
+        Ok(path)
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for InspectorTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use flexi_logger::{LogfileSelector, LoggerHandle};
+use log::{info, warn};
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::fs::{metadata, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::{LogLevel, LoggingConfig, RootConfig};
+use crate::plugins::log_module;
+use crate::utils::config::ConfigHandle;
+use crate::utils::logging::{log_specification, parse_line, LogLine};
+use crate::{AppWindow, LogEntry, LogLevelItem, Logs};
+
+const BUFFER_SIZE: usize = 2000;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How much of the current log file is read when the task starts.
+const INITIAL_TAIL: u64 = 256 * 1024;
+
+const APP_MODULE: &str = env!("CARGO_CRATE_NAME");
+
+pub enum LogsCommand {
+    SetFilter { level: String, module: String },
+    SetLevel { module: String, level: String },
+}
+
+pub struct LogsTask {
+    rx: mpsc::Receiver<LogsCommand>,
+    config: ConfigHandle<RootConfig>,
+    logger: LoggerHandle,
+    app_window: Weak<AppWindow>,
+    plugins: Vec<(String, String)>,
+    buffer: VecDeque<LogLine>,
+    path: Option<PathBuf>,
+    offset: u64,
+    partial: String,
+    skip_line: bool,
+    level_filter: log::Level,
+    module_filter: String,
+    applied: Option<LoggingConfig>,
+    dirty: bool,
+}
+
+impl LogsTask {
+    pub fn new(
+        rx: mpsc::Receiver<LogsCommand>,
+        config: ConfigHandle<RootConfig>,
+        logger: LoggerHandle,
+        app_window: Weak<AppWindow>,
+        plugins: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            rx,
+            config,
+            logger,
+            app_window,
+            plugins,
+            buffer: VecDeque::with_capacity(BUFFER_SIZE),
+            path: None,
+            offset: 0,
+            partial: String::new(),
+            skip_line: false,
+            level_filter: log::Level::Trace,
+            module_filter: String::new(),
+            applied: None,
+            dirty: false,
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        let mut config_rx = self.config.subscribe();
+        let logging = config_rx.borrow_and_update().logging.clone();
+
+        let status = if std::env::var_os("RUST_LOG").is_some() {
+            info!("RUST_LOG is set, configured log levels apply once they are changed");
+            self.applied = Some(logging.clone());
+            Some("Log levels from RUST_LOG are in effect until you change them here".to_string())
+        } else {
+            self.apply_levels(&logging);
+            None
+        };
+
+        self.publish_levels(&logging, status)?;
+
+        let mut poll = interval(POLL_INTERVAL);
+
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => self.handle_command(command).await?,
+                    None => break,
+                },
+                result = config_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    let logging = config_rx.borrow_and_update().logging.clone();
+
+                    if self.applied.as_ref() != Some(&logging) {
+                        self.apply_levels(&logging);
+                        self.publish_levels(&logging, None)?;
+                    }
+                }
+                _ = poll.tick() => {
+                    if let Err(error) = self.read_log().await {
+                        warn!("Failed to read log file: {}", error);
+                    }
+
+                    if self.dirty {
+                        self.refresh_view()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_levels(&mut self, logging: &LoggingConfig) {
+        match log_specification(logging) {
+            Ok(spec) => self.logger.set_new_spec(spec),
+            Err(error) => warn!("Invalid log levels, keeping the previous ones: {:#}", error),
+        }
+
+        self.applied = Some(logging.clone());
+    }
+
+    async fn handle_command(&mut self, command: LogsCommand) -> anyhow::Result<()> {
+        match command {
+            LogsCommand::SetFilter { level, module } => {
+                self.level_filter = level.parse().unwrap_or(log::Level::Trace);
+                self.module_filter = module.trim().to_lowercase();
+                self.dirty = true;
+            }
+            LogsCommand::SetLevel { module, level } => {
+                let level = LogLevel::from_label(&level);
+
+                self.config
+                    .update(|config| {
+                        let logging = &mut config.logging;
+
+                        match level {
+                            Some(level) if module == APP_MODULE => logging.level = level,
+                            Some(level) => {
+                                logging.modules.insert(module, level);
+                            }
+                            None => {
+                                logging.modules.remove(&module);
+                            }
+                        }
+                    })
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads lines appended to the current log file since the last call.
+    async fn read_log(&mut self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => {
+                let Some(path) = self
+                    .logger
+                    .existing_log_files(&LogfileSelector::none().with_r_current())?
+                    .into_iter()
+                    .next()
+                else {
+                    return Ok(());
+                };
+
+                self.offset = metadata(&path).await?.len().saturating_sub(INITIAL_TAIL);
+                // Starting in the middle of the file most likely means starting in the middle of a line.
+                self.skip_line = self.offset > 0;
+                self.path = Some(path.clone());
+                path
+            }
+        };
+
+        let length = metadata(&path).await?.len();
+
+        // The file was rotated, continue with the new one from the start.
+        if length < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        if length == self.offset {
+            return Ok(());
+        }
+
+        let mut file = File::open(&path).await?;
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        let mut content = vec![];
+        file.take(length - self.offset)
+            .read_to_end(&mut content)
+            .await?;
+
+        self.offset += content.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&content));
+
+        let Some(end) = self.partial.rfind('\n') else {
+            return Ok(());
+        };
+
+        let complete: String = self.partial.drain(..=end).collect();
+        let mut lines = complete.lines();
+
+        if self.skip_line {
+            lines.next();
+            self.skip_line = false;
+        }
+
+        for line in lines {
+            match parse_line(line) {
+                Some(line) => {
+                    if self.buffer.len() == BUFFER_SIZE {
+                        self.buffer.pop_front();
+                    }
+
+                    self.buffer.push_back(line);
+                }
+                None => {
+                    if let Some(last) = self.buffer.back_mut() {
+                        last.message.push('\n');
+                        last.message.push_str(line);
+                    }
+                }
+            }
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn matches_filter(&self, line: &LogLine) -> bool {
+        line.level <= self.level_filter
+            && (self.module_filter.is_empty()
+                || line.module.to_lowercase().contains(&self.module_filter))
+    }
+
+    fn refresh_view(&mut self) -> anyhow::Result<()> {
+        let entries: Vec<LogEntry> = self
+            .buffer
+            .iter()
+            .rev()
+            .filter(|line| self.matches_filter(line))
+            .map(|line| LogEntry {
+                timestamp: line.timestamp[11..].to_string().into(),
+                level: line.level.to_string().into(),
+                module: line.module.clone().into(),
+                message: line.message.clone().into(),
+            })
+            .collect();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            handle
+                .global::<Logs>()
+                .set_entries(ModelRc::new(VecModel::from(entries)));
+        })?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn publish_levels(
+        &self,
+        logging: &LoggingConfig,
+        status: Option<String>,
+    ) -> anyhow::Result<()> {
+        let mut items = vec![LogLevelItem {
+            module: APP_MODULE.into(),
+            title: "Application".into(),
+            level: logging.level.label().into(),
+            can_inherit: false,
+        }];
+
+        for (plugin_id, title) in &self.plugins {
+            let module = log_module(plugin_id);
+            let level = match logging.modules.get(&module) {
+                Some(level) => level.label(),
+                None => "Default",
+            };
+
+            items.push(LogLevelItem {
+                module: module.into(),
+                title: title.clone().into(),
+                level: level.into(),
+                can_inherit: true,
+            });
+        }
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let logs = handle.global::<Logs>();
+            logs.set_levels(ModelRc::new(VecModel::from(items)));
+            logs.set_status(status.unwrap_or_default().into());
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for LogsTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+
+use axum::routing::get;
+use axum::{serve, Router};
+use log::info;
+use tokio::net::TcpListener;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::metrics::metrics;
+
+pub struct MetricsTask {
+    listen_address: SocketAddr,
+}
+
+impl MetricsTask {
+    pub fn new(listen_address: SocketAddr) -> Self {
+        Self { listen_address }
+    }
+}
+
+async fn render_metrics() -> ([(&'static str, &'static str); 1], String) {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
+
+impl IntoSubsystem<anyhow::Error> for MetricsTask {
+    async fn run(self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(self.listen_address).await?;
+        let router = Router::new().route("/metrics", get(render_metrics));
+
+        info!("Serving metrics on http://{}/metrics", self.listen_address);
+
+        match serve(listener, router)
+            .into_future()
+            .cancel_on_shutdown(subsys)
+            .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error.into()),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
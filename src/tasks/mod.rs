@@ -1,10 +1,22 @@
+pub mod avatars;
 pub mod broadcaster;
+pub mod config_monitor;
 pub mod config_writer;
+pub mod diagnostics;
+pub mod inspector;
+pub mod logs;
+pub mod metrics;
+pub mod notifications;
 pub mod orchestrate;
 pub mod osc_query;
 pub mod osc_receiver;
 pub mod osc_sender;
 pub mod plugin_manager;
+pub mod profiles;
+pub mod recorder;
+pub mod restart;
+pub mod setups;
+pub mod simulator;
 pub mod tray;
 pub mod update_checker;
 pub mod vrchat_monitor;
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::{NotificationCategory, RootConfig};
+use crate::platform::{get_platform, Platform};
+use crate::tasks::orchestrate::AppEvent;
+use crate::utils::config::ConfigHandle;
+
+/// Notifications with the same category and summary are shown at most once within this time.
+const RATE_LIMIT: Duration = Duration::from_secs(5 * 60);
+
+pub enum NotificationAction {
+    OpenReleasePage(String),
+    RestartPlugin(String),
+    ShowWindow,
+    OpenCrashReport(PathBuf),
+}
+
+impl NotificationAction {
+    fn label(&self) -> &'static str {
+        match self {
+            NotificationAction::OpenReleasePage(_) => "Open release page",
+            NotificationAction::RestartPlugin(_) => "Restart plugin",
+            NotificationAction::ShowWindow => "Show",
+            NotificationAction::OpenCrashReport(_) => "Open crash report",
+        }
+    }
+
+    fn into_app_event(self) -> AppEvent {
+        match self {
+            NotificationAction::OpenReleasePage(url) => AppEvent::OpenUrl(url),
+            NotificationAction::RestartPlugin(plugin_id) => {
+                AppEvent::PluginRestartRequested(plugin_id)
+            }
+            NotificationAction::ShowWindow => AppEvent::AppWindowRequested,
+            NotificationAction::OpenCrashReport(path) => AppEvent::OpenPath(path),
+        }
+    }
+}
+
+pub struct Notification {
+    pub category: NotificationCategory,
+    pub summary: String,
+    pub body: String,
+    pub action: Option<NotificationAction>,
+}
+
+impl Notification {
+    pub fn new(
+        category: NotificationCategory,
+        summary: impl Into<String>,
+        body: impl Into<String>,
+    ) -> Self {
+        Self {
+            category,
+            summary: summary.into(),
+            body: body.into(),
+            action: None,
+        }
+    }
+
+    pub fn with_action(mut self, action: NotificationAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+}
+
+pub struct NotificationsTask {
+    rx: mpsc::Receiver<Notification>,
+    config: ConfigHandle<RootConfig>,
+    app_event_tx: mpsc::Sender<AppEvent>,
+    last_shown: HashMap<(NotificationCategory, String), Instant>,
+    shown: JoinSet<()>,
+}
+
+impl NotificationsTask {
+    pub fn new(
+        rx: mpsc::Receiver<Notification>,
+        config: ConfigHandle<RootConfig>,
+        app_event_tx: mpsc::Sender<AppEvent>,
+    ) -> Self {
+        Self {
+            rx,
+            config,
+            app_event_tx,
+            last_shown: HashMap::new(),
+            shown: JoinSet::new(),
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        loop {
+            select! {
+                notification = self.rx.recv() => match notification {
+                    Some(notification) => self.show(notification).await,
+                    None => break,
+                },
+                Some(_) = self.shown.join_next() => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn show(&mut self, notification: Notification) {
+        if self
+            .config
+            .read()
+            .await
+            .notifications
+            .disabled
+            .contains(&notification.category)
+        {
+            debug!(
+                "Notification disabled by user, skipping: {}",
+                notification.summary
+            );
+            return;
+        }
+
+        let now = Instant::now();
+        self.last_shown
+            .retain(|_, shown_at| now.duration_since(*shown_at) < RATE_LIMIT);
+        let key = (notification.category, notification.summary.clone());
+
+        if self.last_shown.contains_key(&key) {
+            debug!(
+                "Notification shown recently, skipping: {}",
+                notification.summary
+            );
+            return;
+        }
+
+        self.last_shown.insert(key, now);
+
+        let app_event_tx = self.app_event_tx.clone();
+
+        self.shown.spawn(async move {
+            let result = get_platform()
+                .show_notification(
+                    &notification.summary,
+                    &notification.body,
+                    notification.action.as_ref().map(|action| action.label()),
+                )
+                .await;
+
+            match (result, notification.action) {
+                (Ok(true), Some(action)) => {
+                    let _ = app_event_tx.send(action.into_app_event()).await;
+                }
+                (Ok(_), _) => {}
+                (Err(error), _) => warn!("Failed to show notification: {}", error),
+            }
+        });
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for NotificationsTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -1,32 +1,103 @@
-use slint::{ComponentHandle, Weak};
+use log::{debug, warn};
+use slint::{ComponentHandle, Model, Weak};
 use std::path::PathBuf;
 use tokio::select;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{mpsc, Mutex};
 use tokio_graceful_shutdown::errors::CancelledByShutdown;
 use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
 
-use crate::config::{DarkLight, RootConfig};
+use crate::config::{AutoStartMethod, DarkLight, NotificationCategory, RootConfig, UpdateChannel};
 use crate::platform::{get_platform, Platform};
+use crate::plugins::parameters::set_custom_address;
+use crate::tasks::avatars::AvatarCommand;
+use crate::tasks::config_monitor::ConfigCommand;
+use crate::tasks::diagnostics::DiagnosticsCommand;
+use crate::tasks::inspector::InspectorCommand;
+use crate::tasks::logs::LogsCommand;
 use crate::tasks::plugin_manager::Command;
+use crate::tasks::profiles::ProfileCommand;
+use crate::tasks::recorder::RecorderCommand;
+use crate::tasks::setups::SetupCommand;
+use crate::tasks::simulator::SimulatorCommand;
 use crate::tasks::tray::TrayProperty;
+use crate::tasks::update_checker::{UpdateCommand, UpdateStatus};
 use crate::utils::config::ConfigHandle;
-use crate::{AppWindow, UpdateNotice};
+use crate::{AppWindow, PluginItems, Settings, UpdateNotice};
 
 pub enum AppEvent {
     VrchatStarted,
     VrchatStopped,
     AppWindowRequested,
     ShutdownRequested,
-    UpdateAvailable { version: String, url: String },
+    UpdateStatus(UpdateStatus),
+    ProfileRequested(String),
+    OpenUrl(String),
+    OpenPath(PathBuf),
+    PluginRestartRequested(String),
+    PluginToggleRequested(String),
+    PluginActionRequested {
+        plugin_id: String,
+        action_id: String,
+    },
+    StartPluginsRequested,
+    StopPluginsRequested,
+    LogsFolderRequested,
+    ConfigFolderRequested,
+    /// Recorder command handed over by a second launch of the application.
+    RecorderRequested(RecorderCommand),
 }
 
 pub enum UiEvent {
     PluginToggle(String, bool),
     TrayIconsToggle(DarkLight),
     AutoStartToggle(bool),
+    AutoStartMinimizedToggle(bool),
+    AutoStartMethod(AutoStartMethod),
     UpdateCheckToggle(bool),
+    UpdateChannel(UpdateChannel),
+    CheckForUpdates,
+    SkipUpdate(String),
+    InstallUpdate,
+    RestartForUpdate,
+    NotificationToggle(String, bool),
+    ParameterAddress {
+        plugin_id: String,
+        name: String,
+        address: String,
+    },
     OpenLogsFolder,
     StartPlugins,
+    Inspector(InspectorCommand),
+    Recorder(RecorderCommand),
+    Simulator(SimulatorCommand),
+    Config(ConfigCommand),
+    Profile(ProfileCommand),
+    Avatar(AvatarCommand),
+    Setup(SetupCommand),
+    Logs(LogsCommand),
+    Diagnostics(DiagnosticsCommand),
+}
+
+pub struct OrchestrateParams {
+    pub app_event_rx: mpsc::Receiver<AppEvent>,
+    pub ui_event_rx: mpsc::Receiver<UiEvent>,
+    pub plugin_manager_tx: mpsc::Sender<Command>,
+    pub tray_property_tx: mpsc::Sender<TrayProperty>,
+    pub inspector_tx: mpsc::Sender<InspectorCommand>,
+    pub recorder_tx: mpsc::Sender<RecorderCommand>,
+    pub simulator_tx: mpsc::Sender<SimulatorCommand>,
+    pub config_monitor_tx: mpsc::Sender<ConfigCommand>,
+    pub profiles_tx: mpsc::Sender<ProfileCommand>,
+    pub avatars_tx: mpsc::Sender<AvatarCommand>,
+    pub setups_tx: mpsc::Sender<SetupCommand>,
+    pub logs_tx: mpsc::Sender<LogsCommand>,
+    pub diagnostics_tx: mpsc::Sender<DiagnosticsCommand>,
+    pub update_checker_tx: mpsc::Sender<UpdateCommand>,
+    pub app_window: Weak<AppWindow>,
+    pub config: ConfigHandle<RootConfig>,
+    pub logs_dir: PathBuf,
+    pub config_dir: PathBuf,
 }
 
 pub struct OrchestrateTask {
@@ -34,29 +105,43 @@ pub struct OrchestrateTask {
     ui_event_rx: mpsc::Receiver<UiEvent>,
     plugin_manager_tx: mpsc::Sender<Command>,
     tray_property_tx: mpsc::Sender<TrayProperty>,
+    inspector_tx: mpsc::Sender<InspectorCommand>,
+    recorder_tx: mpsc::Sender<RecorderCommand>,
+    simulator_tx: mpsc::Sender<SimulatorCommand>,
+    config_monitor_tx: mpsc::Sender<ConfigCommand>,
+    profiles_tx: mpsc::Sender<ProfileCommand>,
+    avatars_tx: mpsc::Sender<AvatarCommand>,
+    setups_tx: mpsc::Sender<SetupCommand>,
+    logs_tx: mpsc::Sender<LogsCommand>,
+    diagnostics_tx: mpsc::Sender<DiagnosticsCommand>,
+    update_checker_tx: mpsc::Sender<UpdateCommand>,
     app_window: Mutex<Weak<AppWindow>>,
     config: ConfigHandle<RootConfig>,
     logs_dir: PathBuf,
+    config_dir: PathBuf,
 }
 
 impl OrchestrateTask {
-    pub fn new(
-        app_event_rx: mpsc::Receiver<AppEvent>,
-        ui_event_rx: mpsc::Receiver<UiEvent>,
-        plugin_manager_tx: mpsc::Sender<Command>,
-        tray_property_tx: mpsc::Sender<TrayProperty>,
-        app_window: Weak<AppWindow>,
-        config: ConfigHandle<RootConfig>,
-        logs_dir: PathBuf,
-    ) -> Self {
+    pub fn new(params: OrchestrateParams) -> Self {
         Self {
-            app_event_rx,
-            ui_event_rx,
-            plugin_manager_tx,
-            tray_property_tx,
-            app_window: Mutex::new(app_window),
-            config,
-            logs_dir,
+            app_event_rx: params.app_event_rx,
+            ui_event_rx: params.ui_event_rx,
+            plugin_manager_tx: params.plugin_manager_tx,
+            tray_property_tx: params.tray_property_tx,
+            inspector_tx: params.inspector_tx,
+            recorder_tx: params.recorder_tx,
+            simulator_tx: params.simulator_tx,
+            config_monitor_tx: params.config_monitor_tx,
+            profiles_tx: params.profiles_tx,
+            avatars_tx: params.avatars_tx,
+            setups_tx: params.setups_tx,
+            logs_tx: params.logs_tx,
+            diagnostics_tx: params.diagnostics_tx,
+            update_checker_tx: params.update_checker_tx,
+            app_window: Mutex::new(params.app_window),
+            config: params.config,
+            logs_dir: params.logs_dir,
+            config_dir: params.config_dir,
         }
     }
 
@@ -88,16 +173,16 @@ impl OrchestrateTask {
     ) -> anyhow::Result<()> {
         match event {
             AppEvent::VrchatStarted => {
-                self.plugin_manager_tx.send(Command::StartPlugins).await?;
                 self.tray_property_tx
-                    .send(TrayProperty::Running(true))
+                    .send(TrayProperty::VrchatRunning(true))
                     .await?;
+                self.start_plugins().await?;
             }
             AppEvent::VrchatStopped => {
-                self.plugin_manager_tx.send(Command::StopPlugins).await?;
                 self.tray_property_tx
-                    .send(TrayProperty::Running(false))
+                    .send(TrayProperty::VrchatRunning(false))
                     .await?;
+                self.stop_plugins().await?;
             }
             AppEvent::AppWindowRequested => {
                 self.app_window
@@ -117,18 +202,218 @@ impl OrchestrateTask {
                 let _ = slint::quit_event_loop();
                 subsys.request_shutdown();
             }
-            AppEvent::UpdateAvailable { version, url } => {
+            AppEvent::UpdateStatus(status) => {
                 self.app_window
                     .lock()
                     .await
                     .upgrade_in_event_loop(move |handle| {
                         let notice = handle.global::<UpdateNotice>();
-                        notice.set_version(version.into());
-                        notice.set_url(url.into());
-                        notice.set_available(true);
+                        let settings = handle.global::<Settings>();
+                        settings.set_update_checking(matches!(status, UpdateStatus::Checking));
+
+                        match status {
+                            UpdateStatus::Checking => {
+                                settings.set_update_status("Checking for updates…".into());
+                            }
+                            UpdateStatus::UpToDate => {
+                                notice.set_available(false);
+                                settings
+                                    .set_update_status("You are using the latest version".into());
+                            }
+                            UpdateStatus::Available {
+                                version,
+                                url,
+                                notes,
+                                installable,
+                            } => {
+                                settings.set_update_status(
+                                    format!("Version {} is available", version).into(),
+                                );
+                                notice.set_version(version.into());
+                                notice.set_url(url.into());
+                                notice.set_notes(notes.into());
+                                notice.set_installable(installable);
+                                notice.set_progress("".into());
+                                notice.set_available(true);
+                            }
+                            UpdateStatus::Failed(error) => {
+                                settings.set_update_status(
+                                    format!("Update check failed: {}", error).into(),
+                                );
+                            }
+                            UpdateStatus::Downloading(percent) => {
+                                notice.set_installing(true);
+                                notice.set_progress(format!("Downloading… {}%", percent).into());
+                            }
+                            UpdateStatus::Installed => {
+                                notice.set_installing(false);
+                                notice.set_installed(true);
+                                notice.set_progress("".into());
+                                settings.set_update_status(
+                                    "Update installed, restart to apply it".into(),
+                                );
+                            }
+                            UpdateStatus::InstallFailed(error) => {
+                                notice.set_installing(false);
+                                notice.set_progress(format!("Installing failed: {}", error).into());
+                            }
+                        }
                     })?;
             }
+            AppEvent::ProfileRequested(name) => {
+                self.profiles_tx.send(ProfileCommand::Switch(name)).await?;
+            }
+            AppEvent::OpenUrl(url) => {
+                let _ = open::that(url);
+            }
+            AppEvent::OpenPath(path) => {
+                let _ = open::that(path);
+            }
+            AppEvent::PluginRestartRequested(plugin_id) => {
+                self.plugin_manager_tx
+                    .send(Command::RestartPlugin(plugin_id))
+                    .await?;
+            }
+            AppEvent::PluginToggleRequested(plugin_id) => {
+                let enabled = !self
+                    .config
+                    .read()
+                    .await
+                    .enabled_plugins
+                    .contains(&plugin_id);
+
+                self.plugin_manager_tx
+                    .send(if enabled {
+                        Command::EnablePlugin(plugin_id.clone())
+                    } else {
+                        Command::DisablePlugin(plugin_id.clone())
+                    })
+                    .await?;
+
+                self.app_window
+                    .lock()
+                    .await
+                    .upgrade_in_event_loop(move |handle| {
+                        let items = handle.global::<PluginItems>().get_items();
+
+                        for index in 0..items.row_count() {
+                            let Some(mut item) = items.row_data(index) else {
+                                continue;
+                            };
+
+                            if item.id == plugin_id.as_str() {
+                                item.enabled = enabled;
+                                items.set_row_data(index, item);
+                            }
+                        }
+                    })?;
+            }
+            AppEvent::PluginActionRequested {
+                plugin_id,
+                action_id,
+            } => {
+                self.plugin_manager_tx
+                    .send(Command::InvokeTrayAction {
+                        plugin_id,
+                        action_id,
+                    })
+                    .await?;
+            }
+            AppEvent::StartPluginsRequested => {
+                self.start_plugins().await?;
+            }
+            AppEvent::StopPluginsRequested => {
+                self.stop_plugins().await?;
+            }
+            AppEvent::LogsFolderRequested => {
+                get_platform().open_folder(&self.logs_dir);
+            }
+            AppEvent::ConfigFolderRequested => {
+                get_platform().open_folder(&self.config_dir);
+            }
+            AppEvent::RecorderRequested(command) => {
+                self.send_recorder_command(command).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Does not wait for the update checker, as it does not take commands while downloading an update.
+    fn send_update_command(&self, command: UpdateCommand) {
+        match self.update_checker_tx.try_send(command) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => debug!("Update checker is busy, ignoring command"),
+            Err(TrySendError::Closed(_)) => warn!("Update checker is not running"),
         }
+    }
+
+    /// Rewrites an existing auto start entry, so that it matches the current settings.
+    ///
+    /// The new entry is added before the previous one is removed, so a failure never leaves no entry at all.
+    async fn refresh_auto_start(&self) -> anyhow::Result<()> {
+        let platform = get_platform();
+
+        if !platform.has_auto_start() {
+            return Ok(());
+        }
+
+        let auto_start = self.config.read().await.auto_start.clone();
+        platform.add_auto_start(&auto_start).await?;
+        platform.remove_auto_start(Some(auto_start.method)).await?;
+
+        Ok(())
+    }
+
+    /// Shows the outcome of changing the auto start entry in the settings, as it may fail for reasons outside of the
+    /// application, e.g. when systemd is not available.
+    async fn report_auto_start(&self, result: anyhow::Result<()>) -> anyhow::Result<()> {
+        let message = match result {
+            Ok(()) => String::new(),
+            Err(error) => {
+                warn!("Failed to change autostart: {:#}", error);
+                format!("Failed to change autostart: {:#}", error)
+            }
+        };
+        let enabled = get_platform().has_auto_start();
+
+        self.app_window
+            .lock()
+            .await
+            .upgrade_in_event_loop(move |handle| {
+                let settings = handle.global::<Settings>();
+                settings.set_auto_start(enabled);
+                settings.set_auto_start_error(message.into());
+            })?;
+
+        Ok(())
+    }
+
+    async fn send_recorder_command(&self, command: RecorderCommand) -> anyhow::Result<()> {
+        if let RecorderCommand::StartReplay { .. } = command {
+            // Replays are meant for testing without VRChat, so make sure someone listens.
+            self.start_plugins().await?;
+        }
+
+        self.recorder_tx.send(command).await?;
+
+        Ok(())
+    }
+
+    async fn start_plugins(&self) -> anyhow::Result<()> {
+        self.plugin_manager_tx.send(Command::StartPlugins).await?;
+        self.tray_property_tx
+            .send(TrayProperty::Running(true))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn stop_plugins(&self) -> anyhow::Result<()> {
+        self.plugin_manager_tx.send(Command::StopPlugins).await?;
+        self.tray_property_tx
+            .send(TrayProperty::Running(false))
+            .await?;
 
         Ok(())
     }
@@ -156,11 +441,34 @@ impl OrchestrateTask {
                     .await?;
             }
             UiEvent::AutoStartToggle(enabled) => {
-                if enabled {
-                    get_platform().add_auto_start().await?;
+                let result = if enabled {
+                    let auto_start = self.config.read().await.auto_start.clone();
+                    get_platform().add_auto_start(&auto_start).await
                 } else {
-                    get_platform().remove_auto_start().await?;
-                }
+                    get_platform().remove_auto_start(None).await
+                };
+
+                self.report_auto_start(result).await?;
+            }
+            UiEvent::AutoStartMinimizedToggle(start_minimized) => {
+                self.config
+                    .update(|config| {
+                        config.auto_start.start_minimized = start_minimized;
+                    })
+                    .await?;
+
+                let result = self.refresh_auto_start().await;
+                self.report_auto_start(result).await?;
+            }
+            UiEvent::AutoStartMethod(method) => {
+                self.config
+                    .update(|config| {
+                        config.auto_start.method = method;
+                    })
+                    .await?;
+
+                let result = self.refresh_auto_start().await;
+                self.report_auto_start(result).await?;
             }
             UiEvent::UpdateCheckToggle(enabled) => {
                 self.config
@@ -169,14 +477,107 @@ impl OrchestrateTask {
                     })
                     .await?;
             }
+            UiEvent::UpdateChannel(channel) => {
+                self.config
+                    .update(|config| {
+                        config.updates.channel = channel;
+                    })
+                    .await?;
+
+                self.send_update_command(UpdateCommand::CheckNow);
+            }
+            UiEvent::CheckForUpdates => {
+                self.send_update_command(UpdateCommand::CheckNow);
+            }
+            UiEvent::SkipUpdate(version) => {
+                self.config
+                    .update(|config| {
+                        config.updates.skipped_version = Some(version);
+                    })
+                    .await?;
+
+                self.app_window
+                    .lock()
+                    .await
+                    .upgrade_in_event_loop(|handle| {
+                        handle.global::<UpdateNotice>().set_available(false);
+                    })?;
+            }
+            UiEvent::InstallUpdate => {
+                self.app_window
+                    .lock()
+                    .await
+                    .upgrade_in_event_loop(|handle| {
+                        let notice = handle.global::<UpdateNotice>();
+                        notice.set_installing(true);
+                        notice.set_progress("Preparing download…".into());
+                    })?;
+
+                self.send_update_command(UpdateCommand::Install);
+            }
+            UiEvent::RestartForUpdate => {
+                self.send_update_command(UpdateCommand::Restart);
+            }
+            UiEvent::NotificationToggle(id, enabled) => {
+                let Some(category) = NotificationCategory::from_id(&id) else {
+                    return Ok(());
+                };
+
+                self.config
+                    .update(|config| {
+                        let disabled = &mut config.notifications.disabled;
+
+                        if enabled {
+                            disabled.remove(&category);
+                        } else {
+                            disabled.insert(category);
+                        }
+                    })
+                    .await?;
+            }
+            UiEvent::ParameterAddress {
+                plugin_id,
+                name,
+                address,
+            } => {
+                self.config
+                    .update(|config| {
+                        set_custom_address(&mut config.parameters, &plugin_id, &name, &address);
+                    })
+                    .await?;
+            }
             UiEvent::OpenLogsFolder => {
                 get_platform().open_folder(&self.logs_dir);
             }
             UiEvent::StartPlugins => {
-                self.plugin_manager_tx.send(Command::StartPlugins).await?;
-                self.tray_property_tx
-                    .send(TrayProperty::Running(true))
-                    .await?;
+                self.start_plugins().await?;
+            }
+            UiEvent::Inspector(command) => {
+                self.inspector_tx.send(command).await?;
+            }
+            UiEvent::Recorder(command) => {
+                self.send_recorder_command(command).await?;
+            }
+            UiEvent::Simulator(command) => {
+                self.simulator_tx.send(command).await?;
+            }
+            UiEvent::Config(command) => {
+                self.config_monitor_tx.send(command).await?;
+            }
+            UiEvent::Profile(command) => {
+                self.profiles_tx.send(command).await?;
+            }
+            UiEvent::Avatar(command) => {
+                self.avatars_tx.send(command).await?;
+            }
+            UiEvent::Setup(command) => {
+                self.setups_tx.send(command).await?;
+            }
+            UiEvent::Logs(command) => {
+                self.logs_tx.send(command).await?;
+            }
+            UiEvent::Diagnostics(command) => {
+                self.diagnostics_tx.send(command).await?;
             }
         }
 
@@ -1,39 +1,102 @@
-use std::future::IntoFuture;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use axum::serve;
 use tokio::net::TcpListener;
-use tokio_graceful_shutdown::errors::CancelledByShutdown;
-use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+use tokio::select;
+use tokio::sync::watch;
 use tower::make::Shared;
 
-use crate::osc_query::service::OscQueryService;
+use crate::config::{ParameterMappings, RootConfig};
+use crate::osc_query::node::OscAccess;
+use crate::osc_query::service::{OscQueryEndpoints, OscQueryService};
+use crate::plugins::parameters::ParameterMap;
+use crate::plugins::Plugin;
+use crate::tasks::restart::RestartableTask;
+
+/// Returns the endpoints of the avatar change and all plugin parameters, following their parameter mapping.
+pub fn osc_query_endpoints(
+    plugins: &HashMap<&'static str, Arc<dyn Plugin>>,
+    parameter_mappings: &ParameterMappings,
+) -> OscQueryEndpoints {
+    let mut endpoints = OscQueryEndpoints::new();
+    endpoints.add_endpoint(
+        "/avatar/change".to_string(),
+        "s".to_string(),
+        OscAccess::Read,
+        "".to_string(),
+    );
+
+    for (plugin_id, plugin) in plugins {
+        let parameters = plugin.osc_parameters();
+        let parameter_map = ParameterMap::new(parameters, parameter_mappings.get(*plugin_id));
+
+        for parameter in parameters {
+            endpoints.add_endpoint(
+                parameter_map.address(parameter),
+                parameter.osc_type.to_string(),
+                parameter.access,
+                parameter.description.to_string(),
+            );
+        }
+    }
+
+    endpoints
+}
 
 pub struct OscQueryTask {
     port: u16,
     service: OscQueryService,
+    config_rx: watch::Receiver<RootConfig>,
+    plugins: HashMap<&'static str, Arc<dyn Plugin>>,
 }
 
 impl OscQueryTask {
-    pub fn new(port: u16, service: OscQueryService) -> Self {
-        Self { port, service }
+    pub fn new(
+        port: u16,
+        service: OscQueryService,
+        config_rx: watch::Receiver<RootConfig>,
+        plugins: HashMap<&'static str, Arc<dyn Plugin>>,
+    ) -> Self {
+        Self {
+            port,
+            service,
+            config_rx,
+            plugins,
+        }
+    }
+
+    /// Keeps the advertised endpoints in line with the parameter mapping.
+    async fn follow_parameter_mappings(&mut self) {
+        // The mapping may have changed while the server was restarting.
+        let mut parameter_mappings = self.config_rx.borrow_and_update().parameters.clone();
+        self.service
+            .set_endpoints(osc_query_endpoints(&self.plugins, &parameter_mappings));
+
+        while self.config_rx.changed().await.is_ok() {
+            let current = self.config_rx.borrow_and_update().parameters.clone();
+
+            if current != parameter_mappings {
+                parameter_mappings = current;
+                self.service
+                    .set_endpoints(osc_query_endpoints(&self.plugins, &parameter_mappings));
+            }
+        }
     }
 }
 
-impl IntoSubsystem<anyhow::Error> for OscQueryTask {
-    async fn run(self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+impl RestartableTask for OscQueryTask {
+    const NAME: &'static str = "OSCQuery server";
+
+    async fn run_once(&mut self) -> anyhow::Result<()> {
         let addr = SocketAddr::from(([127, 0, 0, 1], self.port));
         let listener = TcpListener::bind(addr).await?;
-        let service = Shared::new(self.service);
-
-        match serve(listener, service)
-            .into_future()
-            .cancel_on_shutdown(subsys)
-            .await
-        {
-            Ok(Ok(())) => {}
-            Ok(Err(error)) => return Err(error.into()),
-            Err(CancelledByShutdown) => {}
+        let service = Shared::new(self.service.clone());
+
+        select! {
+            result = serve(listener, service) => result?,
+            _ = self.follow_parameter_mappings() => {}
         }
 
         Ok(())
@@ -1,17 +1,28 @@
 use async_osc::{OscMessage, OscPacket, OscSocket};
 use tokio::sync::broadcast;
-use tokio_graceful_shutdown::errors::CancelledByShutdown;
-use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
 use tokio_stream::StreamExt;
 
+use crate::metrics::metrics;
+use crate::tasks::restart::RestartableTask;
+
 pub struct OscReceiverTask {
     port: u16,
     tx: broadcast::Sender<OscMessage>,
+    /// Only carries messages which arrived over the network, unlike `tx` which replays and the simulator inject into.
+    received_tx: broadcast::Sender<OscMessage>,
 }
 
 impl OscReceiverTask {
-    pub fn new(port: u16, tx: broadcast::Sender<OscMessage>) -> Self {
-        Self { port, tx }
+    pub fn new(
+        port: u16,
+        tx: broadcast::Sender<OscMessage>,
+        received_tx: broadcast::Sender<OscMessage>,
+    ) -> Self {
+        Self {
+            port,
+            tx,
+            received_tx,
+        }
     }
 
     async fn main_loop(&mut self) -> anyhow::Result<()> {
@@ -23,6 +34,8 @@ impl OscReceiverTask {
             match packet {
                 OscPacket::Bundle(_) => {}
                 OscPacket::Message(message) => {
+                    metrics().record_osc_received(&message.addr);
+                    let _ = self.received_tx.send(message.clone());
                     let _ = self.tx.send(message);
                 }
             }
@@ -32,14 +45,10 @@ impl OscReceiverTask {
     }
 }
 
-impl IntoSubsystem<anyhow::Error> for OscReceiverTask {
-    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
-        match self.main_loop().cancel_on_shutdown(subsys).await {
-            Ok(Ok(())) => {}
-            Ok(Err(error)) => return Err(error),
-            Err(CancelledByShutdown) => {}
-        }
+impl RestartableTask for OscReceiverTask {
+    const NAME: &'static str = "OSC receiver";
 
-        Ok(())
+    async fn run_once(&mut self) -> anyhow::Result<()> {
+        self.main_loop().await
     }
 }
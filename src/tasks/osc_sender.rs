@@ -1,17 +1,23 @@
 use async_osc::{OscMessage, OscSocket};
 use log::debug;
-use tokio::sync::mpsc;
-use tokio_graceful_shutdown::errors::CancelledByShutdown;
-use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::metrics::metrics;
+use crate::tasks::restart::RestartableTask;
 
 pub struct OscSenderTask {
     port: u16,
     rx: mpsc::Receiver<OscMessage>,
+    sent_tx: broadcast::Sender<OscMessage>,
 }
 
 impl OscSenderTask {
-    pub fn new(port: u16, rx: mpsc::Receiver<OscMessage>) -> Self {
-        Self { port, rx }
+    pub fn new(
+        port: u16,
+        rx: mpsc::Receiver<OscMessage>,
+        sent_tx: broadcast::Sender<OscMessage>,
+    ) -> Self {
+        Self { port, rx, sent_tx }
     }
 
     async fn main_loop(&mut self) -> anyhow::Result<()> {
@@ -19,6 +25,9 @@ impl OscSenderTask {
         socket.connect(("127.0.0.1", self.port)).await?;
 
         while let Some(message) = self.rx.recv().await {
+            metrics().record_osc_sent(&message.addr);
+            let _ = self.sent_tx.send(message.clone());
+
             if let Err(error) = socket.send(message).await {
                 debug!("Failed to send OSC message: {}", error);
             }
@@ -28,14 +37,10 @@ impl OscSenderTask {
     }
 }
 
-impl IntoSubsystem<anyhow::Error> for OscSenderTask {
-    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
-        match self.main_loop().cancel_on_shutdown(subsys).await {
-            Ok(Ok(())) => {}
-            Ok(Err(error)) => return Err(error),
-            Err(CancelledByShutdown) => {}
-        }
+impl RestartableTask for OscSenderTask {
+    const NAME: &'static str = "OSC sender";
 
-        Ok(())
+    async fn run_once(&mut self) -> anyhow::Result<()> {
+        self.main_loop().await
     }
 }
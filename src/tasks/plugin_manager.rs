@@ -1,23 +1,52 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use log::{error, warn};
-use tokio::sync::mpsc;
+use log::{error, info, warn};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{sleep_until, Instant};
 use tokio_graceful_shutdown::errors::CancelledByShutdown;
 use tokio_graceful_shutdown::{
-    FutureExt, IntoSubsystem, NestedSubsystem, SubsystemBuilder, SubsystemHandle,
+    ErrorAction, FutureExt, IntoSubsystem, NestedSubsystem, SubsystemBuilder, SubsystemHandle,
 };
 
-use crate::config::RootConfig;
+use crate::config::{NotificationCategory, ParameterMappings, RootConfig};
+use crate::metrics::metrics;
+use crate::plugins::parameters::ParameterMap;
 use crate::plugins::{ChannelManager, Plugin};
-use crate::utils::config::ConfigHandle;
+use crate::tasks::notifications::{Notification, NotificationAction};
+use crate::tasks::tray::TrayProperty;
+use crate::utils::config::{ConfigHandle, ConfigRegistry};
+
+/// Quiet period after a config change before plugins are restarted, so that saving several files at once, or an editor
+/// writing a file in multiple steps, restarts each plugin only once.
+const CONFIG_RESTART_DELAY: Duration = Duration::from_millis(500);
 
 pub enum Command {
     StartPlugins,
     StopPlugins,
     EnablePlugin(String),
     DisablePlugin(String),
+    /// Keeps the given plugins stopped without changing whether they are enabled.
+    SetSuppressed(HashSet<String>),
+    RestartPlugin(String),
+    InvokeTrayAction {
+        plugin_id: String,
+        action_id: String,
+    },
+    /// Reports the state of all plugins, sorted by ID.
+    ReportStates(oneshot::Sender<Vec<PluginState>>),
+}
+
+pub struct PluginState {
+    pub id: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub suppressed: bool,
 }
 
 struct PluginHandle {
@@ -25,11 +54,34 @@ struct PluginHandle {
     subsys: Option<NestedSubsystem<Box<dyn Error + Send + Sync>>>,
 }
 
+/// Hands out channels which follow the parameter mapping of the respective plugin.
+struct PluginChannels {
+    channel_manager: ChannelManager,
+    parameter_mappings: ParameterMappings,
+}
+
+impl PluginChannels {
+    fn for_plugin(&self, plugin_id: &str, plugin: &Arc<dyn Plugin>) -> Arc<ChannelManager> {
+        Arc::new(self.channel_manager.with_parameters(ParameterMap::new(
+            plugin.osc_parameters(),
+            self.parameter_mappings.get(plugin_id),
+        )))
+    }
+}
+
 pub struct PluginManagerTask {
     rx: mpsc::Receiver<Command>,
     config: ConfigHandle<RootConfig>,
     plugins: HashMap<&'static str, PluginHandle>,
-    channel_manager: Arc<ChannelManager>,
+    channels: PluginChannels,
+    config_rx: watch::Receiver<RootConfig>,
+    plugins_config_dir: PathBuf,
+    changes_rx: broadcast::Receiver<PathBuf>,
+    pending_restarts: HashSet<String>,
+    restart_deadline: Option<Instant>,
+    plugins_started: bool,
+    suppressed: HashSet<String>,
+    tray_property_tx: mpsc::Sender<TrayProperty>,
 }
 
 impl PluginManagerTask {
@@ -38,6 +90,8 @@ impl PluginManagerTask {
         config: ConfigHandle<RootConfig>,
         plugins: HashMap<&'static str, Arc<dyn Plugin>>,
         channel_manager: ChannelManager,
+        tray_property_tx: mpsc::Sender<TrayProperty>,
+        config_registry: &ConfigRegistry,
     ) -> Self {
         let plugins = plugins
             .into_iter()
@@ -52,11 +106,25 @@ impl PluginManagerTask {
             })
             .collect();
 
+        let mut config_rx = config.subscribe();
+        let parameter_mappings = config_rx.borrow_and_update().parameters.clone();
+
         Self {
             rx,
             config,
             plugins,
-            channel_manager: Arc::new(channel_manager),
+            channels: PluginChannels {
+                channel_manager,
+                parameter_mappings,
+            },
+            config_rx,
+            plugins_config_dir: config_registry.config_dir().join("plugins"),
+            changes_rx: config_registry.subscribe_changes(),
+            pending_restarts: HashSet::new(),
+            restart_deadline: None,
+            plugins_started: false,
+            suppressed: HashSet::new(),
+            tray_property_tx,
         }
     }
 
@@ -64,118 +132,394 @@ impl PluginManagerTask {
         plugin_id: String,
         plugin: Arc<dyn Plugin>,
         channel_manager: Arc<ChannelManager>,
+        tray_property_tx: mpsc::Sender<TrayProperty>,
         subsys: &SubsystemHandle,
     ) -> Option<NestedSubsystem<Box<dyn Error + Send + Sync>>> {
-        Some(subsys.start(SubsystemBuilder::new(
-            plugin_id,
-            async move |subsys: &mut SubsystemHandle| {
-                match plugin
-                    .run(subsys, channel_manager)
-                    .cancel_on_shutdown(subsys)
-                    .await
-                {
-                    Ok(Ok(())) | Err(CancelledByShutdown) => Ok(()),
-                    Ok(err) => err,
-                }
-            },
-        )))
-    }
+        Some(
+            subsys.start(
+                SubsystemBuilder::new(
+                    plugin_id.clone(),
+                    async move |subsys: &mut SubsystemHandle| {
+                        metrics().set_plugin_running(&plugin_id, true);
+                        let _ = tray_property_tx
+                            .send(TrayProperty::PluginFailed(plugin_id.clone(), false))
+                            .await;
+                        let _ = tray_property_tx
+                            .send(TrayProperty::PluginStarted(
+                                plugin_id.clone(),
+                                plugin.tray_actions().await,
+                            ))
+                            .await;
 
-    async fn main_loop(&mut self, subsys: &SubsystemHandle) -> anyhow::Result<()> {
-        let mut plugins_started = false;
-
-        while let Some(command) = self.rx.recv().await {
-            match command {
-                Command::StartPlugins => {
-                    let config = self.config.read().await;
-
-                    for plugin_id in config.enabled_plugins.iter() {
-                        let container = match self.plugins.get_mut(plugin_id.as_str()) {
-                            Some(container) => container,
-                            None => {
-                                warn!("Unknown plugin found in enabled_plugins: {}", plugin_id);
-                                continue;
+                        let tray_updates = async {
+                            loop {
+                                plugin.tray_actions_changed().await;
+                                let _ = tray_property_tx
+                                    .send(TrayProperty::PluginActions(
+                                        plugin_id.clone(),
+                                        plugin.tray_actions().await,
+                                    ))
+                                    .await;
+                            }
+                        };
+                        let run = async {
+                            select! {
+                                result = plugin.run(subsys, channel_manager.clone()) => result,
+                                _ = tray_updates => Ok(()),
                             }
                         };
 
-                        container.subsys = Self::start_plugin(
-                            plugin_id.clone(),
-                            container.plugin.clone(),
-                            self.channel_manager.clone(),
-                            subsys,
-                        );
-                    }
-
-                    plugins_started = true;
-                }
-                Command::StopPlugins => {
-                    for container in self.plugins.values_mut() {
-                        let subsys = match container.subsys.take() {
-                            Some(subsys) => subsys,
-                            None => continue,
+                        let result = match run.cancel_on_shutdown(subsys).await {
+                            Ok(Ok(())) | Err(CancelledByShutdown) => Ok(()),
+                            Ok(Err(error)) => {
+                                error!("Plugin {} failed: {:#}", plugin_id, error);
+                                channel_manager.notify(
+                                    Notification::new(
+                                        NotificationCategory::PluginFailures,
+                                        format!("{} stopped", plugin.title()),
+                                        format!("{:#}", error),
+                                    )
+                                    .with_action(
+                                        NotificationAction::RestartPlugin(plugin_id.clone()),
+                                    ),
+                                );
+                                let _ = tray_property_tx
+                                    .send(TrayProperty::PluginFailed(plugin_id.clone(), true))
+                                    .await;
+                                Err(error)
+                            }
                         };
 
-                        subsys.initiate_shutdown();
+                        metrics().set_plugin_running(&plugin_id, false);
+                        let _ = tray_property_tx
+                            .send(TrayProperty::PluginStopped(plugin_id.clone()))
+                            .await;
+                        result
+                    },
+                )
+                // A failing plugin must not take down the other plugins, it can be restarted instead.
+                .on_failure(ErrorAction::CatchAndLocalShutdown),
+            ),
+        )
+    }
+
+    async fn main_loop(&mut self, subsys: &SubsystemHandle) -> anyhow::Result<()> {
+        loop {
+            let restart_deadline = self.restart_deadline;
+
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => self.handle_command(command, subsys).await?,
+                    None => break,
+                },
+                path = self.changes_rx.recv() => match path {
+                    Ok(path) => self.schedule_restart_for_config(&path),
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => {}
+                },
+                _ = sleep_until(restart_deadline.unwrap_or_else(Instant::now)),
+                    if restart_deadline.is_some() => {
+                    self.restart_deadline = None;
+
+                    for plugin_id in std::mem::take(&mut self.pending_restarts) {
+                        info!("Restarting plugin {} after config change", plugin_id);
+                        self.restart_plugin(&plugin_id, subsys).await;
+                    }
+                },
+                result = self.config_rx.changed() => {
+                    if result.is_err() {
+                        break;
                     }
 
-                    plugins_started = false;
+                    self.apply_parameter_mappings(subsys).await;
                 }
-                Command::EnablePlugin(plugin_id) => {
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Schedules restarting a running plugin after one of its config files was replaced, unless it handles changes
+    /// itself.
+    fn schedule_restart_for_config(&mut self, path: &Path) {
+        let Some(plugin_id) = path
+            .strip_prefix(&self.plugins_config_dir)
+            .ok()
+            .and_then(|path| path.iter().next())
+            .and_then(|id| id.to_str())
+        else {
+            return;
+        };
+
+        let Some(container) = self.plugins.get(plugin_id) else {
+            return;
+        };
+
+        if container.plugin.reacts_to_config_changes() {
+            return;
+        }
+
+        self.pending_restarts.insert(plugin_id.to_string());
+        self.restart_deadline = Some(Instant::now() + CONFIG_RESTART_DELAY);
+    }
+
+    /// Restarts running plugins whose parameter mapping changed, so that their channels follow it.
+    async fn apply_parameter_mappings(&mut self, subsys: &SubsystemHandle) {
+        let parameter_mappings = self.config_rx.borrow_and_update().parameters.clone();
+
+        if parameter_mappings == self.channels.parameter_mappings {
+            return;
+        }
+
+        let changed: Vec<String> = self
+            .plugins
+            .keys()
+            .filter(|plugin_id| {
+                parameter_mappings.get(**plugin_id)
+                    != self.channels.parameter_mappings.get(**plugin_id)
+            })
+            .map(|plugin_id| plugin_id.to_string())
+            .collect();
+
+        self.channels.parameter_mappings = parameter_mappings;
+
+        for plugin_id in changed {
+            info!("Restarting plugin {} after parameter change", plugin_id);
+            self.restart_plugin(&plugin_id, subsys).await;
+        }
+    }
+
+    async fn restart_plugin(&mut self, plugin_id: &str, subsys: &SubsystemHandle) {
+        let Some(container) = self.plugins.get_mut(plugin_id) else {
+            return;
+        };
+
+        let Some(running) = container.subsys.take() else {
+            return;
+        };
+
+        running.initiate_shutdown();
+
+        if let Err(error) = running.join().await {
+            warn!("Plugin {} did not shut down cleanly: {}", plugin_id, error);
+        }
+
+        container.subsys = Self::start_plugin(
+            plugin_id.to_string(),
+            container.plugin.clone(),
+            self.channels.for_plugin(plugin_id, &container.plugin),
+            self.tray_property_tx.clone(),
+            subsys,
+        );
+    }
+
+    async fn handle_command(
+        &mut self,
+        command: Command,
+        subsys: &SubsystemHandle,
+    ) -> anyhow::Result<()> {
+        match command {
+            Command::StartPlugins => {
+                let config = self.config.read().await;
+
+                for plugin_id in config.enabled_plugins.iter() {
                     let container = match self.plugins.get_mut(plugin_id.as_str()) {
-                        Some(plugin) => plugin,
+                        Some(container) => container,
                         None => {
-                            error!("Plugin with ID {} not found", plugin_id);
+                            warn!("Unknown plugin found in enabled_plugins: {}", plugin_id);
                             continue;
                         }
                     };
 
-                    self.config
-                        .update({
-                            let plugin_id = plugin_id.clone();
-
-                            |config| {
-                                config.enabled_plugins.insert(plugin_id);
-                            }
-                        })
-                        .await?;
+                    let running = container
+                        .subsys
+                        .as_ref()
+                        .is_some_and(|subsys| !subsys.is_finished());
 
-                    if !plugins_started || container.subsys.is_some() {
+                    // The tray can request a start while plugins are already running, which must not start them twice.
+                    if running || self.suppressed.contains(plugin_id) {
                         continue;
                     }
 
                     container.subsys = Self::start_plugin(
                         plugin_id.clone(),
                         container.plugin.clone(),
-                        self.channel_manager.clone(),
+                        self.channels.for_plugin(plugin_id, &container.plugin),
+                        self.tray_property_tx.clone(),
                         subsys,
                     );
                 }
-                Command::DisablePlugin(plugin_id) => {
-                    let container = match self.plugins.get_mut(plugin_id.as_str()) {
-                        Some(plugin) => plugin,
-                        None => {
-                            error!("Plugin with ID {} not found", plugin_id);
-                            continue;
-                        }
-                    };
-
-                    self.config
-                        .update(|config| {
-                            config.enabled_plugins.remove(&plugin_id);
-                        })
-                        .await?;
-
-                    if !plugins_started {
-                        continue;
-                    }
 
+                self.plugins_started = true;
+            }
+            Command::StopPlugins => {
+                for (plugin_id, container) in self.plugins.iter_mut() {
                     let subsys = match container.subsys.take() {
                         Some(subsys) => subsys,
                         None => continue,
                     };
 
                     subsys.initiate_shutdown();
+                    self.tray_property_tx
+                        .send(TrayProperty::PluginFailed(plugin_id.to_string(), false))
+                        .await?;
+                }
+
+                self.plugins_started = false;
+            }
+            Command::EnablePlugin(plugin_id) => {
+                let container = match self.plugins.get_mut(plugin_id.as_str()) {
+                    Some(plugin) => plugin,
+                    None => {
+                        error!("Plugin with ID {} not found", plugin_id);
+                        return Ok(());
+                    }
+                };
+
+                self.config
+                    .update({
+                        let plugin_id = plugin_id.clone();
+
+                        |config| {
+                            config.enabled_plugins.insert(plugin_id);
+                        }
+                    })
+                    .await?;
+
+                if !self.plugins_started
+                    || container.subsys.is_some()
+                    || self.suppressed.contains(&plugin_id)
+                {
+                    return Ok(());
+                }
+
+                container.subsys = Self::start_plugin(
+                    plugin_id.clone(),
+                    container.plugin.clone(),
+                    self.channels.for_plugin(&plugin_id, &container.plugin),
+                    self.tray_property_tx.clone(),
+                    subsys,
+                );
+            }
+            Command::DisablePlugin(plugin_id) => {
+                let container = match self.plugins.get_mut(plugin_id.as_str()) {
+                    Some(plugin) => plugin,
+                    None => {
+                        error!("Plugin with ID {} not found", plugin_id);
+                        return Ok(());
+                    }
+                };
+
+                self.config
+                    .update(|config| {
+                        config.enabled_plugins.remove(&plugin_id);
+                    })
+                    .await?;
+
+                if !self.plugins_started {
+                    return Ok(());
                 }
+
+                let subsys = match container.subsys.take() {
+                    Some(subsys) => subsys,
+                    None => return Ok(()),
+                };
+
+                subsys.initiate_shutdown();
+                self.tray_property_tx
+                    .send(TrayProperty::PluginFailed(plugin_id, false))
+                    .await?;
+            }
+            Command::SetSuppressed(suppressed) => {
+                let enabled_plugins = self.config.read().await.enabled_plugins.clone();
+
+                for (plugin_id, container) in self.plugins.iter_mut() {
+                    let plugin_id = plugin_id.to_string();
+
+                    if suppressed.contains(&plugin_id) {
+                        if let Some(subsys) = container.subsys.take() {
+                            info!("Suppressing plugin {}", plugin_id);
+                            subsys.initiate_shutdown();
+                        }
+                    } else if self.plugins_started
+                        && container.subsys.is_none()
+                        && enabled_plugins.contains(&plugin_id)
+                    {
+                        container.subsys = Self::start_plugin(
+                            plugin_id.clone(),
+                            container.plugin.clone(),
+                            self.channels.for_plugin(&plugin_id, &container.plugin),
+                            self.tray_property_tx.clone(),
+                            subsys,
+                        );
+                    }
+                }
+
+                self.suppressed = suppressed;
+            }
+            Command::RestartPlugin(plugin_id) => {
+                info!("Restarting plugin {} on request", plugin_id);
+                self.restart_plugin(&plugin_id, subsys).await;
+            }
+            Command::InvokeTrayAction {
+                plugin_id,
+                action_id,
+            } => {
+                let Some(container) = self.plugins.get(plugin_id.as_str()) else {
+                    return Ok(());
+                };
+
+                if container
+                    .subsys
+                    .as_ref()
+                    .is_none_or(|subsys| subsys.is_finished())
+                {
+                    info!(
+                        "Ignoring tray action {} of plugin {}, it is not running",
+                        action_id, plugin_id
+                    );
+                    return Ok(());
+                }
+
+                let plugin = container.plugin.clone();
+                let channels = self.channels.for_plugin(&plugin_id, &plugin);
+                let tray_property_tx = self.tray_property_tx.clone();
+
+                // Actions may take a while, so they must not hold up other commands.
+                tokio::spawn(async move {
+                    if let Err(error) = plugin.invoke_tray_action(&action_id, channels).await {
+                        warn!(
+                            "Tray action {} of plugin {} failed: {:#}",
+                            action_id, plugin_id, error
+                        );
+                    }
+
+                    let _ = tray_property_tx
+                        .send(TrayProperty::PluginActions(
+                            plugin_id,
+                            plugin.tray_actions().await,
+                        ))
+                        .await;
+                });
+            }
+            Command::ReportStates(reply_tx) => {
+                let enabled_plugins = self.config.read().await.enabled_plugins.clone();
+                let mut states: Vec<PluginState> = self
+                    .plugins
+                    .iter()
+                    .map(|(plugin_id, container)| PluginState {
+                        id: plugin_id.to_string(),
+                        enabled: enabled_plugins.contains(*plugin_id),
+                        running: container
+                            .subsys
+                            .as_ref()
+                            .is_some_and(|subsys| !subsys.is_finished()),
+                        suppressed: self.suppressed.contains(*plugin_id),
+                    })
+                    .collect();
+                states.sort_by(|a, b| a.id.cmp(&b.id));
+
+                let _ = reply_tx.send(states);
             }
         }
 
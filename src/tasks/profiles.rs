@@ -0,0 +1,322 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel, Weak};
+use tokio::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::RootConfig;
+use crate::tasks::plugin_manager::Command;
+use crate::tasks::tray::TrayProperty;
+use crate::utils::config::{ConfigHandle, ConfigRegistry};
+use crate::{AppWindow, PluginItems, Profiles};
+
+const MANIFEST_FILE: &str = "profile.toml";
+
+pub enum ProfileCommand {
+    Switch(String),
+    Save(String),
+    Delete(String),
+}
+
+/// The part of a profile which lives in the root config.
+#[derive(Serialize, Deserialize)]
+struct ProfileManifest {
+    enabled_plugins: BTreeSet<String>,
+}
+
+/// Lists all profiles found in the profiles directory, sorted by name.
+pub fn list_profiles(profiles_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(profiles_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    names.sort();
+    names
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() {
+        bail!("Profile name must not be empty");
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+    {
+        bail!("Profile names may only contain letters, digits, spaces, dashes and underscores");
+    }
+
+    Ok(())
+}
+
+/// Enables and disables plugins to match the target set, updating the plugin list accordingly.
+///
+/// Only plugins which differ are toggled, so that unaffected plugins keep running.
+pub async fn switch_enabled_plugins(
+    plugin_manager_tx: &mpsc::Sender<Command>,
+    app_window: &Weak<AppWindow>,
+    enabled_plugins: &HashSet<String>,
+    target: BTreeSet<String>,
+) -> anyhow::Result<()> {
+    for plugin_id in &target {
+        if !enabled_plugins.contains(plugin_id) {
+            plugin_manager_tx
+                .send(Command::EnablePlugin(plugin_id.clone()))
+                .await?;
+        }
+    }
+
+    for plugin_id in enabled_plugins {
+        if !target.contains(plugin_id) {
+            plugin_manager_tx
+                .send(Command::DisablePlugin(plugin_id.clone()))
+                .await?;
+        }
+    }
+
+    app_window.upgrade_in_event_loop(move |handle| {
+        let items = handle.global::<PluginItems>().get_items();
+
+        for index in 0..items.row_count() {
+            let Some(mut item) = items.row_data(index) else {
+                continue;
+            };
+
+            item.enabled = target.contains(item.id.as_str());
+            items.set_row_data(index, item);
+        }
+    })?;
+
+    Ok(())
+}
+
+pub struct ProfilesTask {
+    rx: mpsc::Receiver<ProfileCommand>,
+    config: ConfigHandle<RootConfig>,
+    registry: ConfigRegistry,
+    plugin_manager_tx: mpsc::Sender<Command>,
+    tray_property_tx: mpsc::Sender<TrayProperty>,
+    app_window: Weak<AppWindow>,
+    profiles_dir: PathBuf,
+}
+
+impl ProfilesTask {
+    pub fn new(
+        rx: mpsc::Receiver<ProfileCommand>,
+        config: ConfigHandle<RootConfig>,
+        registry: ConfigRegistry,
+        plugin_manager_tx: mpsc::Sender<Command>,
+        tray_property_tx: mpsc::Sender<TrayProperty>,
+        app_window: Weak<AppWindow>,
+    ) -> Self {
+        Self {
+            rx,
+            config,
+            profiles_dir: registry.config_dir().join("profiles"),
+            registry,
+            plugin_manager_tx,
+            tray_property_tx,
+            app_window,
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        self.publish(None).await?;
+
+        while let Some(command) = self.rx.recv().await {
+            let result = match &command {
+                ProfileCommand::Switch(name) => self.switch(name).await,
+                ProfileCommand::Save(name) => self.save(name).await,
+                ProfileCommand::Delete(name) => self.delete(name).await,
+            };
+
+            let status = match (result, command) {
+                (Ok(()), ProfileCommand::Switch(name)) => format!("Switched to profile {}", name),
+                (Ok(()), ProfileCommand::Save(name)) => format!("Saved profile {}", name),
+                (Ok(()), ProfileCommand::Delete(name)) => format!("Deleted profile {}", name),
+                (Err(error), _) => {
+                    warn!("Profile operation failed: {:#}", error);
+                    format!("{:#}", error)
+                }
+            };
+
+            self.publish(Some(status)).await?;
+        }
+
+        Ok(())
+    }
+
+    fn plugins_dir(&self) -> PathBuf {
+        self.registry.config_dir().join("plugins")
+    }
+
+    async fn save(&mut self, name: &str) -> anyhow::Result<()> {
+        validate_name(name)?;
+
+        let profile_dir = self.profiles_dir.join(name);
+        let manifest = ProfileManifest {
+            enabled_plugins: self
+                .config
+                .read()
+                .await
+                .enabled_plugins
+                .iter()
+                .cloned()
+                .collect(),
+        };
+
+        create_dir_all(&profile_dir).await?;
+        write(
+            profile_dir.join(MANIFEST_FILE),
+            toml::to_string_pretty(&manifest)?,
+        )
+        .await?;
+
+        for config in self.registry.configs() {
+            let Ok(relative_path) = config.path().strip_prefix(self.registry.config_dir()) else {
+                continue;
+            };
+
+            if !config.path().starts_with(self.plugins_dir()) {
+                continue;
+            }
+
+            let target = profile_dir.join(relative_path);
+
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent).await?;
+            }
+
+            write(&target, config.snapshot().await).await?;
+        }
+
+        self.config
+            .update(|config| config.active_profile = Some(name.to_string()))
+            .await?;
+        info!("Saved profile {}", name);
+
+        Ok(())
+    }
+
+    async fn switch(&mut self, name: &str) -> anyhow::Result<()> {
+        validate_name(name)?;
+
+        let profile_dir = self.profiles_dir.join(name);
+        let manifest: ProfileManifest = toml::from_str(
+            &read_to_string(profile_dir.join(MANIFEST_FILE))
+                .await
+                .with_context(|| format!("Profile {} does not exist", name))?,
+        )?;
+
+        // Keep changes made while the previous profile was active.
+        let active_profile = self.config.read().await.active_profile.clone();
+
+        if let Some(active_profile) = active_profile.filter(|active| active != name) {
+            if self.profiles_dir.join(&active_profile).is_dir() {
+                self.save(&active_profile).await?;
+            }
+        }
+
+        for config in self.registry.configs() {
+            let Ok(relative_path) = config.path().strip_prefix(self.registry.config_dir()) else {
+                continue;
+            };
+
+            let source = profile_dir.join(relative_path);
+
+            if !config.path().starts_with(self.plugins_dir()) || !source.is_file() {
+                continue;
+            }
+
+            if config.apply(&read_to_string(&source).await?).await? {
+                self.registry.clear_issue(config.path());
+                info!("Applied {} from profile {}", config.name(), name);
+            }
+        }
+
+        let enabled_plugins = self.config.read().await.enabled_plugins.clone();
+        switch_enabled_plugins(
+            &self.plugin_manager_tx,
+            &self.app_window,
+            &enabled_plugins,
+            manifest.enabled_plugins,
+        )
+        .await?;
+
+        self.config
+            .update(|config| config.active_profile = Some(name.to_string()))
+            .await?;
+        info!("Switched to profile {}", name);
+
+        Ok(())
+    }
+
+    async fn delete(&mut self, name: &str) -> anyhow::Result<()> {
+        validate_name(name)?;
+        remove_dir_all(self.profiles_dir.join(name))
+            .await
+            .with_context(|| format!("Could not delete profile {}", name))?;
+
+        self.config
+            .update(|config| {
+                if config.active_profile.as_deref() == Some(name) {
+                    config.active_profile = None;
+                }
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn publish(&self, status: Option<String>) -> anyhow::Result<()> {
+        let profiles = list_profiles(&self.profiles_dir);
+        self.tray_property_tx
+            .send(TrayProperty::Profiles(profiles.clone()))
+            .await?;
+
+        let names: Vec<SharedString> = profiles.into_iter().map(Into::into).collect();
+        let active = self
+            .config
+            .read()
+            .await
+            .active_profile
+            .clone()
+            .unwrap_or_default();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let profiles = handle.global::<Profiles>();
+            profiles.set_names(ModelRc::new(VecModel::from(names)));
+            profiles.set_active(active.into());
+
+            if let Some(status) = status {
+                profiles.set_status(status.into());
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for ProfilesTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use std::convert::Infallible;
+use std::future::pending;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use async_osc::{OscMessage, OscType};
+use chrono::Local;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use slint::{ComponentHandle, Weak};
+use tokio::fs::{create_dir_all, read_to_string, File};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep_until;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemBuilder, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{AppWindow, Recorder};
+
+/// Playback speed multipliers a recording can be replayed at.
+pub const REPLAY_SPEEDS: RangeInclusive<f32> = 0.01..=100.;
+
+pub enum RecorderCommand {
+    StartRecording(Option<PathBuf>),
+    StopRecording,
+    StartReplay { path: PathBuf, speed: f32 },
+    StopReplay,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+enum RecordedArgument {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl RecordedArgument {
+    fn from_osc(argument: &OscType) -> Option<Self> {
+        Some(match argument {
+            OscType::Int(value) => Self::Int(*value),
+            OscType::Long(value) => Self::Long(*value),
+            OscType::Float(value) => Self::Float(*value),
+            OscType::Double(value) => Self::Double(*value),
+            OscType::Bool(value) => Self::Bool(*value),
+            OscType::String(value) => Self::String(value.clone()),
+            _ => return None,
+        })
+    }
+
+    fn into_osc(self) -> OscType {
+        match self {
+            Self::Int(value) => OscType::Int(value),
+            Self::Long(value) => OscType::Long(value),
+            Self::Float(value) => OscType::Float(value),
+            Self::Double(value) => OscType::Double(value),
+            Self::Bool(value) => OscType::Bool(value),
+            Self::String(value) => OscType::String(value),
+        }
+    }
+}
+
+/// A single line of a recording, with the time since the start of the recording.
+#[derive(Serialize, Deserialize)]
+struct RecordedMessage {
+    offset_ms: u64,
+    addr: String,
+    args: Vec<RecordedArgument>,
+}
+
+struct Recording {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    osc_rx: broadcast::Receiver<OscMessage>,
+    started_at: Instant,
+    count: usize,
+}
+
+impl Recording {
+    async fn write(&mut self, message: &OscMessage) -> anyhow::Result<()> {
+        let args: Option<Vec<_>> = message
+            .args
+            .iter()
+            .map(RecordedArgument::from_osc)
+            .collect();
+
+        let Some(args) = args else {
+            debug!(
+                "Skipping message with unsupported arguments: {}",
+                message.addr
+            );
+            return Ok(());
+        };
+
+        let mut line = serde_json::to_string(&RecordedMessage {
+            offset_ms: self.started_at.elapsed().as_millis() as u64,
+            addr: message.addr.clone(),
+            args,
+        })?;
+        line.push('\n');
+
+        self.writer.write_all(line.as_bytes()).await?;
+        self.count += 1;
+
+        Ok(())
+    }
+}
+
+async fn next_message(recording: &mut Option<Recording>) -> Result<OscMessage, RecvError> {
+    match recording {
+        Some(recording) => recording.osc_rx.recv().await,
+        None => pending().await,
+    }
+}
+
+fn set_status(app_window: &Weak<AppWindow>, status: String) {
+    let _ = app_window.upgrade_in_event_loop(move |handle| {
+        handle.global::<Recorder>().set_status(status.into());
+    });
+}
+
+pub struct RecorderTask {
+    rx: mpsc::Receiver<RecorderCommand>,
+    osc_tx: broadcast::Sender<OscMessage>,
+    /// Messages received from VRChat, which leaves out replayed and simulated ones so they are not recorded again.
+    received_tx: broadcast::Sender<OscMessage>,
+    app_window: Weak<AppWindow>,
+    recordings_dir: PathBuf,
+    recording: Option<Recording>,
+    replay: Option<CancellationToken>,
+    /// ID of the most recently started replay, so that a replay which is still stopping does not update the UI.
+    replay_id: Arc<AtomicU64>,
+}
+
+impl RecorderTask {
+    pub fn new(
+        rx: mpsc::Receiver<RecorderCommand>,
+        osc_tx: broadcast::Sender<OscMessage>,
+        received_tx: broadcast::Sender<OscMessage>,
+        app_window: Weak<AppWindow>,
+        recordings_dir: PathBuf,
+    ) -> Self {
+        Self {
+            rx,
+            osc_tx,
+            received_tx,
+            app_window,
+            recordings_dir,
+            recording: None,
+            replay: None,
+            replay_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    async fn main_loop(&mut self, subsys: &SubsystemHandle) -> anyhow::Result<()> {
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(command) => self.handle_command(command, subsys).await,
+                    None => break,
+                },
+                message = next_message(&mut self.recording) => match message {
+                    Ok(message) => self.record(&message).await,
+                    Err(RecvError::Closed) => self.stop_recording().await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Recorder lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                    }
+                },
+            }
+        }
+
+        self.stop_recording().await;
+        Ok(())
+    }
+
+    async fn handle_command(&mut self, command: RecorderCommand, subsys: &SubsystemHandle) {
+        match command {
+            RecorderCommand::StartRecording(path) => {
+                if let Err(error) = self.start_recording(path).await {
+                    warn!("Failed to start recording: {}", error);
+                    set_status(&self.app_window, format!("Recording failed: {}", error));
+                }
+            }
+            RecorderCommand::StopRecording => self.stop_recording().await,
+            RecorderCommand::StartReplay { path, speed } => {
+                if let Some(token) = self.replay.take() {
+                    token.cancel();
+                }
+
+                let cancellation_token = CancellationToken::new();
+                let id = self.replay_id.fetch_add(1, Ordering::SeqCst) + 1;
+
+                subsys.start(SubsystemBuilder::new("Replay", {
+                    let replay = Replay {
+                        id,
+                        current_id: self.replay_id.clone(),
+                        path,
+                        speed,
+                        osc_tx: self.osc_tx.clone(),
+                        app_window: self.app_window.clone(),
+                        cancellation_token: cancellation_token.clone(),
+                    };
+
+                    async move |s: &mut SubsystemHandle| replay.run(s).await
+                }));
+
+                self.replay = Some(cancellation_token);
+            }
+            RecorderCommand::StopReplay => {
+                if let Some(token) = self.replay.take() {
+                    token.cancel();
+                }
+            }
+        }
+    }
+
+    async fn start_recording(&mut self, path: Option<PathBuf>) -> anyhow::Result<()> {
+        self.stop_recording().await;
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                create_dir_all(&self.recordings_dir).await?;
+                self.recordings_dir.join(format!(
+                    "session-{}.jsonl",
+                    Local::now().format("%Y%m%d-%H%M%S")
+                ))
+            }
+        };
+
+        let file = File::create(&path)
+            .await
+            .with_context(|| format!("Could not create {}", path.to_string_lossy()))?;
+
+        info!("Recording OSC session to {}", path.to_string_lossy());
+        set_status(
+            &self.app_window,
+            format!("Recording to {}", path.to_string_lossy()),
+        );
+
+        self.recording = Some(Recording {
+            path,
+            writer: BufWriter::new(file),
+            osc_rx: self.received_tx.subscribe(),
+            started_at: Instant::now(),
+            count: 0,
+        });
+
+        let _ = self.app_window.upgrade_in_event_loop(|handle| {
+            handle.global::<Recorder>().set_recording(true);
+        });
+
+        Ok(())
+    }
+
+    async fn record(&mut self, message: &OscMessage) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+
+        if let Err(error) = recording.write(message).await {
+            warn!("Failed to write recorded message: {}", error);
+            self.stop_recording().await;
+        }
+    }
+
+    async fn stop_recording(&mut self) {
+        let Some(mut recording) = self.recording.take() else {
+            return;
+        };
+
+        if let Err(error) = recording.writer.flush().await {
+            warn!("Failed to flush recording: {}", error);
+        }
+
+        let path = recording.path.to_string_lossy().to_string();
+        info!("Recorded {} messages to {}", recording.count, path);
+        set_status(
+            &self.app_window,
+            format!("Recorded {} messages to {}", recording.count, path),
+        );
+
+        let _ = self.app_window.upgrade_in_event_loop(move |handle| {
+            let recorder = handle.global::<Recorder>();
+            recorder.set_recording(false);
+            recorder.set_replay_path(path.into());
+        });
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for RecorderTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop(subsys).cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => self.stop_recording().await,
+        }
+
+        Ok(())
+    }
+}
+
+struct Replay {
+    id: u64,
+    current_id: Arc<AtomicU64>,
+    path: PathBuf,
+    speed: f32,
+    osc_tx: broadcast::Sender<OscMessage>,
+    app_window: Weak<AppWindow>,
+    cancellation_token: CancellationToken,
+}
+
+impl Replay {
+    async fn load(&self) -> anyhow::Result<Vec<RecordedMessage>> {
+        let content = read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Could not read {}", self.path.to_string_lossy()))?;
+
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).with_context(|| format!("Invalid line {}", index + 1))
+            })
+            .collect()
+    }
+
+    async fn main_loop(&self) -> anyhow::Result<usize> {
+        let messages = self.load().await?;
+        let total = messages.len();
+        let speed = if self.speed.is_nan() {
+            1.
+        } else {
+            self.speed
+                .clamp(*REPLAY_SPEEDS.start(), *REPLAY_SPEEDS.end())
+        };
+        let started_at = tokio::time::Instant::now();
+
+        info!(
+            "Replaying {} messages from {} at {}x speed",
+            total,
+            self.path.to_string_lossy(),
+            speed
+        );
+        set_status(
+            &self.app_window,
+            format!("Replaying {} messages at {}x", total, speed),
+        );
+
+        for (index, message) in messages.into_iter().enumerate() {
+            let deadline = started_at
+                + Duration::try_from_secs_f64(message.offset_ms as f64 / 1000. / speed as f64)
+                    .with_context(|| format!("Invalid offset of message {}", index + 1))?;
+
+            select! {
+                _ = self.cancellation_token.cancelled() => return Ok(index),
+                _ = sleep_until(deadline) => {}
+            }
+
+            let _ = self.osc_tx.send(OscMessage {
+                addr: message.addr,
+                args: message
+                    .args
+                    .into_iter()
+                    .map(RecordedArgument::into_osc)
+                    .collect(),
+            });
+        }
+
+        Ok(total)
+    }
+
+    fn set_replaying(&self, replaying: bool) {
+        let id = self.id;
+        let current_id = self.current_id.clone();
+
+        // Checked in the event loop, as the update of a newer replay may already be queued there.
+        let _ = self.app_window.upgrade_in_event_loop(move |handle| {
+            if current_id.load(Ordering::SeqCst) == id {
+                handle.global::<Recorder>().set_replaying(replaying);
+            }
+        });
+    }
+}
+
+impl IntoSubsystem<Infallible> for Replay {
+    async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Infallible> {
+        self.set_replaying(true);
+
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(count)) => {
+                info!("Replayed {} messages", count);
+                set_status(&self.app_window, format!("Replayed {} messages", count));
+            }
+            Ok(Err(error)) => {
+                warn!("Replay failed: {:#}", error);
+                set_status(&self.app_window, format!("Replay failed: {:#}", error));
+            }
+            Err(CancelledByShutdown) => {}
+        }
+
+        self.set_replaying(false);
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use log::{info, warn};
+use slint::{ComponentHandle, Model, ModelRc, VecModel, Weak};
+use tokio::select;
+use tokio::time::sleep;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::{AppWindow, SubsystemRestartItem, SubsystemRestarts};
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// A task running at least this long before failing counts as recovered, which resets the backoff.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Failures in a row after which the error is passed on, so that all background tasks are restarted.
+const MAX_FAILURES: u32 = 5;
+
+/// A core task which can be run again after a failure, keeping its channels and other state.
+pub trait RestartableTask: Send + Sync + 'static {
+    /// Name shown to the user when the task is restarted.
+    const NAME: &'static str;
+
+    fn run_once(&mut self) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// Restarts the wrapped task with an exponential backoff whenever it fails.
+pub struct RestartingTask<T> {
+    task: T,
+    app_window: Weak<AppWindow>,
+}
+
+impl<T: RestartableTask> RestartingTask<T> {
+    pub fn new(task: T, app_window: Weak<AppWindow>) -> Self {
+        Self { task, app_window }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut failures: u32 = 0;
+        let mut restarts: u32 = 0;
+
+        loop {
+            let started_at = Instant::now();
+            let run = self.task.run_once();
+            tokio::pin!(run);
+
+            let result = select! {
+                result = &mut run => result,
+                _ = sleep(STABLE_AFTER) => {
+                    // Running stable again, so the restarts no longer need the user's attention.
+                    if restarts > 0 {
+                        restarts = 0;
+                        publish::<T>(&self.app_window, None)?;
+                    }
+                    run.await
+                }
+            };
+
+            let Err(error) = result else {
+                return Ok(());
+            };
+
+            if started_at.elapsed() >= STABLE_AFTER {
+                failures = 0;
+                delay = INITIAL_RETRY_DELAY;
+            }
+
+            failures += 1;
+
+            if failures >= MAX_FAILURES {
+                return Err(error)
+                    .with_context(|| format!("{} failed {} times in a row", T::NAME, failures));
+            }
+
+            restarts += 1;
+            warn!(
+                "{} failed, restarting in {} seconds: {:#}",
+                T::NAME,
+                delay.as_secs(),
+                error
+            );
+            publish::<T>(&self.app_window, Some((format!("{:#}", error), restarts)))?;
+
+            sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+            info!("Restarting {}", T::NAME);
+        }
+    }
+}
+
+/// Replaces the restart entry of the task shown to the user, or removes it if there is no restart to show.
+fn publish<T: RestartableTask>(
+    app_window: &Weak<AppWindow>,
+    restart: Option<(String, u32)>,
+) -> anyhow::Result<()> {
+    app_window.upgrade_in_event_loop(move |handle| {
+        let subsystem_restarts = handle.global::<SubsystemRestarts>();
+        let mut items: Vec<SubsystemRestartItem> = subsystem_restarts
+            .get_items()
+            .iter()
+            .filter(|item| item.name != T::NAME)
+            .collect();
+
+        if let Some((message, restarts)) = restart {
+            items.push(SubsystemRestartItem {
+                name: T::NAME.into(),
+                message: message.into(),
+                restarts: restarts as i32,
+            });
+        }
+        subsystem_restarts.set_items(ModelRc::new(VecModel::from(items)));
+    })?;
+
+    Ok(())
+}
+
+impl<T: RestartableTask> IntoSubsystem<anyhow::Error> for RestartingTask<T> {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,543 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use chrono::Local;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use slint::{ComponentHandle, Weak};
+use tokio::fs::create_dir_all;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::config::RootConfig;
+use crate::platform::{get_platform, Platform};
+use crate::tasks::plugin_manager::Command;
+use crate::tasks::profiles::switch_enabled_plugins;
+use crate::utils::config::{ConfigHandle, ConfigRegistry, ManagedConfig, Secrets, VERSION_KEY};
+use crate::{AppWindow, Setup, VERSION};
+
+const MANIFEST_FILE: &str = "setup.toml";
+const FORMAT_VERSION: u32 = 1;
+
+/// Config files are small, anything beyond this is not a config file.
+const MAX_ENTRY_SIZE: u64 = 1024 * 1024;
+
+/// Settings of the main config which are part of a setup. All others, such as auto start or ports, are specific to the
+/// machine and keep their local values.
+const PORTABLE_ROOT_KEYS: &[&str] = &["enabled_plugins", "parameters"];
+
+pub enum SetupCommand {
+    Export {
+        plugins: Vec<String>,
+        include_secrets: bool,
+    },
+    Preview(PathBuf),
+    Apply,
+    Discard,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SetupManifest {
+    format: u32,
+    app_version: String,
+    includes_secrets: bool,
+}
+
+/// A validated config file from an archive, ready to be applied.
+struct PendingFile {
+    config: Arc<dyn ManagedConfig>,
+    /// ID of the plugin the config belongs to, which scopes its secrets.
+    plugin_id: Option<String>,
+    source: String,
+}
+
+/// Leaves only the portable settings of the main config.
+fn portable_settings(source: &str) -> anyhow::Result<String> {
+    let mut table: toml::Table = toml::from_str(source)?;
+    table.retain(|key, _| key == VERSION_KEY || PORTABLE_ROOT_KEYS.contains(&key));
+
+    Ok(toml::to_string_pretty(&table)?)
+}
+
+/// Replaces the portable settings of the current main config with the imported ones.
+fn with_portable_settings(source: &str, current: &str) -> anyhow::Result<String> {
+    let source: toml::Table = toml::from_str(source)?;
+    let mut table: toml::Table = toml::from_str(current)?;
+
+    for key in PORTABLE_ROOT_KEYS {
+        match source.get(*key) {
+            Some(value) => table.insert(key.to_string(), value.clone()),
+            None => table.remove(*key),
+        };
+    }
+
+    Ok(toml::to_string_pretty(&table)?)
+}
+
+/// Normalizes a config source for comparison, leaving out the given keys.
+fn normalize(source: &str, excluded_keys: &[&str]) -> anyhow::Result<String> {
+    let mut table: toml::Table = toml::from_str(source)?;
+
+    for key in excluded_keys {
+        table.remove(*key);
+    }
+
+    Ok(toml::to_string_pretty(&table)?)
+}
+
+/// Fills in secrets missing from an imported source with the current values.
+fn keep_secrets(source: &str, current: &str, secret_keys: &[&str]) -> anyhow::Result<String> {
+    if secret_keys.is_empty() {
+        return Ok(source.to_string());
+    }
+
+    let mut table: toml::Table = toml::from_str(source)?;
+    let current: toml::Table = toml::from_str(current)?;
+
+    for key in secret_keys {
+        if table.contains_key(*key) {
+            continue;
+        }
+
+        if let Some(value) = current.get(*key) {
+            table.insert(key.to_string(), value.clone());
+        }
+    }
+
+    Ok(toml::to_string_pretty(&table)?)
+}
+
+fn write_archive(path: &Path, entries: Vec<(String, String)>) -> anyhow::Result<()> {
+    let mut writer = ZipWriter::new(File::create(path)?);
+
+    for (name, content) in entries {
+        writer.start_file(name, SimpleFileOptions::default())?;
+        writer.write_all(content.as_bytes())?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+fn read_archive(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let mut archive = ZipArchive::new(File::open(path)?).context("Not a setup archive")?;
+    let mut entries = vec![];
+
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+
+        if file.is_dir() {
+            continue;
+        }
+
+        if file.size() > MAX_ENTRY_SIZE {
+            bail!("{} is too large to be a config file", file.name());
+        }
+
+        let name = file.name().to_string();
+        let mut content = String::new();
+        file.take(MAX_ENTRY_SIZE)
+            .read_to_string(&mut content)
+            .with_context(|| format!("{} is not a text file", name))?;
+        entries.push((name, content));
+    }
+
+    Ok(entries)
+}
+
+pub struct SetupsTask {
+    rx: mpsc::Receiver<SetupCommand>,
+    config: ConfigHandle<RootConfig>,
+    registry: ConfigRegistry,
+    secrets: Secrets,
+    plugin_manager_tx: mpsc::Sender<Command>,
+    app_window: Weak<AppWindow>,
+    exports_dir: PathBuf,
+    pending: Vec<PendingFile>,
+}
+
+impl SetupsTask {
+    pub fn new(
+        rx: mpsc::Receiver<SetupCommand>,
+        config: ConfigHandle<RootConfig>,
+        registry: ConfigRegistry,
+        secrets: Secrets,
+        plugin_manager_tx: mpsc::Sender<Command>,
+        app_window: Weak<AppWindow>,
+        exports_dir: PathBuf,
+    ) -> Self {
+        Self {
+            rx,
+            config,
+            registry,
+            secrets,
+            plugin_manager_tx,
+            app_window,
+            exports_dir,
+            pending: vec![],
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        while let Some(command) = self.rx.recv().await {
+            let result = match command {
+                SetupCommand::Export {
+                    plugins,
+                    include_secrets,
+                } => self.export(plugins, include_secrets).await,
+                SetupCommand::Preview(path) => self.preview(&path).await,
+                SetupCommand::Apply => self.apply().await,
+                SetupCommand::Discard => {
+                    self.pending.clear();
+                    Ok("Import discarded".to_string())
+                }
+            };
+
+            let status = match result {
+                Ok(status) => status,
+                Err(error) => {
+                    warn!("Setup transfer failed: {:#}", error);
+                    format!("{:#}", error)
+                }
+            };
+
+            self.publish(status, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path of a config file within the archive, if it is part of a setup.
+    fn entry_name(&self, config: &dyn ManagedConfig) -> Option<String> {
+        let relative_path = config
+            .path()
+            .strip_prefix(self.registry.config_dir())
+            .ok()?;
+        let components: Vec<_> = relative_path
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect();
+
+        match components.as_slice() {
+            [file] if file == "config.toml" => Some(file.to_string()),
+            [plugins, plugin_id, file] if plugins == "plugins" => {
+                Some(format!("plugins/{}/{}", plugin_id, file))
+            }
+            _ => None,
+        }
+    }
+
+    async fn export(
+        &mut self,
+        plugins: Vec<String>,
+        include_secrets: bool,
+    ) -> anyhow::Result<String> {
+        let manifest = SetupManifest {
+            format: FORMAT_VERSION,
+            app_version: VERSION.to_string(),
+            includes_secrets: include_secrets,
+        };
+        let mut entries = vec![(
+            MANIFEST_FILE.to_string(),
+            toml::to_string_pretty(&manifest)?,
+        )];
+
+        for config in self.registry.configs() {
+            let Some(name) = self.entry_name(config.as_ref()) else {
+                continue;
+            };
+
+            let selected = match name.split('/').nth(1) {
+                Some(plugin_id) => plugins.iter().any(|selected| selected == plugin_id),
+                None => true,
+            };
+
+            if !selected {
+                continue;
+            }
+
+            let snapshot = config.snapshot().await;
+            let content = match (include_secrets, name.split('/').nth(1)) {
+                (true, Some(plugin_id)) => {
+                    self.with_stored_secrets(&snapshot, plugin_id, config.secret_keys())
+                        .await?
+                }
+                (false, Some(_)) => normalize(&snapshot, config.secret_keys())?,
+                (_, None) => portable_settings(&snapshot)?,
+            };
+
+            entries.push((name, content));
+        }
+
+        create_dir_all(&self.exports_dir).await?;
+        let path = self.exports_dir.join(format!(
+            "setup-{}.zip",
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+
+        spawn_blocking({
+            let path = path.clone();
+            move || write_archive(&path, entries)
+        })
+        .await??;
+
+        info!("Exported setup to {}", path.to_string_lossy());
+        get_platform().open_folder(&self.exports_dir);
+
+        Ok(format!("Exported setup to {}", path.to_string_lossy()))
+    }
+
+    /// Adds the credentials a plugin keeps in the secret store, which the plugin moves back there once imported.
+    async fn with_stored_secrets(
+        &self,
+        source: &str,
+        plugin_id: &str,
+        secret_keys: &[&str],
+    ) -> anyhow::Result<String> {
+        if secret_keys.is_empty() {
+            return Ok(source.to_string());
+        }
+
+        let mut table: toml::Table = toml::from_str(source)?;
+
+        for key in secret_keys {
+            let value = self
+                .secrets
+                .get(&format!("{}.{}", plugin_id, key))
+                .await
+                .with_context(|| format!("Could not read {} of {}", key, plugin_id))?;
+
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                table.insert(key.to_string(), toml::Value::String(value));
+            }
+        }
+
+        Ok(toml::to_string_pretty(&table)?)
+    }
+
+    async fn preview(&mut self, path: &Path) -> anyhow::Result<String> {
+        self.pending.clear();
+
+        let entries = spawn_blocking({
+            let path = path.to_path_buf();
+            move || read_archive(&path)
+        })
+        .await?
+        .with_context(|| format!("Could not read {}", path.to_string_lossy()))?;
+
+        let manifest: SetupManifest = toml::from_str(
+            entries
+                .iter()
+                .find(|(name, _)| name == MANIFEST_FILE)
+                .map(|(_, content)| content.as_str())
+                .context("Not a setup archive, the manifest is missing")?,
+        )
+        .context("Invalid setup manifest")?;
+
+        if manifest.format > FORMAT_VERSION {
+            bail!(
+                "The setup was exported by a newer version ({}), please update first",
+                manifest.app_version
+            );
+        }
+
+        let configs = self.registry.configs();
+        let mut problems = vec![];
+        let mut pending = vec![];
+        let mut preview = String::new();
+
+        for (name, content) in entries {
+            if name == MANIFEST_FILE {
+                continue;
+            }
+
+            let Some(config) = configs
+                .iter()
+                .find(|config| self.entry_name(config.as_ref()).as_deref() == Some(&name))
+            else {
+                problems.push(format!("{}: unknown config file", name));
+                continue;
+            };
+
+            let current = config.snapshot().await;
+            let plugin_id = name.split('/').nth(1).map(str::to_string);
+            let content = match plugin_id {
+                Some(_) => content,
+                None => match with_portable_settings(&content, &current) {
+                    Ok(content) => content,
+                    Err(error) => {
+                        problems.push(format!("{}: {:#}", name, error));
+                        continue;
+                    }
+                },
+            };
+
+            if let Err(error) = config.validate(&content) {
+                problems.push(format!("{}: {:#}", name, error));
+                continue;
+            }
+
+            let source = keep_secrets(&content, &current, config.secret_keys())?;
+
+            if normalize(&source, &[])? == normalize(&current, &[])? {
+                continue;
+            }
+
+            let old = normalize(&current, config.secret_keys())?;
+            let new = normalize(&source, config.secret_keys())?;
+
+            if old == new {
+                preview.push_str(&format!("{}: credentials changed\n\n", name));
+            } else {
+                preview.push_str(
+                    &TextDiff::from_lines(&old, &new)
+                        .unified_diff()
+                        .context_radius(2)
+                        .header(&format!("current/{}", name), &format!("import/{}", name))
+                        .to_string(),
+                );
+                preview.push('\n');
+            }
+
+            pending.push(PendingFile {
+                config: config.clone(),
+                plugin_id,
+                source,
+            });
+        }
+
+        if !problems.is_empty() {
+            bail!("The setup can not be imported:\n{}", problems.join("\n"));
+        }
+
+        if pending.is_empty() {
+            return Ok("The setup matches your current configuration".to_string());
+        }
+
+        let status = format!(
+            "{} config files will change{}",
+            pending.len(),
+            if manifest.includes_secrets {
+                ", including credentials"
+            } else {
+                ""
+            }
+        );
+
+        self.pending = pending;
+        self.publish(status.clone(), Some(preview))?;
+
+        Ok(status)
+    }
+
+    async fn apply(&mut self) -> anyhow::Result<String> {
+        if self.pending.is_empty() {
+            bail!("No import to apply");
+        }
+
+        let enabled_plugins = self.config.read().await.enabled_plugins.clone();
+
+        for file in std::mem::take(&mut self.pending) {
+            let source = self.store_secrets(&file).await?;
+
+            if file.config.apply(&source).await? {
+                self.registry.clear_issue(file.config.path());
+                info!("Imported {}", file.config.name());
+            }
+        }
+
+        // The root config may enable or disable plugins, which the plugin manager has to act on.
+        let target = self
+            .config
+            .read()
+            .await
+            .enabled_plugins
+            .iter()
+            .cloned()
+            .collect();
+        switch_enabled_plugins(
+            &self.plugin_manager_tx,
+            &self.app_window,
+            &enabled_plugins,
+            target,
+        )
+        .await?;
+
+        Ok("Setup imported".to_string())
+    }
+
+    /// Moves imported credentials into the secret store, so they are not written to the config file even if the plugin
+    /// is not running to do so. Credentials which cannot be stored are kept in the file, for the plugin to move later.
+    async fn store_secrets(&self, file: &PendingFile) -> anyhow::Result<String> {
+        let secret_keys = file.config.secret_keys();
+        let Some(plugin_id) = file.plugin_id.as_ref().filter(|_| !secret_keys.is_empty()) else {
+            return Ok(file.source.clone());
+        };
+
+        let mut table: toml::Table = toml::from_str(&file.source)?;
+
+        for key in secret_keys {
+            let Some(value) = table.get(*key).and_then(|value| value.as_str()) else {
+                continue;
+            };
+
+            if value.is_empty() {
+                continue;
+            }
+
+            match self
+                .secrets
+                .set(&format!("{}.{}", plugin_id, key), value)
+                .await
+            {
+                Ok(()) => {
+                    table.remove(*key);
+                }
+                Err(error) => warn!(
+                    "Failed to store imported {} of {}: {:#}",
+                    key, plugin_id, error
+                ),
+            }
+        }
+
+        Ok(toml::to_string_pretty(&table)?)
+    }
+
+    fn publish(&self, status: String, preview: Option<String>) -> anyhow::Result<()> {
+        let pending = !self.pending.is_empty();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            let setup = handle.global::<Setup>();
+            setup.set_status(status.into());
+            setup.set_import_pending(pending);
+
+            if !pending {
+                setup.set_preview("".into());
+            } else if let Some(preview) = preview {
+                setup.set_preview(preview.into());
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for SetupsTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use async_osc::OscMessage;
+use log::warn;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::interval;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::osc_query::service::OscQueryService;
+use crate::utils::osc::format_arguments;
+use crate::{AppWindow, Simulator, SimulatorParameter, SimulatorReadout};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum SimulatorCommand {
+    Inject(OscMessage),
+    ClearReadouts,
+}
+
+pub struct SimulatorTask {
+    rx: mpsc::Receiver<SimulatorCommand>,
+    osc_tx: broadcast::Sender<OscMessage>,
+    sent_rx: broadcast::Receiver<OscMessage>,
+    app_window: Weak<AppWindow>,
+    osc_query_service: OscQueryService,
+    readouts: BTreeMap<String, String>,
+    dirty: bool,
+}
+
+impl SimulatorTask {
+    pub fn new(
+        rx: mpsc::Receiver<SimulatorCommand>,
+        osc_tx: broadcast::Sender<OscMessage>,
+        sent_rx: broadcast::Receiver<OscMessage>,
+        app_window: Weak<AppWindow>,
+        osc_query_service: OscQueryService,
+    ) -> Self {
+        Self {
+            rx,
+            osc_tx,
+            sent_rx,
+            app_window,
+            osc_query_service,
+            readouts: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+
+    fn publish_parameters(&self) -> anyhow::Result<()> {
+        let parameters: Vec<SimulatorParameter> = self
+            .osc_query_service
+            .endpoints()
+            .into_iter()
+            .map(|endpoint| SimulatorParameter {
+                address: endpoint.full_path.clone().into(),
+                name: endpoint
+                    .full_path
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .into(),
+                osc_type: endpoint.osc_type.clone().into(),
+                description: endpoint.description.clone().into(),
+            })
+            .collect();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            handle
+                .global::<Simulator>()
+                .set_parameters(ModelRc::new(VecModel::from(parameters)));
+        })?;
+
+        Ok(())
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        let mut refresh = interval(REFRESH_INTERVAL);
+        let mut endpoints_rx = self.osc_query_service.subscribe();
+        self.publish_parameters()?;
+
+        loop {
+            select! {
+                command = self.rx.recv() => match command {
+                    Some(SimulatorCommand::Inject(message)) => {
+                        let _ = self.osc_tx.send(message);
+                    }
+                    Some(SimulatorCommand::ClearReadouts) => {
+                        self.readouts.clear();
+                        self.dirty = true;
+                    }
+                    None => break,
+                },
+                message = self.sent_rx.recv() => match message {
+                    Ok(message) => {
+                        self.readouts.insert(message.addr, format_arguments(&message.args));
+                        self.dirty = true;
+                    }
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Simulator lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                    }
+                },
+                Ok(()) = endpoints_rx.changed() => {
+                    self.publish_parameters()?;
+                }
+                _ = refresh.tick() => {
+                    if self.dirty {
+                        self.refresh_readouts()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn refresh_readouts(&mut self) -> anyhow::Result<()> {
+        let readouts: Vec<SimulatorReadout> = self
+            .readouts
+            .iter()
+            .map(|(address, value)| SimulatorReadout {
+                address: address.clone().into(),
+                value: value.clone().into(),
+            })
+            .collect();
+
+        self.app_window.upgrade_in_event_loop(move |handle| {
+            handle
+                .global::<Simulator>()
+                .set_readouts(ModelRc::new(VecModel::from(readouts)));
+        })?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for SimulatorTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use ksni::menu::{CheckmarkItem, StandardItem, SubMenu};
+use ksni::{Handle, Icon, MenuItem, ToolTip, TrayService};
+use tokio::sync::mpsc;
+
+use super::{MenuEntry, TrayIcon, TrayState, TITLE};
+use crate::tasks::orchestrate::AppEvent;
+
+const DARK_INACTIVE_ICON: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/linux-dark-inactive-icon"));
+
+const DARK_ACTIVE_ICON: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/linux-dark-active-icon"));
+
+const DARK_ERROR_ICON: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/linux-dark-error-icon"));
+
+const LIGHT_INACTIVE_ICON: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/linux-light-inactive-icon"));
+
+const LIGHT_ACTIVE_ICON: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/linux-light-active-icon"));
+
+const LIGHT_ERROR_ICON: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/linux-light-error-icon"));
+
+fn get_icon(icon: TrayIcon, dark_mode: bool) -> Icon {
+    let data = match (icon, dark_mode) {
+        (TrayIcon::Inactive, true) => DARK_INACTIVE_ICON,
+        (TrayIcon::Active, true) => DARK_ACTIVE_ICON,
+        (TrayIcon::Error, true) => DARK_ERROR_ICON,
+        (TrayIcon::Inactive, false) => LIGHT_INACTIVE_ICON,
+        (TrayIcon::Active, false) => LIGHT_ACTIVE_ICON,
+        (TrayIcon::Error, false) => LIGHT_ERROR_ICON,
+    };
+
+    Icon {
+        width: 64,
+        height: 64,
+        data: data.to_vec(),
+    }
+}
+
+struct TrayModel {
+    state: TrayState,
+    app_event_tx: mpsc::Sender<AppEvent>,
+}
+
+impl ksni::Tray for TrayModel {
+    fn id(&self) -> String {
+        TITLE.to_string()
+    }
+
+    fn title(&self) -> String {
+        TITLE.to_string()
+    }
+
+    fn icon_pixmap(&self) -> Vec<Icon> {
+        vec![get_icon(self.state.icon(), self.state.dark_mode)]
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: TITLE.to_string(),
+            // The description is interpreted as markup, where plain line breaks are not preserved.
+            description: self.state.tooltip().replace('\n', "<br>"),
+            ..Default::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        self.state.menu().into_iter().map(menu_item).collect()
+    }
+}
+
+fn menu_item(entry: MenuEntry) -> MenuItem<TrayModel> {
+    match entry {
+        MenuEntry::Label(label) => StandardItem {
+            label,
+            enabled: false,
+            ..Default::default()
+        }
+        .into(),
+        MenuEntry::Item { label, action } => StandardItem {
+            label,
+            activate: Box::new(move |model: &mut TrayModel| action.dispatch(&model.app_event_tx)),
+            ..Default::default()
+        }
+        .into(),
+        MenuEntry::Check {
+            label,
+            checked,
+            action,
+        } => CheckmarkItem {
+            label,
+            checked,
+            activate: Box::new(move |model: &mut TrayModel| action.dispatch(&model.app_event_tx)),
+            ..Default::default()
+        }
+        .into(),
+        MenuEntry::Submenu { label, entries } => SubMenu {
+            label,
+            submenu: entries.into_iter().map(menu_item).collect(),
+            ..Default::default()
+        }
+        .into(),
+        MenuEntry::Separator => MenuItem::Separator,
+    }
+}
+
+pub struct Tray {
+    handle: Handle<TrayModel>,
+}
+
+impl Tray {
+    pub fn new(state: &TrayState, app_event_tx: mpsc::Sender<AppEvent>) -> anyhow::Result<Self> {
+        let service = TrayService::new(TrayModel {
+            state: state.clone(),
+            app_event_tx,
+        });
+        let handle = service.handle();
+        service.spawn();
+
+        Ok(Self { handle })
+    }
+
+    pub fn update(&mut self, state: &TrayState) -> anyhow::Result<()> {
+        self.handle.update(|model| model.state = state.clone());
+
+        Ok(())
+    }
+}
+
+impl Drop for Tray {
+    /// Stops the tray service, so that restarting the background tasks does not leave the previous icon behind.
+    fn drop(&mut self) {
+        self.handle.shutdown();
+    }
+}
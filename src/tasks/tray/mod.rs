@@ -0,0 +1,379 @@
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "windows")]
+mod windows;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::errors::CancelledByShutdown;
+use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
+
+use crate::config::RootConfig;
+use crate::plugins::PluginTrayAction;
+use crate::tasks::orchestrate::AppEvent;
+use crate::utils::config::ConfigHandle;
+
+#[cfg(target_os = "linux")]
+use linux::Tray;
+#[cfg(target_os = "windows")]
+use windows::Tray;
+
+const TITLE: &str = "VRC OSC Manager";
+
+pub enum TrayProperty {
+    Running(bool),
+    VrchatRunning(bool),
+    DarkMode(bool),
+    PluginFailed(String, bool),
+    PluginStarted(String, Vec<PluginTrayAction>),
+    PluginStopped(String),
+    /// Updated actions of a plugin, ignored unless the plugin is running.
+    PluginActions(String, Vec<PluginTrayAction>),
+    /// Names of the saved profiles, sent whenever one is saved or deleted.
+    Profiles(Vec<String>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TrayIcon {
+    Inactive,
+    Active,
+    Error,
+}
+
+#[derive(Clone, PartialEq)]
+enum TrayAction {
+    OpenWindow,
+    TogglePlugin(String),
+    InvokePluginAction(String, String),
+    StartPlugins,
+    StopPlugins,
+    SwitchProfile(String),
+    OpenLogsFolder,
+    OpenConfigFolder,
+    Quit,
+}
+
+impl TrayAction {
+    fn app_event(&self) -> AppEvent {
+        match self {
+            TrayAction::OpenWindow => AppEvent::AppWindowRequested,
+            TrayAction::TogglePlugin(plugin_id) => {
+                AppEvent::PluginToggleRequested(plugin_id.clone())
+            }
+            TrayAction::InvokePluginAction(plugin_id, action_id) => {
+                AppEvent::PluginActionRequested {
+                    plugin_id: plugin_id.clone(),
+                    action_id: action_id.clone(),
+                }
+            }
+            TrayAction::StartPlugins => AppEvent::StartPluginsRequested,
+            TrayAction::StopPlugins => AppEvent::StopPluginsRequested,
+            TrayAction::SwitchProfile(name) => AppEvent::ProfileRequested(name.clone()),
+            TrayAction::OpenLogsFolder => AppEvent::LogsFolderRequested,
+            TrayAction::OpenConfigFolder => AppEvent::ConfigFolderRequested,
+            TrayAction::Quit => AppEvent::ShutdownRequested,
+        }
+    }
+
+    fn dispatch(&self, app_event_tx: &mpsc::Sender<AppEvent>) {
+        app_event_tx.blocking_send(self.app_event()).unwrap();
+    }
+}
+
+/// Platform independent description of a tray menu entry.
+enum MenuEntry {
+    Label(String),
+    Item {
+        label: String,
+        action: TrayAction,
+    },
+    Check {
+        label: String,
+        checked: bool,
+        action: TrayAction,
+    },
+    Submenu {
+        label: String,
+        entries: Vec<MenuEntry>,
+    },
+    Separator,
+}
+
+#[derive(Clone)]
+struct TrayState {
+    running: bool,
+    vrchat_running: bool,
+    dark_mode: bool,
+    plugins: Vec<(String, String)>,
+    enabled_plugins: HashSet<String>,
+    failed_plugins: BTreeSet<String>,
+    plugin_actions: BTreeMap<String, Vec<PluginTrayAction>>,
+    profiles: Vec<String>,
+}
+
+impl TrayState {
+    fn icon(&self) -> TrayIcon {
+        if !self.failed_plugins.is_empty() {
+            TrayIcon::Error
+        } else if self.running {
+            TrayIcon::Active
+        } else {
+            TrayIcon::Inactive
+        }
+    }
+
+    fn status(&self) -> String {
+        if self.vrchat_running {
+            "VRChat is running".to_string()
+        } else {
+            "VRChat is not running".to_string()
+        }
+    }
+
+    fn tooltip(&self) -> String {
+        let mut lines = vec![
+            self.status(),
+            if self.running {
+                "Plugins are running".to_string()
+            } else {
+                "Plugins are stopped".to_string()
+            },
+        ];
+
+        let failed: Vec<&str> = self
+            .plugins
+            .iter()
+            .filter(|(plugin_id, _)| self.failed_plugins.contains(plugin_id))
+            .map(|(_, title)| title.as_str())
+            .collect();
+
+        if !failed.is_empty() {
+            lines.push(format!("Failed plugins: {}", failed.join(", ")));
+        }
+
+        lines.join("\n")
+    }
+
+    fn menu(&self) -> Vec<MenuEntry> {
+        let mut entries = vec![
+            MenuEntry::Label(self.status()),
+            MenuEntry::Item {
+                label: "Open VRC OSC Manager".to_string(),
+                action: TrayAction::OpenWindow,
+            },
+            MenuEntry::Separator,
+        ];
+
+        for (plugin_id, title) in &self.plugins {
+            entries.push(MenuEntry::Check {
+                label: if self.failed_plugins.contains(plugin_id) {
+                    format!("{} (failed)", title)
+                } else {
+                    title.clone()
+                },
+                checked: self.enabled_plugins.contains(plugin_id),
+                action: TrayAction::TogglePlugin(plugin_id.clone()),
+            });
+        }
+
+        let submenus: Vec<MenuEntry> = self
+            .plugins
+            .iter()
+            .filter_map(|(plugin_id, title)| {
+                let actions = self.plugin_actions.get(plugin_id)?;
+
+                if actions.is_empty() {
+                    return None;
+                }
+
+                Some(MenuEntry::Submenu {
+                    label: title.clone(),
+                    entries: actions
+                        .iter()
+                        .map(|action| Self::plugin_action_entry(plugin_id, action))
+                        .collect(),
+                })
+            })
+            .collect();
+
+        if !submenus.is_empty() {
+            entries.push(MenuEntry::Separator);
+            entries.extend(submenus);
+        }
+
+        entries.extend([
+            MenuEntry::Separator,
+            MenuEntry::Item {
+                label: "Start plugins now".to_string(),
+                action: TrayAction::StartPlugins,
+            },
+            MenuEntry::Item {
+                label: "Stop plugins".to_string(),
+                action: TrayAction::StopPlugins,
+            },
+            MenuEntry::Separator,
+        ]);
+
+        if !self.profiles.is_empty() {
+            for profile in &self.profiles {
+                entries.push(MenuEntry::Item {
+                    label: format!("Profile: {}", profile),
+                    action: TrayAction::SwitchProfile(profile.clone()),
+                });
+            }
+
+            entries.push(MenuEntry::Separator);
+        }
+
+        entries.extend([
+            MenuEntry::Item {
+                label: "Open logs folder".to_string(),
+                action: TrayAction::OpenLogsFolder,
+            },
+            MenuEntry::Item {
+                label: "Open config folder".to_string(),
+                action: TrayAction::OpenConfigFolder,
+            },
+            MenuEntry::Separator,
+            MenuEntry::Item {
+                label: "Quit".to_string(),
+                action: TrayAction::Quit,
+            },
+        ]);
+
+        entries
+    }
+
+    fn plugin_action_entry(plugin_id: &str, action: &PluginTrayAction) -> MenuEntry {
+        let tray_action =
+            TrayAction::InvokePluginAction(plugin_id.to_string(), action.id.to_string());
+
+        match action.checked {
+            _ if !action.enabled => MenuEntry::Label(action.label.clone()),
+            Some(checked) => MenuEntry::Check {
+                label: action.label.clone(),
+                checked,
+                action: tray_action,
+            },
+            None => MenuEntry::Item {
+                label: action.label.clone(),
+                action: tray_action,
+            },
+        }
+    }
+}
+
+pub struct TrayTask {
+    rx: mpsc::Receiver<TrayProperty>,
+    app_event_tx: mpsc::Sender<AppEvent>,
+    config: ConfigHandle<RootConfig>,
+    state: TrayState,
+}
+
+impl TrayTask {
+    pub fn new(
+        rx: mpsc::Receiver<TrayProperty>,
+        app_event_tx: mpsc::Sender<AppEvent>,
+        config: ConfigHandle<RootConfig>,
+        dark_mode: bool,
+        plugins: Vec<(String, String)>,
+        profiles: Vec<String>,
+    ) -> Self {
+        Self {
+            rx,
+            app_event_tx,
+            config,
+            state: TrayState {
+                running: false,
+                vrchat_running: false,
+                dark_mode,
+                plugins,
+                enabled_plugins: HashSet::new(),
+                failed_plugins: BTreeSet::new(),
+                plugin_actions: BTreeMap::new(),
+                profiles,
+            },
+        }
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
+        let mut config_rx = self.config.subscribe();
+        self.state.enabled_plugins = config_rx.borrow_and_update().enabled_plugins.clone();
+
+        let mut tray = Tray::new(&self.state, self.app_event_tx.clone())?;
+
+        loop {
+            select! {
+                property = self.rx.recv() => match property {
+                    Some(property) => self.apply_property(property),
+                    None => break,
+                },
+                result = config_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    let enabled_plugins = config_rx.borrow_and_update().enabled_plugins.clone();
+
+                    if enabled_plugins == self.state.enabled_plugins {
+                        continue;
+                    }
+
+                    self.state.enabled_plugins = enabled_plugins;
+                }
+            }
+
+            tray.update(&self.state)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_property(&mut self, property: TrayProperty) {
+        match property {
+            TrayProperty::Running(running) => {
+                self.state.running = running;
+            }
+            TrayProperty::VrchatRunning(vrchat_running) => {
+                self.state.vrchat_running = vrchat_running;
+            }
+            TrayProperty::DarkMode(dark_mode) => {
+                self.state.dark_mode = dark_mode;
+            }
+            TrayProperty::PluginFailed(plugin_id, true) => {
+                self.state.failed_plugins.insert(plugin_id);
+            }
+            TrayProperty::PluginFailed(plugin_id, false) => {
+                self.state.failed_plugins.remove(&plugin_id);
+            }
+            TrayProperty::PluginStarted(plugin_id, actions) => {
+                self.state.plugin_actions.insert(plugin_id, actions);
+            }
+            TrayProperty::PluginStopped(plugin_id) => {
+                self.state.plugin_actions.remove(&plugin_id);
+            }
+            TrayProperty::PluginActions(plugin_id, actions) => {
+                if let Some(current) = self.state.plugin_actions.get_mut(&plugin_id) {
+                    *current = actions;
+                }
+            }
+            TrayProperty::Profiles(profiles) => {
+                self.state.profiles = profiles;
+            }
+        }
+    }
+}
+
+impl IntoSubsystem<anyhow::Error> for TrayTask {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+        match self.main_loop().cancel_on_shutdown(subsys).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
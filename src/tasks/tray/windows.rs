@@ -0,0 +1,193 @@
+use tokio::sync::mpsc;
+use tray_item::{IconSource, TrayItem};
+
+use super::{MenuEntry, TrayAction, TrayIcon, TrayState, TITLE};
+use crate::tasks::orchestrate::AppEvent;
+
+/// Windows limits tooltips to 127 characters.
+const MAX_TOOLTIP_LENGTH: usize = 127;
+
+fn get_icon(icon: TrayIcon, dark_mode: bool) -> IconSource {
+    IconSource::Resource(match (icon, dark_mode) {
+        (TrayIcon::Inactive, true) => "dark_inactive_icon",
+        (TrayIcon::Active, true) => "dark_active_icon",
+        (TrayIcon::Error, true) => "dark_error_icon",
+        (TrayIcon::Inactive, false) => "light_inactive_icon",
+        (TrayIcon::Active, false) => "light_active_icon",
+        (TrayIcon::Error, false) => "light_error_icon",
+    })
+}
+
+/// Windows menus have no check marks, so checked entries are prefixed instead.
+fn check_label(label: &str, checked: bool) -> String {
+    if checked {
+        format!("✓ {}", label)
+    } else {
+        format!("    {}", label)
+    }
+}
+
+/// Windows menus have no submenus here, so their entries are inlined with the submenu label as prefix.
+fn flatten(entries: Vec<MenuEntry>) -> Vec<MenuEntry> {
+    let mut flattened = vec![];
+
+    for entry in entries {
+        match entry {
+            MenuEntry::Submenu { label, entries } => {
+                for entry in flatten(entries) {
+                    flattened.push(match entry {
+                        MenuEntry::Label(item) => MenuEntry::Label(format!("{}: {}", label, item)),
+                        MenuEntry::Item {
+                            label: item,
+                            action,
+                        } => MenuEntry::Item {
+                            label: format!("{}: {}", label, item),
+                            action,
+                        },
+                        MenuEntry::Check {
+                            label: item,
+                            checked,
+                            action,
+                        } => MenuEntry::Check {
+                            label: format!("{}: {}", label, item),
+                            checked,
+                            action,
+                        },
+                        entry => entry,
+                    });
+                }
+            }
+            entry => flattened.push(entry),
+        }
+    }
+
+    flattened
+}
+
+/// Describes the kind and action of each entry, as items cannot be removed or change their callback once added.
+fn layout(entries: &[MenuEntry]) -> Vec<(u8, Option<TrayAction>)> {
+    entries
+        .iter()
+        .map(|entry| match entry {
+            MenuEntry::Label(_) => (0, None),
+            MenuEntry::Item { action, .. } | MenuEntry::Check { action, .. } => {
+                (1, Some(action.clone()))
+            }
+            MenuEntry::Separator | MenuEntry::Submenu { .. } => (2, None),
+        })
+        .collect()
+}
+
+fn tooltip(state: &TrayState) -> String {
+    let tooltip = format!("{}\n{}", TITLE, state.tooltip());
+    tooltip.chars().take(MAX_TOOLTIP_LENGTH).collect()
+}
+
+pub struct Tray {
+    tray: TrayItem,
+    app_event_tx: mpsc::Sender<AppEvent>,
+    layout: Vec<(u8, Option<TrayAction>)>,
+    /// Menu item IDs and current labels, in the order of [`TrayState::menu`].
+    items: Vec<Option<(u32, String)>>,
+    icon: TrayIcon,
+    dark_mode: bool,
+}
+
+impl Tray {
+    pub fn new(state: &TrayState, app_event_tx: mpsc::Sender<AppEvent>) -> anyhow::Result<Self> {
+        let mut tray = TrayItem::new(TITLE, get_icon(state.icon(), state.dark_mode))?;
+        let menu = flatten(state.menu());
+        let layout = layout(&menu);
+        let mut items = vec![];
+
+        for entry in menu {
+            let inner = tray.inner_mut();
+
+            items.push(match entry {
+                MenuEntry::Label(label) => Some((inner.add_label_with_id(&label)?, label)),
+                MenuEntry::Item { label, action } => {
+                    let app_event_tx = app_event_tx.clone();
+                    let id = inner
+                        .add_menu_item_with_id(&label, move || action.dispatch(&app_event_tx))?;
+                    Some((id, label))
+                }
+                MenuEntry::Check {
+                    label,
+                    checked,
+                    action,
+                } => {
+                    let label = check_label(&label, checked);
+                    let app_event_tx = app_event_tx.clone();
+                    let id = inner
+                        .add_menu_item_with_id(&label, move || action.dispatch(&app_event_tx))?;
+                    Some((id, label))
+                }
+                MenuEntry::Separator | MenuEntry::Submenu { .. } => {
+                    inner.add_separator()?;
+                    None
+                }
+            });
+        }
+
+        tray.inner_mut().set_tooltip(&tooltip(state))?;
+
+        Ok(Self {
+            tray,
+            app_event_tx,
+            layout,
+            items,
+            icon: state.icon(),
+            dark_mode: state.dark_mode,
+        })
+    }
+
+    pub fn update(&mut self, state: &TrayState) -> anyhow::Result<()> {
+        let menu = flatten(state.menu());
+
+        if layout(&menu) != self.layout {
+            // Drop the current icon before adding the new one, so that both never show up at once.
+            let app_event_tx = self.app_event_tx.clone();
+            self.tray.inner_mut().shutdown()?;
+            *self = Self::new(state, app_event_tx)?;
+            return Ok(());
+        }
+
+        if state.icon() != self.icon || state.dark_mode != self.dark_mode {
+            self.icon = state.icon();
+            self.dark_mode = state.dark_mode;
+            self.tray.set_icon(get_icon(self.icon, self.dark_mode))?;
+        }
+
+        for (entry, item) in menu.into_iter().zip(self.items.iter_mut()) {
+            let Some((id, current)) = item else {
+                continue;
+            };
+
+            let inner = self.tray.inner_mut();
+
+            match entry {
+                MenuEntry::Label(label) if label != *current => {
+                    inner.set_label(&label, *id)?;
+                    *current = label;
+                }
+                MenuEntry::Item { label, .. } if label != *current => {
+                    inner.set_menu_item_label(&label, *id)?;
+                    *current = label;
+                }
+                MenuEntry::Check { label, checked, .. } => {
+                    let label = check_label(&label, checked);
+
+                    if label != *current {
+                        inner.set_menu_item_label(&label, *id)?;
+                        *current = label;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.tray.inner_mut().set_tooltip(&tooltip(state))?;
+
+        Ok(())
+    }
+}
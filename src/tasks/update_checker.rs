@@ -1,124 +1,477 @@
-use crate::config::RootConfig;
+use crate::config::{NotificationCategory, RootConfig, UpdateChannel};
+use crate::platform::{get_platform, Platform};
+use crate::tasks::notifications::{Notification, NotificationAction};
 use crate::tasks::orchestrate::AppEvent;
 use crate::utils::config::ConfigHandle;
-use log::{debug, warn};
+use crate::utils::self_update::{self, ASSET_NAME, CHECKSUMS_ASSET_NAME};
+use anyhow::{anyhow, bail, Context};
+use log::{debug, info, warn};
 use semver::Version;
 use serde::Deserialize;
+use std::env;
+use std::path::PathBuf;
+use std::process;
 use std::time::Duration;
+use tokio::fs;
+use tokio::select;
 use tokio::sync::mpsc;
-use tokio::time::sleep;
+use tokio::task::spawn_blocking;
+use tokio::time::{sleep_until, Instant};
 use tokio_graceful_shutdown::errors::CancelledByShutdown;
 use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
 
-const LATEST_RELEASE_URL: &str =
-    "https://api.github.com/repos/DASPRiD/vrc-osc-manager/releases/latest";
 const USER_AGENT: &str = concat!("vrc-osc-manager/", env!("CARGO_PKG_VERSION"));
 const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Endpoint listing the official releases, the only one updates are installed from.
+const RELEASES_ENDPOINT: &str = "https://api.github.com/repos/DASPRiD/vrc-osc-manager/releases";
+/// Environment variable pointing the update check to another GitHub compatible endpoint, for testing.
+const ENDPOINT_VARIABLE: &str = "VRC_OSC_MANAGER_UPDATE_ENDPOINT";
+/// Set when running from an AppImage or Flatpak, whose executable cannot be replaced in place.
+const PACKAGE_VARIABLES: &[&str] = &["APPIMAGE", "FLATPAK_ID"];
+
+pub enum UpdateCommand {
+    /// Checks right away, even if periodic checks are turned off.
+    CheckNow,
+    /// Downloads and installs the update found by the last check.
+    Install,
+    /// Starts the installed update and shuts down the running instance.
+    Restart,
+}
+
+pub enum UpdateStatus {
+    Checking,
+    UpToDate,
+    Available {
+        version: String,
+        url: String,
+        notes: String,
+        installable: bool,
+    },
+    Failed(String),
+    Downloading(u8),
+    Installed,
+    InstallFailed(String),
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
 
 #[derive(Deserialize)]
 struct GithubRelease {
     tag_name: String,
     html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    assets: Vec<GithubAsset>,
+}
+
+impl GithubRelease {
+    fn asset_url(&self, name: &str) -> Option<String> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.clone())
+    }
+}
+
+#[derive(Clone)]
+struct PendingUpdate {
+    version: Version,
+    asset_url: String,
+    checksums_url: String,
+}
+
+/// Turns the markdown of a release body into plain text, as the UI cannot render markdown.
+fn release_notes(body: &str) -> String {
+    body.lines()
+        .map(|line| {
+            let line = line.trim_end().replace("**", "").replace('`', "");
+            let trimmed = line.trim_start();
+
+            if let Some(heading) = trimmed.strip_prefix('#') {
+                heading.trim_start_matches('#').trim().to_string()
+            } else if let Some(item) = trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                let indent = (line.len() - trimmed.len()) / 2;
+                format!("{}• {}", "  ".repeat(indent), item)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
 }
 
 pub struct UpdateCheckerTask {
+    rx: mpsc::Receiver<UpdateCommand>,
     app_event_tx: mpsc::Sender<AppEvent>,
+    notifications_tx: mpsc::Sender<Notification>,
     config: ConfigHandle<RootConfig>,
     current_version: Version,
+    last_notified: Option<Version>,
+    downloads_dir: PathBuf,
+    exe_path: Option<PathBuf>,
+    endpoint: String,
+    pending: Option<PendingUpdate>,
+    installed: bool,
 }
 
 impl UpdateCheckerTask {
     pub fn new(
+        rx: mpsc::Receiver<UpdateCommand>,
         app_event_tx: mpsc::Sender<AppEvent>,
+        notifications_tx: mpsc::Sender<Notification>,
         config: ConfigHandle<RootConfig>,
+        downloads_dir: PathBuf,
     ) -> anyhow::Result<Self> {
         let current_version = Version::parse(env!("CARGO_PKG_VERSION"))?;
 
+        // Resolved once, as the path of the running executable changes once an update moved it aside.
+        let exe_path = match env::current_exe().and_then(|path| path.canonicalize()) {
+            // The executable lives inside a read-only image or sandbox, which has to be updated as a whole.
+            Ok(_)
+                if PACKAGE_VARIABLES
+                    .iter()
+                    .any(|name| env::var_os(name).is_some()) =>
+            {
+                info!(
+                    "Running from an AppImage or Flatpak, updates have to be installed through it"
+                );
+                None
+            }
+            Ok(path) => Some(path),
+            Err(error) => {
+                warn!(
+                    "Executable path not available, updates cannot be installed: {}",
+                    error
+                );
+                None
+            }
+        };
+
+        // Not part of the config, so that sharing or importing a setup cannot change where updates come from.
+        let endpoint = env::var(ENDPOINT_VARIABLE)
+            .ok()
+            .filter(|endpoint| !endpoint.is_empty())
+            .unwrap_or_else(|| RELEASES_ENDPOINT.to_string());
+
         Ok(Self {
+            rx,
             app_event_tx,
+            notifications_tx,
             config,
             current_version,
+            last_notified: None,
+            downloads_dir,
+            exe_path,
+            endpoint,
+            pending: None,
+            installed: false,
         })
     }
 
-    async fn check_once(
+    /// Fetches the releases and returns the newest one on the configured channel.
+    async fn latest_release(
         &self,
         client: &reqwest::Client,
-        last_notified: &mut Option<Version>,
-    ) -> anyhow::Result<()> {
-        if !self.config.read().await.check_for_updates {
-            debug!("Update check disabled by user, skipping");
-            return Ok(());
-        }
+    ) -> anyhow::Result<(Version, GithubRelease)> {
+        let updates = self.config.read().await.updates.clone();
 
-        let release = client
-            .get(LATEST_RELEASE_URL)
+        let releases = client
+            .get(&self.endpoint)
             .header("Accept", "application/vnd.github+json")
             .send()
             .await?
             .error_for_status()?
-            .json::<GithubRelease>()
+            .json::<Vec<GithubRelease>>()
             .await?;
 
-        let tag = release.tag_name.trim_start_matches('v');
-        let latest = Version::parse(tag)?;
+        releases
+            .into_iter()
+            .filter(|release| !release.draft)
+            .filter(|release| updates.channel == UpdateChannel::PreRelease || !release.prerelease)
+            .filter_map(|release| {
+                let version = Version::parse(release.tag_name.trim_start_matches('v')).ok()?;
 
-        if latest <= self.current_version {
-            debug!("Already on latest version: {}", self.current_version);
+                // Pre-release versions can also be tagged without being marked as such.
+                if updates.channel == UpdateChannel::Stable && !version.pre.is_empty() {
+                    return None;
+                }
+
+                Some((version, release))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .ok_or_else(|| {
+                anyhow!(
+                    "No release found on the {} channel",
+                    updates.channel.label()
+                )
+            })
+    }
+
+    async fn check(&mut self, client: &reqwest::Client, forced: bool) -> anyhow::Result<()> {
+        if !forced && !self.config.read().await.check_for_updates {
+            debug!("Update check disabled by user, skipping");
+            return Ok(());
+        }
+
+        if self.installed {
+            debug!("Update already installed, waiting for restart");
+            return Ok(());
+        }
+
+        self.app_event_tx
+            .send(AppEvent::UpdateStatus(UpdateStatus::Checking))
+            .await?;
+
+        let (latest, release) = match self.latest_release(client).await {
+            Ok(latest) => latest,
+            Err(error) => {
+                warn!("Update check failed: {:#}", error);
+                self.app_event_tx
+                    .send(AppEvent::UpdateStatus(UpdateStatus::Failed(format!(
+                        "{:#}",
+                        error
+                    ))))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let skipped = self.config.read().await.updates.skipped_version.clone();
+
+        if latest <= self.current_version || skipped.as_deref() == Some(&latest.to_string()) {
+            debug!(
+                "No update to offer, current version {}, latest version {}",
+                self.current_version, latest
+            );
+            self.app_event_tx
+                .send(AppEvent::UpdateStatus(UpdateStatus::UpToDate))
+                .await?;
             return Ok(());
         }
 
         debug!("New version available: {}", latest);
 
+        // Checksums only prove that a download is intact, so the binary itself has to come from the official releases.
+        let official = self.endpoint == RELEASES_ENDPOINT;
+
+        if !official {
+            info!("Releases are listed by a custom endpoint, updates cannot be installed in place");
+        }
+
+        self.pending = match (
+            release.asset_url(ASSET_NAME),
+            release.asset_url(CHECKSUMS_ASSET_NAME),
+        ) {
+            (Some(asset_url), Some(checksums_url)) if official => Some(PendingUpdate {
+                version: latest.clone(),
+                asset_url,
+                checksums_url,
+            }),
+            _ => None,
+        };
+
         self.app_event_tx
-            .send(AppEvent::UpdateAvailable {
+            .send(AppEvent::UpdateStatus(UpdateStatus::Available {
                 version: latest.to_string(),
                 url: release.html_url.clone(),
-            })
+                notes: release_notes(release.body.as_deref().unwrap_or_default()),
+                installable: self.pending.is_some() && self.exe_path.is_some(),
+            }))
             .await?;
 
-        let should_notify = last_notified.as_ref().is_none_or(|seen| *seen < latest);
+        let should_notify = self
+            .last_notified
+            .as_ref()
+            .is_none_or(|seen| *seen < latest);
 
         if should_notify {
-            notify_update(&latest, &release.html_url);
-            *last_notified = Some(latest);
+            self.notifications_tx
+                .send(
+                    Notification::new(
+                        NotificationCategory::Updates,
+                        "Update available",
+                        format!("Version {} is available.", latest),
+                    )
+                    .with_action(NotificationAction::OpenReleasePage(release.html_url)),
+                )
+                .await?;
+            self.last_notified = Some(latest);
         }
 
         Ok(())
     }
 
-    async fn main_loop(&self) -> anyhow::Result<()> {
+    async fn install(&mut self, download_client: &reqwest::Client) -> anyhow::Result<()> {
+        let Some(update) = self.pending.clone() else {
+            return Ok(());
+        };
+
+        match self.download_and_install(download_client, &update).await {
+            Ok(()) => {
+                self.installed = true;
+                self.app_event_tx
+                    .send(AppEvent::UpdateStatus(UpdateStatus::Installed))
+                    .await?;
+            }
+            Err(error) => {
+                warn!("Failed to install update {}: {:#}", update.version, error);
+                self.app_event_tx
+                    .send(AppEvent::UpdateStatus(UpdateStatus::InstallFailed(
+                        format!("{:#}", error),
+                    )))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn download_and_install(
+        &self,
+        download_client: &reqwest::Client,
+        update: &PendingUpdate,
+    ) -> anyhow::Result<()> {
+        let exe_path = self
+            .exe_path
+            .clone()
+            .context("Executable path not available")?;
+
+        let checksums = download_client
+            .get(&update.checksums_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let expected = self_update::find_checksum(&checksums, ASSET_NAME)
+            .with_context(|| format!("No checksum published for {}", ASSET_NAME))?;
+
+        let download_path = self
+            .downloads_dir
+            .join(format!("{}-{}.part", update.version, ASSET_NAME));
+        let app_event_tx = self.app_event_tx.clone();
+        let mut last_percent = None;
+
+        self_update::download(
+            download_client,
+            &update.asset_url,
+            &download_path,
+            |downloaded, total| {
+                let Some(total) = total.filter(|total| *total > 0) else {
+                    return;
+                };
+                let percent = (downloaded * 100 / total).min(100) as u8;
+
+                if last_percent != Some(percent) {
+                    last_percent = Some(percent);
+                    // Progress is best effort and must not hold up the download.
+                    let _ = app_event_tx
+                        .try_send(AppEvent::UpdateStatus(UpdateStatus::Downloading(percent)));
+                }
+            },
+        )
+        .await?;
+
+        let actual = self_update::sha256(&download_path).await?;
+
+        if actual != expected {
+            // Start over on the next attempt instead of resuming a corrupted download.
+            fs::remove_file(&download_path).await?;
+            bail!(
+                "Checksum mismatch for {}, expected {} but got {}",
+                ASSET_NAME,
+                expected,
+                actual
+            );
+        }
+
+        {
+            let download_path = download_path.clone();
+            spawn_blocking(move || self_update::install(&exe_path, &download_path)).await??;
+        }
+
+        fs::remove_file(&download_path).await?;
+
+        Ok(())
+    }
+
+    async fn restart(&self) -> anyhow::Result<()> {
+        let Some(exe_path) = self.exe_path.as_ref().filter(|_| self.installed) else {
+            return Ok(());
+        };
+
+        info!("Restarting into the installed update");
+
+        // A new process would be stopped together with this one, so leave starting the update to the service manager.
+        if get_platform().runs_as_service() {
+            self_update::request_restart_on_exit();
+            self.app_event_tx.send(AppEvent::ShutdownRequested).await?;
+            return Ok(());
+        }
+
+        if let Err(error) = process::Command::new(exe_path).arg("--takeover").spawn() {
+            self.app_event_tx
+                .send(AppEvent::UpdateStatus(UpdateStatus::InstallFailed(
+                    format!("Failed to restart: {}", error),
+                )))
+                .await?;
+            return Ok(());
+        }
+
+        self.app_event_tx.send(AppEvent::ShutdownRequested).await?;
+
+        Ok(())
+    }
+
+    async fn main_loop(&mut self) -> anyhow::Result<()> {
         let client = reqwest::Client::builder()
             .user_agent(USER_AGENT)
             .timeout(REQUEST_TIMEOUT)
             .build()?;
-        let mut last_notified: Option<Version> = None;
+        // Downloads can take a while, so only stalls are treated as failures.
+        let download_client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(REQUEST_TIMEOUT)
+            .read_timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let mut next_check = Instant::now();
 
         loop {
-            if let Err(error) = self.check_once(&client, &mut last_notified).await {
-                warn!("Update check failed: {}", error);
+            select! {
+                _ = sleep_until(next_check) => {
+                    self.check(&client, false).await?;
+                    next_check = Instant::now() + CHECK_INTERVAL;
+                }
+                command = self.rx.recv() => match command {
+                    Some(UpdateCommand::CheckNow) => self.check(&client, true).await?,
+                    Some(UpdateCommand::Install) => self.install(&download_client).await?,
+                    Some(UpdateCommand::Restart) => self.restart().await?,
+                    None => break,
+                },
             }
-
-            sleep(CHECK_INTERVAL).await;
         }
-    }
-}
-
-fn notify_update(version: &Version, url: &str) {
-    let result = notify_rust::Notification::new()
-        .appname("VRC OSC Manager")
-        .summary("VRC OSC Manager update available")
-        .body(&format!("Version {version} is available.\n{url}"))
-        .show();
 
-    if let Err(error) = result {
-        warn!("Failed to show update notification: {}", error);
+        Ok(())
     }
 }
 
 impl IntoSubsystem<anyhow::Error> for UpdateCheckerTask {
-    async fn run(self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
+    async fn run(mut self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
         match self.main_loop().cancel_on_shutdown(subsys).await {
             Ok(Ok(())) => {}
             Ok(Err(error)) => return Err(error),
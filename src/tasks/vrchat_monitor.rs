@@ -1,12 +1,12 @@
+use crate::metrics::metrics;
 use crate::tasks::orchestrate::AppEvent;
+use crate::tasks::restart::RestartableTask;
 use log::debug;
 use std::ffi::OsStr;
 use std::time::Duration;
 use sysinfo::{ProcessRefreshKind, RefreshKind, System};
 use tokio::sync::mpsc;
 use tokio::time::sleep;
-use tokio_graceful_shutdown::errors::CancelledByShutdown;
-use tokio_graceful_shutdown::{FutureExt, IntoSubsystem, SubsystemHandle};
 
 pub struct VrchatMonitorTask {
     app_event_tx: mpsc::Sender<AppEvent>,
@@ -30,6 +30,7 @@ impl VrchatMonitorTask {
 
             if process_running != is_running {
                 is_running = process_running;
+                metrics().set_vrchat_running(is_running);
 
                 match is_running {
                     true => {
@@ -48,14 +49,10 @@ impl VrchatMonitorTask {
     }
 }
 
-impl IntoSubsystem<anyhow::Error> for VrchatMonitorTask {
-    async fn run(self, subsys: &mut SubsystemHandle) -> anyhow::Result<()> {
-        match self.main_loop().cancel_on_shutdown(subsys).await {
-            Ok(Ok(())) => {}
-            Ok(Err(error)) => return Err(error),
-            Err(CancelledByShutdown) => {}
-        }
+impl RestartableTask for VrchatMonitorTask {
+    const NAME: &'static str = "VRChat monitor";
 
-        Ok(())
+    async fn run_once(&mut self) -> anyhow::Result<()> {
+        self.main_loop().await
     }
 }
@@ -1,11 +1,28 @@
-use crate::config::{DarkLight, RootConfig};
+use crate::config::{
+    AutoStartMethod, DarkLight, NotificationCategory, ParameterMappings, RootConfig, UpdateChannel,
+};
 use crate::platform::{get_platform, Platform};
+use crate::plugins::parameters::{set_custom_address, ParameterMap};
 use crate::plugins::Plugin;
+use crate::tasks::avatars::AvatarCommand;
+use crate::tasks::config_monitor::ConfigCommand;
+use crate::tasks::diagnostics::DiagnosticsCommand;
+use crate::tasks::inspector::InspectorCommand;
+use crate::tasks::logs::LogsCommand;
 use crate::tasks::orchestrate::UiEvent;
+use crate::tasks::profiles::ProfileCommand;
+use crate::tasks::recorder::RecorderCommand;
+use crate::tasks::setups::SetupCommand;
+use crate::tasks::simulator::SimulatorCommand;
 use crate::utils::config::ConfigHandle;
-use crate::{AppWindow, PluginItem, PluginItems, Settings, UpdateNotice};
+use crate::{
+    AppWindow, Avatars, ConfigStatus, Diagnostics, Inspector, Logs, NotificationCategoryItem,
+    ParameterItem, Parameters, PluginItem, PluginItems, Profiles, Recorder, Settings, Setup,
+    SetupPluginItem, Simulator, SubsystemRestartItem, SubsystemRestarts, UpdateNotice,
+};
+use async_osc::{OscMessage, OscType};
 use log::error;
-use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -19,9 +36,133 @@ pub fn run_ui(
     config: ConfigHandle<RootConfig>,
 ) -> anyhow::Result<()> {
     let plugin_items = create_plugin_items(&plugins, &enabled_plugins);
-    let model = ModelRc::new(VecModel::from(plugin_items));
+    let model = ModelRc::new(VecModel::from(plugin_items.clone()));
     app_window.global::<PluginItems>().set_items(model.clone());
 
+    let parameter_items = create_parameter_items(&plugins, &config.blocking_read().parameters);
+    app_window
+        .global::<Parameters>()
+        .set_items(ModelRc::new(VecModel::from(parameter_items)));
+
+    app_window.global::<Parameters>().on_set_address({
+        let ui_event_tx = ui_event_tx.clone();
+        let app_window = app_window.as_weak();
+        let plugins = plugins.clone();
+        let config = config.clone();
+
+        move |plugin_id, name, address| {
+            let address = address.trim().to_string();
+
+            // Shows right away whether the new address clashes with another parameter.
+            let mut parameter_mappings = config.blocking_read().parameters.clone();
+            set_custom_address(&mut parameter_mappings, &plugin_id, &name, &address);
+            app_window
+                .unwrap()
+                .global::<Parameters>()
+                .set_items(ModelRc::new(VecModel::from(create_parameter_items(
+                    &plugins,
+                    &parameter_mappings,
+                ))));
+
+            ui_event_tx
+                .blocking_send(UiEvent::ParameterAddress {
+                    plugin_id: plugin_id.into(),
+                    name: name.into(),
+                    address,
+                })
+                .unwrap();
+        }
+    });
+
+    let setup_plugins: Vec<SetupPluginItem> = plugin_items
+        .iter()
+        .map(|item| SetupPluginItem {
+            id: item.id.clone(),
+            title: item.title.clone(),
+            selected: true,
+        })
+        .collect();
+    let setup_plugins = ModelRc::new(VecModel::from(setup_plugins));
+    let setup = app_window.global::<Setup>();
+    setup.set_plugins(setup_plugins.clone());
+
+    setup.on_toggle_plugin({
+        let setup_plugins = setup_plugins.clone();
+
+        move |plugin_id, selected| {
+            if let Some(index) = setup_plugins.iter().position(|item| item.id == plugin_id) {
+                let mut row_data = setup_plugins.row_data(index).unwrap();
+                row_data.selected = selected;
+                setup_plugins.set_row_data(index, row_data);
+            }
+        }
+    });
+
+    setup.on_export({
+        let app_window = app_window.as_weak();
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            let include_secrets = app_window.unwrap().global::<Setup>().get_include_secrets();
+            let plugins = setup_plugins
+                .iter()
+                .filter(|item| item.selected)
+                .map(|item| item.id.to_string())
+                .collect();
+
+            ui_event_tx
+                .blocking_send(UiEvent::Setup(SetupCommand::Export {
+                    plugins,
+                    include_secrets,
+                }))
+                .unwrap();
+        }
+    });
+
+    setup.on_preview_import({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            ui_event_tx
+                .blocking_send(UiEvent::Setup(SetupCommand::Preview(path.trim().into())))
+                .unwrap();
+        }
+    });
+
+    app_window.global::<Diagnostics>().on_create({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            let path = path.trim();
+
+            ui_event_tx
+                .blocking_send(UiEvent::Diagnostics(DiagnosticsCommand::Create(
+                    (!path.is_empty()).then(|| path.into()),
+                )))
+                .unwrap();
+        }
+    });
+
+    setup.on_apply_import({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Setup(SetupCommand::Apply))
+                .unwrap();
+        }
+    });
+
+    setup.on_discard_import({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Setup(SetupCommand::Discard))
+                .unwrap();
+        }
+    });
+
     app_window.global::<PluginItems>().on_toggle_enabled({
         let model = model.clone();
         let ui_event_tx = ui_event_tx.clone();
@@ -47,6 +188,50 @@ pub fn run_ui(
         let _ = open::that(<&str as Into<PathBuf>>::into(url.as_str()));
     });
 
+    app_window.global::<SubsystemRestarts>().on_dismiss({
+        let app_window = app_window.as_weak();
+
+        move |name| {
+            let app_window = app_window.unwrap();
+            let subsystem_restarts = app_window.global::<SubsystemRestarts>();
+            let items: Vec<SubsystemRestartItem> = subsystem_restarts
+                .get_items()
+                .iter()
+                .filter(|item| item.name != name)
+                .collect();
+
+            subsystem_restarts.set_items(ModelRc::new(VecModel::from(items)));
+        }
+    });
+
+    app_window.global::<UpdateNotice>().on_skip({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |version| {
+            ui_event_tx
+                .blocking_send(UiEvent::SkipUpdate(version.into()))
+                .unwrap();
+        }
+    });
+
+    app_window.global::<UpdateNotice>().on_install({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx.blocking_send(UiEvent::InstallUpdate).unwrap();
+        }
+    });
+
+    app_window.global::<UpdateNotice>().on_restart({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::RestartForUpdate)
+                .unwrap();
+        }
+    });
+
     app_window.global::<PluginItems>().on_open_settings({
         let app_window = app_window.as_weak();
 
@@ -72,7 +257,27 @@ pub fn run_ui(
     });
 
     settings.set_auto_start(get_platform().has_auto_start());
+    settings.set_auto_start_minimized(config.blocking_read().auto_start.start_minimized);
+    settings.set_auto_start_method(config.blocking_read().auto_start.method.label().into());
+    let auto_start_methods: Vec<SharedString> = get_platform()
+        .auto_start_methods()
+        .iter()
+        .map(|method| method.label().into())
+        .collect();
+    settings.set_auto_start_methods(ModelRc::new(VecModel::from(auto_start_methods)));
     settings.set_check_for_updates(config.blocking_read().check_for_updates);
+    settings.set_update_channel(config.blocking_read().updates.channel.label().into());
+
+    let disabled_notifications = config.blocking_read().notifications.disabled.clone();
+    let notification_items: Vec<NotificationCategoryItem> = NotificationCategory::ALL
+        .iter()
+        .map(|category| NotificationCategoryItem {
+            id: category.id().into(),
+            title: category.label().into(),
+            enabled: !disabled_notifications.contains(category),
+        })
+        .collect();
+    settings.set_notifications(ModelRc::new(VecModel::from(notification_items)));
 
     settings.on_toggle_tray_icons({
         let ui_event_tx = ui_event_tx.clone();
@@ -101,6 +306,30 @@ pub fn run_ui(
         }
     });
 
+    settings.on_toggle_auto_start_minimized({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |start_minimized| {
+            ui_event_tx
+                .blocking_send(UiEvent::AutoStartMinimizedToggle(start_minimized))
+                .unwrap();
+        }
+    });
+
+    settings.on_set_auto_start_method({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |method| {
+            let Some(method) = AutoStartMethod::from_label(&method) else {
+                return;
+            };
+
+            ui_event_tx
+                .blocking_send(UiEvent::AutoStartMethod(method))
+                .unwrap();
+        }
+    });
+
     settings.on_toggle_check_for_updates({
         let ui_event_tx = ui_event_tx.clone();
 
@@ -111,6 +340,38 @@ pub fn run_ui(
         }
     });
 
+    settings.on_toggle_notification({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id, enabled| {
+            ui_event_tx
+                .blocking_send(UiEvent::NotificationToggle(id.into(), enabled))
+                .unwrap();
+        }
+    });
+
+    settings.on_set_update_channel({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |channel| {
+            let Some(channel) = UpdateChannel::from_label(&channel) else {
+                return;
+            };
+
+            ui_event_tx
+                .blocking_send(UiEvent::UpdateChannel(channel))
+                .unwrap();
+        }
+    });
+
+    settings.on_check_for_updates_now({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx.blocking_send(UiEvent::CheckForUpdates).unwrap();
+        }
+    });
+
     settings.on_open_logs_folder({
         let ui_event_tx = ui_event_tx.clone();
 
@@ -127,6 +388,297 @@ pub fn run_ui(
         }
     });
 
+    let inspector = app_window.global::<Inspector>();
+
+    inspector.on_toggle_paused({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |paused| {
+            ui_event_tx
+                .blocking_send(UiEvent::Inspector(InspectorCommand::SetPaused(paused)))
+                .unwrap();
+        }
+    });
+
+    inspector.on_filter_changed({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |filter| {
+            ui_event_tx
+                .blocking_send(UiEvent::Inspector(InspectorCommand::SetFilter(
+                    filter.into(),
+                )))
+                .unwrap();
+        }
+    });
+
+    inspector.on_clear({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Inspector(InspectorCommand::Clear))
+                .unwrap();
+        }
+    });
+
+    inspector.on_export({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            let path = path.trim();
+
+            ui_event_tx
+                .blocking_send(UiEvent::Inspector(InspectorCommand::Export(
+                    (!path.is_empty()).then(|| path.into()),
+                )))
+                .unwrap();
+        }
+    });
+
+    let logs = app_window.global::<Logs>();
+
+    logs.on_filter_changed({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |level, module| {
+            ui_event_tx
+                .blocking_send(UiEvent::Logs(LogsCommand::SetFilter {
+                    level: level.into(),
+                    module: module.into(),
+                }))
+                .unwrap();
+        }
+    });
+
+    logs.on_set_level({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |module, level| {
+            ui_event_tx
+                .blocking_send(UiEvent::Logs(LogsCommand::SetLevel {
+                    module: module.into(),
+                    level: level.into(),
+                }))
+                .unwrap();
+        }
+    });
+
+    let recorder = app_window.global::<Recorder>();
+
+    recorder.on_start_recording({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Recorder(RecorderCommand::StartRecording(None)))
+                .unwrap();
+        }
+    });
+
+    recorder.on_stop_recording({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Recorder(RecorderCommand::StopRecording))
+                .unwrap();
+        }
+    });
+
+    recorder.on_start_replay({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path, speed| {
+            let speed = speed.trim_end_matches('x').parse().unwrap_or(1.);
+
+            ui_event_tx
+                .blocking_send(UiEvent::Recorder(RecorderCommand::StartReplay {
+                    path: PathBuf::from(path.trim()),
+                    speed,
+                }))
+                .unwrap();
+        }
+    });
+
+    recorder.on_stop_replay({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Recorder(RecorderCommand::StopReplay))
+                .unwrap();
+        }
+    });
+
+    let simulator = app_window.global::<Simulator>();
+    let inject = {
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |address: &str, argument: OscType| {
+            ui_event_tx
+                .blocking_send(UiEvent::Simulator(SimulatorCommand::Inject(OscMessage {
+                    addr: address.to_string(),
+                    args: vec![argument],
+                })))
+                .unwrap();
+        }
+    };
+
+    simulator.on_set_bool({
+        let inject = inject.clone();
+        move |address, value| inject(&address, OscType::Bool(value))
+    });
+
+    simulator.on_set_float({
+        let inject = inject.clone();
+        move |address, value| inject(&address, OscType::Float(value))
+    });
+
+    simulator.on_set_int({
+        let inject = inject.clone();
+        move |address, value| inject(&address, OscType::Int(value))
+    });
+
+    simulator.on_send_string(move |address, value| inject(&address, OscType::String(value.into())));
+
+    simulator.on_clear_readouts({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move || {
+            ui_event_tx
+                .blocking_send(UiEvent::Simulator(SimulatorCommand::ClearReadouts))
+                .unwrap();
+        }
+    });
+
+    let config_status = app_window.global::<ConfigStatus>();
+
+    config_status.on_retry({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            ui_event_tx
+                .blocking_send(UiEvent::Config(ConfigCommand::Retry(path.as_str().into())))
+                .unwrap();
+        }
+    });
+
+    config_status.on_reset({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            ui_event_tx
+                .blocking_send(UiEvent::Config(ConfigCommand::Reset(path.as_str().into())))
+                .unwrap();
+        }
+    });
+
+    config_status.on_restore_previous({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |path| {
+            ui_event_tx
+                .blocking_send(UiEvent::Config(ConfigCommand::RestorePrevious(
+                    path.as_str().into(),
+                )))
+                .unwrap();
+        }
+    });
+
+    let profiles = app_window.global::<Profiles>();
+
+    profiles.on_switch({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |name| {
+            ui_event_tx
+                .blocking_send(UiEvent::Profile(ProfileCommand::Switch(name.into())))
+                .unwrap();
+        }
+    });
+
+    profiles.on_save({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |name| {
+            ui_event_tx
+                .blocking_send(UiEvent::Profile(ProfileCommand::Save(
+                    name.trim().to_string(),
+                )))
+                .unwrap();
+        }
+    });
+
+    profiles.on_delete({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |name| {
+            ui_event_tx
+                .blocking_send(UiEvent::Profile(ProfileCommand::Delete(name.into())))
+                .unwrap();
+        }
+    });
+
+    let avatars = app_window.global::<Avatars>();
+
+    avatars.on_rename({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id, name| {
+            ui_event_tx
+                .blocking_send(UiEvent::Avatar(AvatarCommand::Rename(
+                    id.into(),
+                    name.into(),
+                )))
+                .unwrap();
+        }
+    });
+
+    avatars.on_save_overrides({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id| {
+            ui_event_tx
+                .blocking_send(UiEvent::Avatar(AvatarCommand::SaveOverrides(id.into())))
+                .unwrap();
+        }
+    });
+
+    avatars.on_clear_overrides({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id| {
+            ui_event_tx
+                .blocking_send(UiEvent::Avatar(AvatarCommand::ClearOverrides(id.into())))
+                .unwrap();
+        }
+    });
+
+    avatars.on_set_plugin_enabled({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id, plugin_id, enabled| {
+            ui_event_tx
+                .blocking_send(UiEvent::Avatar(AvatarCommand::SetPluginEnabled(
+                    id.into(),
+                    plugin_id.into(),
+                    enabled,
+                )))
+                .unwrap();
+        }
+    });
+
+    avatars.on_forget({
+        let ui_event_tx = ui_event_tx.clone();
+
+        move |id| {
+            ui_event_tx
+                .blocking_send(UiEvent::Avatar(AvatarCommand::Forget(id.into())))
+                .unwrap();
+        }
+    });
+
     slint::run_event_loop_until_quit()?;
     Ok(())
 }
@@ -150,3 +702,35 @@ fn create_plugin_items(
     items.sort_by(|a, b| a.title.cmp(&b.title));
     items
 }
+
+fn create_parameter_items(
+    plugins: &HashMap<&'static str, Arc<dyn Plugin>>,
+    parameter_mappings: &ParameterMappings,
+) -> Vec<ParameterItem> {
+    let mut plugins: Vec<_> = plugins.iter().collect();
+    plugins.sort_by_key(|(_, plugin)| plugin.title());
+
+    plugins
+        .into_iter()
+        .flat_map(|(id, plugin)| {
+            let mappings = parameter_mappings.get(*id);
+            let parameter_map = ParameterMap::new(plugin.osc_parameters(), mappings);
+
+            plugin
+                .osc_parameters()
+                .iter()
+                .map(move |parameter| ParameterItem {
+                    plugin_id: id.to_string().into(),
+                    plugin_title: plugin.title().into(),
+                    name: parameter.name.into(),
+                    description: parameter.description.into(),
+                    address: mappings
+                        .and_then(|mappings| mappings.get(parameter.name))
+                        .cloned()
+                        .unwrap_or_default()
+                        .into(),
+                    error: parameter_map.conflict(parameter).unwrap_or_default().into(),
+                })
+        })
+        .collect()
+}
@@ -1,30 +1,278 @@
-use std::fs::File;
-use std::io::{ErrorKind, Read};
+use std::fmt::{self, Display, Formatter};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
-use log::{debug, error};
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use chrono::Local;
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tokio::sync::mpsc::error::SendError;
-use tokio::sync::{mpsc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{broadcast, mpsc, watch, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::task::spawn_blocking;
 
 use crate::tasks::config_writer::WriteConfigRequest;
 
+mod secrets;
+
+pub use secrets::Secrets;
+
+pub const VERSION_KEY: &str = "schema_version";
+
+/// Directories holding copies of the plugin config files, one folder each laid out like the `plugins` directory.
+const SNAPSHOT_DIRS: &[&str] = &["profiles", "avatars"];
+
+/// Number of rolling backups kept per config file.
+pub const BACKUP_COUNT: usize = 3;
+
+/// Upgrades a raw config table by exactly one schema version.
+pub type Migration = fn(&mut toml::Table) -> anyhow::Result<()>;
+
+/// A config type that can be loaded through the [`ConfigManager`].
+///
+/// Every file written through a [`ConfigHandle`] carries a `schema_version` key. When a breaking change is made to a
+/// config type, append a migration to [`Config::MIGRATIONS`] which rewrites the previous schema into the new one. The
+/// schema version is implicitly the number of migrations plus one, and files without a version are considered to be
+/// at version 1.
+pub trait Config: Serialize + DeserializeOwned + Default + Clone + Send + Sync + 'static {
+    const MIGRATIONS: &'static [Migration] = &[];
+
+    /// Top-level keys holding credentials, which are left out of exported setups unless requested.
+    ///
+    /// Plugins keep their values in [`Secrets`] under the same name, where exports with credentials read them from.
+    const SECRET_KEYS: &'static [&'static str] = &[];
+
+    fn schema_version() -> u32 {
+        Self::MIGRATIONS.len() as u32 + 1
+    }
+}
+
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    config: &'a T,
+}
+
+#[derive(Default)]
+struct FileStampState {
+    modified: Option<SystemTime>,
+    generation: u64,
+}
+
+/// Tracks what the application last knew about a config file on disk, so that external edits are never overwritten.
+///
+/// The generation is bumped every time the file is (re)loaded, which invalidates writes that were requested before.
+#[derive(Clone, Default)]
+pub struct FileStamp(Arc<Mutex<FileStampState>>);
+
+impl FileStamp {
+    fn generation(&self) -> u64 {
+        self.0.lock().unwrap().generation
+    }
+
+    fn mark_loaded(&self, modified: Option<SystemTime>) {
+        let mut state = self.0.lock().unwrap();
+        state.modified = modified;
+        state.generation += 1;
+    }
+
+    pub fn mark_written(&self, path: &Path) {
+        self.0.lock().unwrap().modified = modified_time(path);
+    }
+
+    pub fn changed_on_disk(&self, path: &Path) -> bool {
+        self.0.lock().unwrap().modified != modified_time(path)
+    }
+
+    /// Whether the file was reloaded since a write was requested at the given generation, which discards the write.
+    pub fn superseded(&self, generation: u64) -> bool {
+        self.0.lock().unwrap().generation != generation
+    }
+
+    /// Whether a write requested at the given generation can go ahead without clobbering newer content.
+    pub fn allows_write(&self, path: &Path, generation: u64) -> bool {
+        let state = self.0.lock().unwrap();
+        state.generation == generation && state.modified == modified_time(path)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// A config file which could not be loaded or saved. Writes to it are blocked until it is reset or successfully
+/// reloaded.
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub path: PathBuf,
+    pub name: String,
+    pub summary: String,
+    pub message: String,
+    pub line: Option<usize>,
+    pub preserved_path: Option<PathBuf>,
+}
+
+struct ParseError {
+    message: String,
+    line: Option<usize>,
+}
+
+impl ParseError {
+    fn from_toml(error: toml::de::Error, source: &str) -> Self {
+        Self {
+            message: error.message().to_string(),
+            line: error
+                .span()
+                .map(|span| source[..span.start].matches('\n').count() + 1),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ParseError {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            message: format!("{:#}", error),
+            line: None,
+        }
+    }
+}
+
+/// Type-erased access to a loaded config, used to act on config files without knowing their type.
+#[async_trait]
+pub trait ManagedConfig: Send + Sync {
+    fn path(&self) -> &Path;
+    fn name(&self) -> &str;
+    fn changed_on_disk(&self) -> bool;
+    fn secret_keys(&self) -> &'static [&'static str];
+
+    /// Checks whether the given source could be applied, without applying it.
+    fn validate(&self, toml_config: &str) -> anyhow::Result<()>;
+
+    /// Re-reads the file from disk, replacing the in-memory config on success.
+    async fn reload(&self) -> Result<(), ConfigIssue>;
+
+    /// Replaces both the in-memory config and the file on disk with the defaults.
+    async fn reset(&self) -> anyhow::Result<()>;
+
+    /// Replaces both the in-memory config and the file on disk with the most recent backup.
+    async fn restore_previous(&self) -> anyhow::Result<()>;
+
+    /// Serializes the current in-memory config.
+    async fn snapshot(&self) -> String;
+
+    /// Replaces the config with the given source, returning whether anything changed.
+    async fn apply(&self, toml_config: &str) -> anyhow::Result<bool>;
+}
+
+/// Keeps track of all loaded config files and of those which failed to load.
+#[derive(Clone)]
+pub struct ConfigRegistry {
+    config_dir: PathBuf,
+    configs: Arc<Mutex<Vec<Arc<dyn ManagedConfig>>>>,
+    issues: Arc<watch::Sender<Vec<ConfigIssue>>>,
+    changes: broadcast::Sender<PathBuf>,
+}
+
+impl ConfigRegistry {
+    fn new(config_dir: PathBuf) -> Self {
+        Self {
+            config_dir,
+            configs: Arc::new(Mutex::new(vec![])),
+            issues: Arc::new(watch::channel(vec![]).0),
+            changes: broadcast::channel(64).0,
+        }
+    }
+
+    fn register(&self, config: Arc<dyn ManagedConfig>) {
+        self.configs.lock().unwrap().push(config);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Arc<dyn ManagedConfig>> {
+        self.configs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|config| config.path() == path)
+            .cloned()
+    }
+
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Subscribes to the paths of config files which were replaced as a whole, either by reloading them from disk or
+    /// by applying a profile or setup. Changes made through [`ConfigHandle::update`] are not included.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<PathBuf> {
+        self.changes.subscribe()
+    }
+
+    pub fn configs(&self) -> Vec<Arc<dyn ManagedConfig>> {
+        self.configs.lock().unwrap().clone()
+    }
+
+    pub fn subscribe_issues(&self) -> watch::Receiver<Vec<ConfigIssue>> {
+        self.issues.subscribe()
+    }
+
+    pub fn report_issue(&self, issue: ConfigIssue) {
+        self.issues.send_modify(|issues| {
+            issues.retain(|existing| existing.path != issue.path);
+            issues.push(issue);
+        });
+    }
+
+    pub fn clear_issue(&self, path: &Path) {
+        self.issues.send_if_modified(|issues| {
+            let count = issues.len();
+            issues.retain(|issue| issue.path != path);
+            issues.len() != count
+        });
+    }
+
+    fn display_name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.config_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
 #[derive(Clone)]
 pub struct ConfigHandle<T>
 where
-    T: Serialize,
+    T: Config,
 {
     config: Arc<RwLock<T>>,
     file_path: PathBuf,
+    config_dir: PathBuf,
+    name: String,
     debounce: Option<Duration>,
     sender: mpsc::Sender<WriteConfigRequest>,
+    blocked: Arc<AtomicBool>,
+    stamp: FileStamp,
+    snapshots: watch::Sender<T>,
+    changes: broadcast::Sender<PathBuf>,
 }
 
 impl<T> ConfigHandle<T>
 where
-    T: Serialize,
+    T: Config,
 {
     pub fn blocking_update<F, R>(&self, modify_fn: F) -> Result<R, SendError<WriteConfigRequest>>
     where
@@ -33,10 +281,14 @@ where
         let (request, result) = {
             let mut config = self.config.blocking_write();
             let result = modify_fn(&mut config);
+            self.notify_change(&config);
             (self.write_config_request(&config), result)
         };
 
-        self.sender.blocking_send(request)?;
+        if let Some(request) = request {
+            self.sender.blocking_send(request)?;
+        }
+
         Ok(result)
     }
 
@@ -51,10 +303,14 @@ where
         let (request, result) = {
             let mut config = self.config.write().await;
             let result = modify_fn(&mut config);
+            self.notify_change(&config);
             (self.write_config_request(&config), result)
         };
 
-        self.sender.send(request).await?;
+        if let Some(request) = request {
+            self.sender.send(request).await?;
+        }
+
         Ok(result)
     }
 
@@ -62,27 +318,516 @@ where
         self.config.read().await
     }
 
-    fn write_config_request(&self, config: &T) -> WriteConfigRequest {
-        WriteConfigRequest {
-            config: toml::to_string_pretty(&config).expect("Serialization of config failed"),
+    /// Subscribes to snapshots of the config, which are published after every change.
+    pub fn subscribe(&self) -> watch::Receiver<T> {
+        self.snapshots.subscribe()
+    }
+
+    fn notify_change(&self, config: &T) {
+        self.snapshots.send_replace(config.clone());
+    }
+
+    /// Tells subscribers of [`ConfigRegistry::subscribe_changes`] that the config was replaced from outside.
+    fn notify_replaced(&self) {
+        let _ = self.changes.send(self.file_path.clone());
+    }
+
+    fn write_config_request(&self, config: &T) -> Option<WriteConfigRequest> {
+        if self.blocked.load(Ordering::SeqCst) {
+            warn!(
+                "Not writing config file {} as it could not be loaded",
+                self.file_path.to_string_lossy()
+            );
+            return None;
+        }
+
+        Some(WriteConfigRequest {
+            config: serialize_config(config).expect("Serialization of config failed"),
             path: self.file_path.clone(),
             debounce: self.debounce,
+            stamp: self.stamp.clone(),
+            generation: self.stamp.generation(),
+            rotate_backups: true,
+        })
+    }
+
+    /// Applies a change which removes credentials from the config, and removes them from every copy of the file too.
+    ///
+    /// Backups are not rotated for this write, as that would copy the credentials into a backup once more.
+    pub async fn remove_secrets<F>(&self, modify_fn: F) -> Result<(), SendError<WriteConfigRequest>>
+    where
+        F: FnOnce(&mut T),
+    {
+        let request = {
+            let mut config = self.config.write().await;
+            modify_fn(&mut config);
+            self.notify_change(&config);
+            self.write_config_request(&config)
+        };
+
+        if let Some(mut request) = request {
+            request.rotate_backups = false;
+            self.sender.send(request).await?;
+        }
+
+        self.remove_secrets_from_copies().await;
+        Ok(())
+    }
+
+    async fn remove_secrets_from_copies(&self) {
+        let file_path = self.file_path.clone();
+        let config_dir = self.config_dir.clone();
+
+        // Every copy is read and possibly rewritten, which must not hold up the runtime.
+        let result = spawn_blocking(move || {
+            for path in copies(&file_path, &config_dir) {
+                match remove_keys(&path, T::SECRET_KEYS) {
+                    Ok(true) => info!("Removed credentials from {}", path.to_string_lossy()),
+                    Ok(false) => {}
+                    Err(error) => warn!(
+                        "Failed to remove credentials from {}: {:#}",
+                        path.to_string_lossy(),
+                        error
+                    ),
+                }
+            }
+        })
+        .await;
+
+        if let Err(error) = result {
+            error!("Failed to remove credentials from copies: {}", error);
+        }
+    }
+
+    fn issue(&self, error: ParseError, preserved_path: Option<PathBuf>) -> ConfigIssue {
+        ConfigIssue {
+            path: self.file_path.clone(),
+            name: self.name.clone(),
+            summary: format!(
+                "Could not load {}, changes to it will not be saved",
+                self.name
+            ),
+            message: error.message,
+            line: error.line,
+            preserved_path,
+        }
+    }
+}
+
+#[async_trait]
+impl<T> ManagedConfig for ConfigHandle<T>
+where
+    T: Config,
+{
+    fn path(&self) -> &Path {
+        &self.file_path
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn changed_on_disk(&self) -> bool {
+        self.stamp.changed_on_disk(&self.file_path)
+    }
+
+    fn secret_keys(&self) -> &'static [&'static str] {
+        T::SECRET_KEYS
+    }
+
+    fn validate(&self, toml_config: &str) -> anyhow::Result<()> {
+        parse_config::<T>(toml_config).map_err(|error| anyhow!("{}", error))?;
+        Ok(())
+    }
+
+    async fn reload(&self) -> Result<(), ConfigIssue> {
+        let config = match load_config_from_file::<T>(&self.file_path, &self.sender, &self.stamp) {
+            Ok(config) => config.unwrap_or_default(),
+            Err(error) => {
+                self.blocked.store(true, Ordering::SeqCst);
+                let preserved_path = preserve_broken_file(&self.file_path);
+                return Err(self.issue(error, preserved_path));
+            }
+        };
+
+        let mut current = self.config.write().await;
+        *current = config;
+        self.notify_change(&current);
+        drop(current);
+        self.notify_replaced();
+
+        self.blocked.store(false, Ordering::SeqCst);
+        info!("Reloaded config file {}", self.file_path.to_string_lossy());
+
+        Ok(())
+    }
+
+    async fn reset(&self) -> anyhow::Result<()> {
+        self.blocked.store(false, Ordering::SeqCst);
+        self.update(|config| **config = T::default()).await?;
+        self.notify_replaced();
+        info!(
+            "Reset config file {} to defaults",
+            self.file_path.to_string_lossy()
+        );
+
+        Ok(())
+    }
+
+    async fn restore_previous(&self) -> anyhow::Result<()> {
+        let backup_path = backup_path(&self.file_path, 1);
+        let config = read_config_file::<T>(&backup_path)
+            .map_err(|error| anyhow!("{}", error))?
+            .context("No previous version available")?
+            .config;
+
+        self.blocked.store(false, Ordering::SeqCst);
+        let request = {
+            let mut current = self.config.write().await;
+            *current = config;
+            self.notify_change(&current);
+            self.write_config_request(&current)
+        };
+
+        // Rotating the backups here would push the version being restored out of the first backup slot.
+        if let Some(mut request) = request {
+            request.rotate_backups = false;
+            self.sender.send(request).await?;
+        }
+        self.notify_replaced();
+        info!(
+            "Restored config file {} from {}",
+            self.file_path.to_string_lossy(),
+            backup_path.to_string_lossy()
+        );
+
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> String {
+        serialize_config(&*self.read().await).expect("Serialization of config failed")
+    }
+
+    async fn apply(&self, toml_config: &str) -> anyhow::Result<bool> {
+        let (config, _) = parse_config::<T>(toml_config).map_err(|error| anyhow!("{}", error))?;
+
+        if serialize_config(&config)? == self.snapshot().await {
+            return Ok(false);
+        }
+
+        self.blocked.store(false, Ordering::SeqCst);
+        self.update(|current| **current = config).await?;
+        self.notify_replaced();
+        Ok(true)
+    }
+}
+
+fn serialize_config<T: Config>(config: &T) -> Result<String, toml::ser::Error> {
+    toml::to_string_pretty(&Versioned {
+        schema_version: T::schema_version(),
+        config,
+    })
+}
+
+/// Runs all pending migrations on a raw config table and returns the version it was migrated from.
+fn migrate<T: Config>(table: &mut toml::Table) -> anyhow::Result<u32> {
+    let version = match table.remove(VERSION_KEY) {
+        Some(toml::Value::Integer(version)) if version >= 1 => version as u32,
+        Some(value) => bail!("Invalid schema version: {}", value),
+        None => 1,
+    };
+
+    if version > T::schema_version() {
+        bail!(
+            "Schema version {} is newer than the supported version {}",
+            version,
+            T::schema_version()
+        );
+    }
+
+    for (index, migration) in T::MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(table)
+            .with_context(|| format!("Migration to schema version {} failed", index + 2))?;
+    }
+
+    Ok(version)
+}
+
+struct LoadedConfig<T> {
+    config: T,
+    version: u32,
+    source: String,
+}
+
+/// Reads and migrates a config file without persisting anything, returning `None` if it does not exist.
+fn read_config_file<T>(path: &Path) -> Result<Option<LoadedConfig<T>>, ParseError>
+where
+    T: Config,
+{
+    let toml_config = match std::fs::read_to_string(path) {
+        Ok(toml_config) => toml_config,
+        Err(ref error) if error.kind() == ErrorKind::NotFound => {
+            debug!(
+                "Config file {} does not exist, falling back to default",
+                path.to_string_lossy()
+            );
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(ParseError {
+                message: format!("Could not read file: {}", error),
+                line: None,
+            })
+        }
+    };
+
+    let (config, version) = parse_config(&toml_config)?;
+
+    Ok(Some(LoadedConfig {
+        config,
+        version,
+        source: toml_config,
+    }))
+}
+
+/// Parses and migrates config source, returning the config and the version it was migrated from.
+fn parse_config<T>(toml_config: &str) -> Result<(T, u32), ParseError>
+where
+    T: Config,
+{
+    let mut table: toml::Table =
+        toml::from_str(toml_config).map_err(|error| ParseError::from_toml(error, toml_config))?;
+    let version = migrate::<T>(&mut table)?;
+
+    let config = if version == T::schema_version() {
+        // Deserialize from the source so that type errors carry a line number.
+        toml::from_str(toml_config).map_err(|error| ParseError::from_toml(error, toml_config))?
+    } else {
+        table.try_into().map_err(anyhow::Error::from)?
+    };
+
+    Ok((config, version))
+}
+
+/// Loads a config file, returning `None` if it does not exist.
+fn load_config_from_file<T>(
+    path: &Path,
+    sender: &mpsc::Sender<WriteConfigRequest>,
+    stamp: &FileStamp,
+) -> Result<Option<T>, ParseError>
+where
+    T: Config,
+{
+    stamp.mark_loaded(modified_time(path));
+
+    let Some(loaded) = read_config_file::<T>(path)? else {
+        return Ok(None);
+    };
+
+    if loaded.version < T::schema_version() {
+        backup_and_rewrite(
+            path,
+            &loaded.source,
+            loaded.version,
+            &loaded.config,
+            sender,
+            stamp,
+        );
+    }
+
+    Ok(Some(loaded.config))
+}
+
+fn backup_and_rewrite<T>(
+    path: &Path,
+    toml_config: &str,
+    version: u32,
+    config: &T,
+    sender: &mpsc::Sender<WriteConfigRequest>,
+    stamp: &FileStamp,
+) where
+    T: Config,
+{
+    let backup_path = sibling_path(path, &format!("v{}.bak", version));
+
+    // The migrated config is used either way, the regular backups still keep the file before it is rewritten.
+    match std::fs::write(&backup_path, toml_config) {
+        Ok(()) => info!(
+            "Migrated config file {} from schema version {} to {}, backup kept at {}",
+            path.to_string_lossy(),
+            version,
+            T::schema_version(),
+            backup_path.to_string_lossy()
+        ),
+        Err(error) => warn!(
+            "Migrated config file {} from schema version {} to {}, but could not back up to {}: {}",
+            path.to_string_lossy(),
+            version,
+            T::schema_version(),
+            backup_path.to_string_lossy(),
+            error
+        ),
+    }
+
+    let result = serialize_config(config)
+        .map_err(anyhow::Error::from)
+        .and_then(|config| {
+            Ok(sender.try_send(WriteConfigRequest {
+                config,
+                path: path.to_path_buf(),
+                debounce: None,
+                stamp: stamp.clone(),
+                generation: stamp.generation(),
+                rotate_backups: true,
+            })?)
+        });
+
+    if let Err(error) = result {
+        warn!(
+            "Could not persist migrated config file {}: {}",
+            path.to_string_lossy(),
+            error
+        );
+    }
+}
+
+/// Lists the backups of a config file, as well as its copies in profiles and per-avatar settings.
+fn copies(file_path: &Path, config_dir: &Path) -> Vec<PathBuf> {
+    let mut copies = vec![];
+
+    if let (Some(parent), Some(file_name)) = (file_path.parent(), file_path.file_name()) {
+        let mut prefix = file_name.to_os_string();
+        prefix.push(".");
+        let prefix = prefix.to_string_lossy().to_string();
+
+        copies.extend(
+            std::fs::read_dir(parent)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .map(|name| name.to_string_lossy())
+                        .is_some_and(|name| name.starts_with(&prefix) && !name.ends_with(".tmp"))
+                }),
+        );
+    }
+
+    if let Ok(relative_path) = file_path.strip_prefix(config_dir.join("plugins")) {
+        for snapshots_dir in SNAPSHOT_DIRS {
+            copies.extend(
+                std::fs::read_dir(config_dir.join(snapshots_dir))
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|entry| entry.path().join(relative_path))
+                    .filter(|path| path.is_file()),
+            );
+        }
+    }
+
+    copies
+}
+
+/// Removes the given top-level keys from a config file, returning whether it contained any of them.
+fn remove_keys(path: &Path, keys: &[&str]) -> anyhow::Result<bool> {
+    let mut table: toml::Table = std::fs::read_to_string(path)?.parse()?;
+    let count = table.len();
+    table.retain(|key, _| !keys.contains(&key));
+
+    if table.len() == count {
+        return Ok(false);
+    }
+
+    std::fs::write(path, toml::to_string_pretty(&table)?)?;
+    Ok(true)
+}
+
+/// Keeps a timestamped copy of a config file which failed to load, so it survives a later reset. Reuses an earlier
+/// copy with the same content, so loading the same broken file again does not pile up copies.
+fn preserve_broken_file(path: &Path) -> Option<PathBuf> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(error) => {
+            error!(
+                "Failed to preserve broken config file {}: {}",
+                path.to_string_lossy(),
+                error
+            );
+            return None;
         }
+    };
+    let mut prefix = path.file_name()?.to_os_string();
+    prefix.push(".broken-");
+    let prefix = prefix.to_string_lossy().to_string();
+
+    let existing = std::fs::read_dir(path.parent()?)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|copy| {
+            copy.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .find(|copy| std::fs::read(copy).is_ok_and(|copy_content| copy_content == content));
+    if existing.is_some() {
+        return existing;
     }
+
+    let preserved_path = sibling_path(
+        path,
+        &format!("broken-{}", Local::now().format("%Y%m%d-%H%M%S")),
+    );
+
+    match std::fs::write(&preserved_path, content) {
+        Ok(()) => Some(preserved_path),
+        Err(error) => {
+            error!(
+                "Failed to preserve broken config file {}: {}",
+                path.to_string_lossy(),
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Returns the path of a rolling backup of a config file, with index 1 being the most recent one.
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    sibling_path(path, &format!("bak.{}", index))
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(".");
+    sibling.push(suffix);
+    PathBuf::from(sibling)
 }
 
 pub struct ConfigManager {
     config_dir: PathBuf,
     plugin_id: Option<&'static str>,
     sender: mpsc::Sender<WriteConfigRequest>,
+    registry: ConfigRegistry,
+    secrets: Secrets,
 }
 
 impl ConfigManager {
-    pub fn new<P: Into<PathBuf>>(config_dir: P, sender: mpsc::Sender<WriteConfigRequest>) -> Self {
+    pub fn new<P: Into<PathBuf>>(
+        config_dir: P,
+        sender: mpsc::Sender<WriteConfigRequest>,
+        secrets: Secrets,
+    ) -> Self {
+        let config_dir = config_dir.into();
+
         Self {
-            config_dir: config_dir.into(),
+            registry: ConfigRegistry::new(config_dir.clone()),
+            config_dir,
             plugin_id: None,
             sender,
+            secrets,
         }
     }
 
@@ -91,16 +836,27 @@ impl ConfigManager {
             config_dir: self.config_dir.clone(),
             plugin_id: Some(id),
             sender: self.sender.clone(),
+            registry: self.registry.clone(),
+            secrets: self.secrets.scoped(id),
         }
     }
 
+    pub fn registry(&self) -> ConfigRegistry {
+        self.registry.clone()
+    }
+
+    /// Credentials belong here instead of into config files, scoped to the plugin if there is one.
+    pub fn secrets(&self) -> Secrets {
+        self.secrets.clone()
+    }
+
     pub fn load_config<T>(
         &self,
         name: Option<&str>,
         debounce_write: Option<Duration>,
     ) -> ConfigHandle<T>
     where
-        T: Serialize + for<'de> Deserialize<'de> + Default + Clone,
+        T: Config,
     {
         let path = match self.plugin_id {
             Some(id) => {
@@ -111,63 +867,45 @@ impl ConfigManager {
                     None => path.join("config.toml"),
                 }
             }
-            None => self.config_dir.join("config.toml"),
+            None => match name {
+                Some(name) => self.config_dir.join(format!("{}.toml", name)),
+                None => self.config_dir.join("config.toml"),
+            },
         };
 
-        let config = self.load_config_from_file(&path);
-
-        ConfigHandle {
-            config: Arc::new(RwLock::new(config)),
-            file_path: path,
-            debounce: debounce_write,
-            sender: self.sender.clone(),
-        }
-    }
-
-    fn load_config_from_file<T>(&self, path: &Path) -> T
-    where
-        T: Serialize + for<'de> Deserialize<'de> + Default + Clone,
-    {
-        let mut file = match File::open(path) {
-            Ok(file) => file,
-            Err(ref error) if error.kind() == ErrorKind::NotFound => {
-                debug!(
-                    "Config file {} does not exist, falling back to default",
-                    path.to_string_lossy()
-                );
-                return T::default();
-            }
+        let stamp = FileStamp::default();
+        let (config, error) = match load_config_from_file::<T>(&path, &self.sender, &stamp) {
+            Ok(config) => (config.unwrap_or_default(), None),
             Err(error) => {
                 error!(
-                    "Failed to open config file {}, falling back to default: {:?}",
+                    "Failed to load config file {}, falling back to default: {}",
                     path.to_string_lossy(),
                     error
                 );
-                return T::default();
+                (T::default(), Some(error))
             }
         };
 
-        let mut toml_config = String::new();
+        let handle = ConfigHandle {
+            snapshots: watch::channel(config.clone()).0,
+            changes: self.registry.changes.clone(),
+            config: Arc::new(RwLock::new(config)),
+            name: self.registry.display_name(&path),
+            file_path: path,
+            config_dir: self.config_dir.clone(),
+            debounce: debounce_write,
+            sender: self.sender.clone(),
+            blocked: Arc::new(AtomicBool::new(error.is_some())),
+            stamp,
+        };
 
-        if let Err(error) = file.read_to_string(&mut toml_config) {
-            error!(
-                "Failed to read config file {}, falling back to default: {:?}",
-                path.to_string_lossy(),
-                error
-            );
-            return T::default();
+        if let Some(error) = error {
+            let preserved_path = preserve_broken_file(&handle.file_path);
+            self.registry
+                .report_issue(handle.issue(error, preserved_path));
         }
 
-        match toml::from_str(&toml_config) {
-            Ok(config) => config,
-            Err(error) => {
-                error!(
-                    "Failed to parse config file {}, falling back to default: {:?}",
-                    path.to_string_lossy(),
-                    error
-                );
-                T::default()
-            }
-        }
+        self.registry.register(Arc::new(handle.clone()));
+        handle
     }
 }
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use keyring::Entry;
+use log::warn;
+use tokio::sync::broadcast;
+use tokio::task::spawn_blocking;
+
+const SERVICE: &str = "vrc-osc-manager";
+const NONCE_SIZE: usize = 12;
+
+/// Writes a file readable by the current user only, replacing it atomically.
+fn write_private(path: &Path, content: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+/// Fallback storage for systems without a secret store.
+///
+/// The encryption key is kept apart from the config directory, so that copies of the latter don't reveal anything.
+struct EncryptedFile {
+    path: PathBuf,
+    key_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl EncryptedFile {
+    fn cipher(&self, create: bool) -> anyhow::Result<Option<ChaCha20Poly1305>> {
+        match fs::read(&self.key_path) {
+            Ok(key) if key.len() == 32 => Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&key)))),
+            Ok(_) => bail!("Invalid key file {}", self.key_path.to_string_lossy()),
+            Err(error) if error.kind() == ErrorKind::NotFound && create => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&self.key_path, &key)?;
+                Ok(Some(ChaCha20Poly1305::new(&key)))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn load(&self) -> anyhow::Result<BTreeMap<String, String>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let cipher = self.cipher(false)?.with_context(|| {
+            format!(
+                "Key for {} is missing, secrets can not be decrypted",
+                self.path.to_string_lossy()
+            )
+        })?;
+
+        if content.len() < NONCE_SIZE {
+            bail!("Secrets file {} is truncated", self.path.to_string_lossy());
+        }
+
+        let (nonce, ciphertext) = content.split_at(NONCE_SIZE);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Could not decrypt {}", self.path.to_string_lossy()))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> anyhow::Result<()> {
+        let cipher = self.cipher(true)?.context("Could not create key file")?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, serde_json::to_vec(secrets)?.as_slice())
+            .map_err(|_| anyhow!("Could not encrypt secrets"))?;
+
+        let mut content = nonce.to_vec();
+        content.extend(ciphertext);
+        write_private(&self.path, &content)
+    }
+
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.load()?.remove(name))
+    }
+
+    fn set(&self, name: &str, value: Option<&str>) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+
+        let changed = match value {
+            Some(value) => {
+                secrets
+                    .insert(name.to_string(), value.to_string())
+                    .as_deref()
+                    != Some(value)
+            }
+            None => secrets.remove(name).is_some(),
+        };
+
+        if changed {
+            self.save(&secrets)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct SecretStore {
+    fallback: EncryptedFile,
+    /// Whether the last call to the system secret store failed, so that its failures are logged only once in a row.
+    keyring_failing: AtomicBool,
+    changes: broadcast::Sender<String>,
+}
+
+/// Credentials which are kept out of config files.
+///
+/// Secrets live in the system secret store, e.g. the freedesktop Secret Service on Linux. When that fails, they are
+/// written to an encrypted file instead. Values in that file take precedence, as they are removed from it whenever the
+/// secret store accepts a value again. A removal which the secret store refused is kept in the file as an empty value.
+#[derive(Clone)]
+pub struct Secrets {
+    scope: Option<&'static str>,
+    store: Arc<SecretStore>,
+}
+
+impl Secrets {
+    pub fn new(path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            scope: None,
+            store: Arc::new(SecretStore {
+                fallback: EncryptedFile {
+                    path,
+                    key_path,
+                    lock: Mutex::new(()),
+                },
+                keyring_failing: AtomicBool::new(false),
+                changes: broadcast::channel(8).0,
+            }),
+        }
+    }
+
+    /// Returns secrets whose names are prefixed with the given scope, e.g. a plugin ID.
+    pub fn scoped(&self, scope: &'static str) -> Self {
+        Self {
+            scope: Some(scope),
+            store: self.store.clone(),
+        }
+    }
+
+    /// Subscribes to the full names of changed secrets, which can be matched with [`Secrets::is_named`].
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<String> {
+        self.store.changes.subscribe()
+    }
+
+    /// Whether the full name of a changed secret refers to the given secret within the scope.
+    pub fn is_named(&self, full_name: &str, name: &str) -> bool {
+        self.full_name(name) == full_name
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        match self.scope {
+            Some(scope) => format!("{}.{}", scope, name),
+            None => name.to_string(),
+        }
+    }
+
+    fn keyring_failed(&self, error: keyring::Error) {
+        if !self.store.keyring_failing.swap(true, Ordering::SeqCst) {
+            warn!(
+                "System secret store not available, falling back to an encrypted file: {}",
+                error
+            );
+        }
+    }
+
+    fn keyring_succeeded(&self) {
+        self.store.keyring_failing.store(false, Ordering::SeqCst);
+    }
+
+    fn keyring_entry(&self, name: &str) -> Option<Entry> {
+        match Entry::new(SERVICE, name) {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                self.keyring_failed(error);
+                None
+            }
+        }
+    }
+
+    /// Must not be called from within the async runtime.
+    pub fn blocking_get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let name = self.full_name(name);
+
+        let fallback_error = match self.store.fallback.get(&name) {
+            Ok(Some(value)) => return Ok(Some(value).filter(|value| !value.is_empty())),
+            Ok(None) => None,
+            Err(error) => Some(error),
+        };
+
+        if let Some(entry) = self.keyring_entry(&name) {
+            match entry.get_password() {
+                Ok(value) => {
+                    self.keyring_succeeded();
+                    return Ok(Some(value));
+                }
+                Err(keyring::Error::NoEntry) => self.keyring_succeeded(),
+                Err(error) => self.keyring_failed(error),
+            }
+        }
+
+        match fallback_error {
+            Some(error) => Err(error),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a secret, an empty value removes it.
+    ///
+    /// Must not be called from within the async runtime, use [`Secrets::set`] there.
+    pub fn blocking_set(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        let name = self.full_name(name);
+        self.store_value(&name, value)?;
+        let _ = self.store.changes.send(name);
+
+        Ok(())
+    }
+
+    fn store_value(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        if let Some(entry) = self.keyring_entry(name) {
+            let result = match value.is_empty() {
+                true => match entry.delete_credential() {
+                    Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                    Err(error) => Err(error),
+                },
+                false => entry.set_password(value),
+            };
+
+            match result {
+                Ok(()) => {
+                    self.keyring_succeeded();
+
+                    // Make sure that no stale copy is left behind from times the secret store failed. The file may not
+                    // be readable, e.g. when the config directory was copied without the key, which is fine to ignore.
+                    if let Err(error) = self.store.fallback.set(name, None) {
+                        warn!(
+                            "Failed to remove {} from the encrypted file: {:#}",
+                            name, error
+                        );
+                    }
+
+                    return Ok(());
+                }
+                Err(error) => self.keyring_failed(error),
+            }
+        }
+
+        // An empty value is kept rather than removed, so that it overrides what the secret store still holds.
+        self.store.fallback.set(name, Some(value))
+    }
+
+    pub async fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let secrets = self.clone();
+        let name = name.to_string();
+
+        spawn_blocking(move || secrets.blocking_get(&name)).await?
+    }
+
+    pub async fn set(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        let secrets = self.clone();
+        let name = name.to_string();
+        let value = value.to_string();
+
+        spawn_blocking(move || secrets.blocking_set(&name, &value)).await?
+    }
+}
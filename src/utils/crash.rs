@@ -0,0 +1,90 @@
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use chrono::Local;
+use log::error;
+use sysinfo::System;
+
+use crate::VERSION;
+
+/// Report written by the last panic, so that recovering from it can point to the report instead of writing another.
+static LAST_PANIC_REPORT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Writes a crash report with the given details into the logs directory and returns its path.
+pub fn write_report(logs_dir: &Path, summary: &str, details: &str) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(logs_dir)?;
+
+    let path = logs_dir.join(format!(
+        "crash-{}.txt",
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    let content = format!(
+        "{}\n\nVersion: {}\nOS: {}\nArchitecture: {}\nTime: {}\n\n{}\n",
+        summary,
+        VERSION,
+        System::long_os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        std::env::consts::ARCH,
+        Local::now().to_rfc3339(),
+        details
+    );
+    fs::write(&path, content)?;
+
+    Ok(path)
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown panic payload".to_string());
+
+    match info.location() {
+        Some(location) => format!("{} at {}", message, location),
+        None => message,
+    }
+}
+
+/// Writes a crash report for every panic, in addition to what the default hook prints to stderr.
+pub fn install_panic_hook(logs_dir: PathBuf) {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let thread = thread::current();
+        let summary = format!(
+            "Thread '{}' panicked: {}",
+            thread.name().unwrap_or("<unnamed>"),
+            panic_message(info)
+        );
+        let details = format!("Backtrace:\n{}", Backtrace::force_capture());
+
+        match write_report(&logs_dir, &summary, &details) {
+            Ok(path) => {
+                error!(
+                    "{}, crash report written to {}",
+                    summary,
+                    path.to_string_lossy()
+                );
+                *LAST_PANIC_REPORT
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner()) = Some(path);
+            }
+            Err(error) => error!("{}, failed to write crash report: {:#}", summary, error),
+        }
+    }));
+}
+
+/// Returns the report written by the most recent panic, if it was not taken yet.
+pub fn take_panic_report() -> Option<PathBuf> {
+    LAST_PANIC_REPORT
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .take()
+}
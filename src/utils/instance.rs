@@ -0,0 +1,208 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::{GenericNamespaced, ListenerOptions, Name};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::tasks::orchestrate::AppEvent;
+use crate::tasks::recorder::RecorderCommand;
+
+/// How long a restarted instance waits for the previous one to exit.
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Request sent by a second launch to the running instance, one JSON object per connection.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InstanceRequest {
+    ShowWindow,
+    Record { path: Option<PathBuf> },
+    Replay { path: PathBuf, speed: f32 },
+}
+
+impl InstanceRequest {
+    fn app_event(self) -> AppEvent {
+        match self {
+            InstanceRequest::ShowWindow => AppEvent::AppWindowRequested,
+            InstanceRequest::Record { path } => {
+                AppEvent::RecorderRequested(RecorderCommand::StartRecording(path))
+            }
+            InstanceRequest::Replay { path, speed } => {
+                AppEvent::RecorderRequested(RecorderCommand::StartReplay { path, speed })
+            }
+        }
+    }
+}
+
+/// Returns the per-user socket name, which is a file in the runtime directory where available.
+fn socket_name() -> io::Result<Name<'static>> {
+    #[cfg(unix)]
+    {
+        use interprocess::local_socket::GenericFilePath;
+
+        if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())
+        {
+            return PathBuf::from(runtime_dir)
+                .join("vrc-osc-manager.sock")
+                .to_fs_name::<GenericFilePath>();
+        }
+    }
+
+    format!("vrc-osc-manager-{}.sock", user_id()).to_ns_name::<GenericNamespaced>()
+}
+
+/// Returns the lock file which serializes claiming the socket between instances launched at the same time.
+fn lock_path() -> PathBuf {
+    #[cfg(unix)]
+    {
+        if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())
+        {
+            return PathBuf::from(runtime_dir).join("vrc-osc-manager.lock");
+        }
+    }
+
+    std::env::temp_dir().join(format!("vrc-osc-manager-{}.lock", user_id()))
+}
+
+#[cfg(unix)]
+fn user_id() -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata("/proc/self")
+        .map(|metadata| metadata.uid().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn user_id() -> String {
+    std::env::var("USERNAME").unwrap_or_default()
+}
+
+fn send(stream: LocalSocketStream, request: &InstanceRequest) -> anyhow::Result<()> {
+    stream.set_recv_timeout(Some(IO_TIMEOUT))?;
+    stream.set_send_timeout(Some(IO_TIMEOUT))?;
+
+    let mut stream = BufReader::new(stream);
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.get_mut().write_all(line.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_line(&mut response)?;
+
+    if response.trim() != "ok" {
+        bail!("Running instance rejected the request: {}", response.trim());
+    }
+
+    Ok(())
+}
+
+/// Claims the single instance socket, or hands the request over to the instance holding it.
+///
+/// Returns `None` if another instance is running. With `takeover`, waits for the running instance to exit instead,
+/// which is used when restarting into an update.
+pub fn claim(
+    request: Option<&InstanceRequest>,
+    takeover: bool,
+) -> anyhow::Result<Option<LocalSocketListener>> {
+    let name = socket_name()?;
+    let lock_path = lock_path();
+    let deadline = Instant::now() + TAKEOVER_TIMEOUT;
+
+    loop {
+        // Held until the socket is bound, so that two launches cannot both replace the socket file and both run.
+        let lock = File::create(&lock_path)?;
+        lock.lock()?;
+
+        if let Ok(stream) = LocalSocketStream::connect(name.borrow()) {
+            if !takeover {
+                if let Some(request) = request {
+                    send(stream, request)?;
+                }
+
+                return Ok(None);
+            }
+
+            if Instant::now() >= deadline {
+                bail!("Previous instance did not exit in time");
+            }
+
+            drop(stream);
+            drop(lock);
+            thread::sleep(RETRY_INTERVAL);
+            continue;
+        }
+
+        // Nobody answered, so a socket file left behind by a crashed instance can safely be replaced.
+        match ListenerOptions::new()
+            .name(name.borrow())
+            .try_overwrite(true)
+            .create_sync()
+        {
+            Ok(listener) => return Ok(Some(listener)),
+            Err(error) if error.kind() == io::ErrorKind::AddrInUse && Instant::now() < deadline => {
+                drop(lock);
+                thread::sleep(RETRY_INTERVAL);
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+fn handle_connection(
+    stream: LocalSocketStream,
+    app_event_tx: &mpsc::Sender<AppEvent>,
+) -> anyhow::Result<()> {
+    stream.set_recv_timeout(Some(IO_TIMEOUT))?;
+    stream.set_send_timeout(Some(IO_TIMEOUT))?;
+
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    stream.read_line(&mut line)?;
+
+    if line.trim().is_empty() {
+        // Connections without a request only check whether this instance is still running.
+        return Ok(());
+    }
+
+    let request: InstanceRequest = serde_json::from_str(&line)?;
+    debug!("Received request from another instance: {:?}", request);
+    app_event_tx.blocking_send(request.app_event())?;
+    stream.get_mut().write_all(b"ok\n")?;
+
+    Ok(())
+}
+
+/// Accepts requests from later launches until the application exits.
+///
+/// Accepting blocks, so this runs on a detached thread, which keeps it from holding up the shutdown of the runtime.
+pub fn serve(listener: LocalSocketListener, app_event_tx: mpsc::Sender<AppEvent>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(anyhow::Error::from)
+                .and_then(|stream| handle_connection(stream, &app_event_tx));
+
+            if let Err(error) = result {
+                warn!(
+                    "Failed to handle request from another instance: {:#}",
+                    error
+                );
+            }
+
+            if app_event_tx.is_closed() {
+                break;
+            }
+        }
+
+        info!("Stopped accepting requests from other instances");
+    });
+}
@@ -0,0 +1,65 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use flexi_logger::{DeferredNow, LogSpecification, Record};
+
+use crate::config::LoggingConfig;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+const TIMESTAMP_LENGTH: usize = 23;
+
+/// Format of the log files, which [`parse_line`] has to be able to read back.
+pub fn file_format(
+    w: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    write!(
+        w,
+        "{} {:<5} [{}] {}",
+        now.format(TIMESTAMP_FORMAT),
+        record.level(),
+        record.module_path().unwrap_or("<unnamed>"),
+        record.args()
+    )
+}
+
+pub struct LogLine {
+    pub timestamp: String,
+    pub level: log::Level,
+    pub module: String,
+    pub message: String,
+}
+
+/// Parses a line written with [`file_format`], returns `None` for continuation lines of multi-line messages.
+pub fn parse_line(line: &str) -> Option<LogLine> {
+    let timestamp = line.get(..TIMESTAMP_LENGTH)?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+
+    let (level, rest) = line[TIMESTAMP_LENGTH..].trim_start().split_once(' ')?;
+    let level = log::Level::from_str(level).ok()?;
+    let (module, message) = rest.trim_start().strip_prefix('[')?.split_once("] ")?;
+
+    Some(LogLine {
+        timestamp: timestamp.to_string(),
+        level,
+        module: module.to_string(),
+        message: message.to_string(),
+    })
+}
+
+/// Builds the log specification for the given config. Dependencies only log errors.
+pub fn log_specification(config: &LoggingConfig) -> anyhow::Result<LogSpecification> {
+    let mut spec = format!(
+        "error, {}={}",
+        env!("CARGO_CRATE_NAME"),
+        config.level.as_str()
+    );
+
+    for (module, level) in &config.modules {
+        spec.push_str(&format!(", {}={}", module, level.as_str()));
+    }
+
+    Ok(LogSpecification::parse(&spec)?)
+}
@@ -1 +1,6 @@
 pub mod config;
+pub mod crash;
+pub mod instance;
+pub mod logging;
+pub mod osc;
+pub mod self_update;
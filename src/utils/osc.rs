@@ -0,0 +1,25 @@
+use async_osc::OscType;
+
+pub fn format_argument(argument: &OscType) -> String {
+    match argument {
+        OscType::Int(value) => format!("i:{}", value),
+        OscType::Long(value) => format!("h:{}", value),
+        OscType::Float(value) => format!("f:{}", value),
+        OscType::Double(value) => format!("d:{}", value),
+        OscType::Bool(value) => format!("b:{}", value),
+        OscType::String(value) => format!("s:{:?}", value),
+        OscType::Char(value) => format!("c:{:?}", value),
+        OscType::Blob(value) => format!("blob:{} bytes", value.len()),
+        OscType::Nil => "nil".to_string(),
+        OscType::Inf => "inf".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn format_arguments(arguments: &[OscType]) -> String {
+    arguments
+        .iter()
+        .map(format_argument)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
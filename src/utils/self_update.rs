@@ -0,0 +1,193 @@
+use std::fs as std_fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Context};
+use log::{debug, info};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Set when the service manager should start the installed update once the application has shut down.
+static RESTART_ON_EXIT: AtomicBool = AtomicBool::new(false);
+
+pub fn request_restart_on_exit() {
+    RESTART_ON_EXIT.store(true, Ordering::SeqCst);
+}
+
+pub fn restart_on_exit_requested() -> bool {
+    RESTART_ON_EXIT.load(Ordering::SeqCst)
+}
+
+/// Name of the release asset containing the executable for this platform.
+#[cfg(target_os = "linux")]
+pub const ASSET_NAME: &str = "vrc-osc-manager";
+#[cfg(target_os = "windows")]
+pub const ASSET_NAME: &str = "vrc-osc-manager.exe";
+
+/// Release asset listing the SHA-256 checksums of all other assets, in the format of `sha256sum`.
+pub const CHECKSUMS_ASSET_NAME: &str = "SHA256SUMS";
+
+/// Looks up the checksum of an asset in the contents of a checksums file.
+pub fn find_checksum(checksums: &str, asset_name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (checksum, name) = line.trim().split_once(char::is_whitespace)?;
+        // Binary mode entries are prefixed with an asterisk.
+        let name = name.trim_start().trim_start_matches('*');
+
+        (name == asset_name).then(|| checksum.to_ascii_lowercase())
+    })
+}
+
+/// Downloads a file, continuing a previous partial download at the same path if the server supports it.
+pub async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let offset = match fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    let mut request = client.get(url);
+
+    if offset > 0 {
+        debug!("Resuming download of {} at {} bytes", url, offset);
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+
+    let mut response = request.send().await?;
+
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial download is already complete, the checksum decides whether it can be used.
+        return Ok(());
+    }
+
+    let response_status = response.status();
+    response = response.error_for_status()?;
+
+    let resumed = response_status == StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { offset } else { 0 };
+    let total = response.content_length().map(|length| length + downloaded);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(path)
+        .await?;
+
+    progress(downloaded, total);
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        progress(downloaded, total);
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
+
+pub async fn sha256(path: &Path) -> anyhow::Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Path the executable replaced by an update is kept at.
+fn previous_path(exe_path: &Path) -> PathBuf {
+    let mut file_name = exe_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".old");
+    exe_path.with_file_name(file_name)
+}
+
+fn staged_path(exe_path: &Path) -> PathBuf {
+    let mut file_name = exe_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".new");
+    exe_path.with_file_name(file_name)
+}
+
+/// Replaces the executable at the given path with a verified download, keeping the current one for rollback.
+///
+/// Both platforms allow renaming a running executable, so the running process keeps working until it is restarted.
+pub fn install(exe_path: &Path, download_path: &Path) -> anyhow::Result<()> {
+    let staged_path = staged_path(exe_path);
+    let previous_path = previous_path(exe_path);
+
+    // The download may live on another file system, so copy it next to the executable first for an atomic rename.
+    std_fs::copy(download_path, &staged_path)
+        .with_context(|| format!("Failed to copy update to {}", staged_path.to_string_lossy()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std_fs::set_permissions(&staged_path, std_fs::Permissions::from_mode(0o755))?;
+    }
+
+    if previous_path.exists() {
+        std_fs::remove_file(&previous_path)?;
+    }
+
+    std_fs::rename(exe_path, &previous_path).context("Failed to move current executable aside")?;
+
+    if let Err(error) = std_fs::rename(&staged_path, exe_path) {
+        std_fs::rename(&previous_path, exe_path)?;
+        let _ = std_fs::remove_file(&staged_path);
+        return Err(error).context("Failed to move update into place");
+    }
+
+    info!(
+        "Installed update to {}, previous version kept at {}",
+        exe_path.to_string_lossy(),
+        previous_path.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Swaps the executable with the one kept by the last update, so that a rollback can be undone the same way.
+pub fn rollback(exe_path: &Path) -> anyhow::Result<()> {
+    let previous_path = previous_path(exe_path);
+    let staged_path = staged_path(exe_path);
+
+    if !previous_path.exists() {
+        bail!(
+            "No previous version found at {}",
+            previous_path.to_string_lossy()
+        );
+    }
+
+    std_fs::rename(exe_path, &staged_path)?;
+    std_fs::rename(&previous_path, exe_path)?;
+    std_fs::rename(&staged_path, &previous_path)?;
+
+    info!(
+        "Rolled back to the previous version, the replaced one is kept at {}",
+        previous_path.to_string_lossy()
+    );
+
+    Ok(())
+}